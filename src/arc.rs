@@ -0,0 +1,151 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An `A`tomically `r`eference `c`ounted container: [`crate::rc::Rc`] with a
+//! reference count that can be shared between threads.
+//!
+//! The only differences from `Rc` are in the count:
+//!
+//! - Cloning only needs the increment itself to be atomic, so it uses
+//!   `Relaxed` ordering. Nothing else is published by it: the clone was made
+//!   from a live reference, so the value is already visible to this thread.
+//!   A count past `isize::MAX` can only come from leaking clones (say with
+//!   `mem::forget`), and left alone would eventually wrap to zero and free the
+//!   value while it is still shared, so the process aborts instead.
+//! - Dropping decrements with `Release`, so that every use of the value through
+//!   this reference happens before the decrement. Whichever thread brings the
+//!   count to zero then needs an `Acquire` fence before freeing the value, to
+//!   see all of those uses from the other threads. This is the same protocol
+//!   as `std::sync::Arc`; see [the `Arc` chapter of the nomicon][1].
+//!
+//! [1]: https://doc.rust-lang.org/nomicon/arc-mutex/arc.html
+use std::{
+    marker::PhantomData,
+    ops::Deref,
+    ptr::NonNull,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+/// `Shared` is an inner container which holds the reference count
+struct Shared<T> {
+    value: T,
+    refcount: AtomicUsize,
+}
+
+/// An atomically reference counted container for `T`
+pub struct Arc<T> {
+    inner: NonNull<Shared<T>>,
+    _marker: PhantomData<Shared<T>>,
+}
+
+/// An `Arc<T>` hands out `&T` on whichever thread holds it, and may drop the
+/// `T` on any thread, so `T` must be both `Send` and `Sync`.
+unsafe impl<T: Send + Sync> Send for Arc<T> {}
+unsafe impl<T: Send + Sync> Sync for Arc<T> {}
+
+impl<T> Arc<T> {
+    pub fn from(value: T) -> Self {
+        let inner = Box::new(Shared {
+            value,
+            refcount: AtomicUsize::new(1),
+        });
+        Arc {
+            // SAFETY: `Box::into_raw` never returns a null pointer.
+            inner: unsafe { NonNull::new_unchecked(Box::into_raw(inner)) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Arc<T> {
+    fn clone(&self) -> Self {
+        // SAFETY: `self` holds a count, so `inner` has not been freed.
+        let inner = unsafe { self.inner.as_ref() };
+        let old = inner.refcount.fetch_add(1, Ordering::Relaxed);
+        if old > isize::MAX as usize {
+            // far too many leaked clones: panicking would unwind into code
+            // that may clone again, before the count could wrap
+            std::process::abort();
+        }
+        Arc {
+            inner: self.inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for Arc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `self` holds a count, so `inner` has not been freed, and
+        // only shared references to it are ever handed out.
+        &unsafe { self.inner.as_ref() }.value
+    }
+}
+
+impl<T> Drop for Arc<T> {
+    fn drop(&mut self) {
+        // SAFETY: `self` still holds its count, so `inner` has not been freed.
+        let inner = unsafe { self.inner.as_ref() };
+        if inner.refcount.fetch_sub(1, Ordering::Release) != 1 {
+            // there are other shared refs already given out
+            return;
+        }
+        // this was the last ref
+        atomic::fence(Ordering::Acquire);
+        // SAFETY: the count was one, so no other `Arc` is left to use `inner`,
+        // and it came from `Box::into_raw` in `Arc::from`.
+        let _ = unsafe { Box::from_raw(self.inner.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Counts its own drops
+    struct Canary<'a>(&'a AtomicUsize);
+
+    impl Drop for Canary<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn drops_the_value_once_after_the_last_clone() {
+        let drops = AtomicUsize::new(0);
+        let arc = Arc::from(Canary(&drops));
+        thread::scope(|s| {
+            for _ in 0..8 {
+                let arc = arc.clone();
+                s.spawn(move || {
+                    let clones: Vec<_> = (0..100).map(|_| arc.clone()).collect();
+                    drop(clones);
+                });
+            }
+        });
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(arc);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn derefs_to_the_value() {
+        let a = Arc::from(vec![1, 2, 3]);
+        let b = a.clone();
+        assert_eq!(b.len(), 3);
+        assert!(std::ptr::eq(&*a, &*b));
+    }
+}
@@ -0,0 +1,41 @@
+use std::{cell::Cell, rc::Rc};
+
+/// A basic structure used to evaluate different sorting algorithms based on
+/// the number of comparisons made. This does _not_ match up 1:1 with `O()`
+/// complexity analysis, but it is nonetheless a good performance heuristic.
+#[derive(Clone)]
+pub struct SortEvaluator<T> {
+    pub t: T,
+    pub comps: Rc<Cell<usize>>,
+}
+
+// The long set of boilerplate that follow implement the required comparison
+// trait methods for our `SortEvaluator`
+impl<T: PartialEq> PartialEq for SortEvaluator<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.comps.set(self.comps.get() + 1);
+        self.t == other.t
+    }
+}
+
+impl<T> PartialOrd for SortEvaluator<T>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.comps.set(self.comps.get() + 1);
+        self.t.partial_cmp(&other.t)
+    }
+}
+
+impl<T> Ord for SortEvaluator<T>
+where
+    T: Ord,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.comps.set(self.comps.get() + 1);
+        self.t.cmp(&other.t)
+    }
+}
+
+impl<T: Eq> Eq for SortEvaluator<T> {}
@@ -0,0 +1,93 @@
+use rand::prelude::*;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+    time::Instant,
+};
+use tinystd::{
+    hash::{FnvBuildHasher, FxBuildHasher, SipState},
+    hashmap::HashMap,
+};
+
+/// Fills a map with `keys` under `hash_builder` and prints one row describing
+/// how the keys ended up spread over its bins.
+fn run_bench<K, S>(hasher: &str, key_set: &str, keys: &[K], max_load_factor: f64, hash_builder: S)
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher,
+{
+    let mut map = HashMap::with_hasher(hash_builder);
+    map.set_max_load_factor(max_load_factor);
+
+    let time = Instant::now();
+    for k in keys {
+        map.insert(k.clone(), ());
+    }
+    let elapsed = time.elapsed().as_secs_f64();
+
+    let stats = map.stats();
+    let histogram: Vec<_> = stats.histogram.iter().map(usize::to_string).collect();
+    println!(
+        "{},{},{},{},{},{:.4},{},{},{:.4},{},{}",
+        hasher,
+        key_set,
+        stats.len,
+        max_load_factor,
+        stats.bins,
+        stats.load_factor,
+        stats.occupied_bins,
+        stats.max_chain,
+        stats.mean_chain,
+        histogram.join("|"),
+        elapsed
+    );
+}
+
+/// Runs every hasher over the same keys
+fn run_hashers<K: Hash + Eq + Clone>(key_set: &str, keys: &[K], max_load_factor: f64) {
+    run_bench("std", key_set, keys, max_load_factor, RandomState::new());
+    run_bench("sip13", key_set, keys, max_load_factor, SipState::new());
+    run_bench(
+        "fx",
+        key_set,
+        keys,
+        max_load_factor,
+        FxBuildHasher::default(),
+    );
+    run_bench(
+        "fnv1a",
+        key_set,
+        keys,
+        max_load_factor,
+        FnvBuildHasher::default(),
+    );
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut rand = rand::thread_rng();
+
+    // the histogram is `|`-separated: column i counts the bins holding i pairs
+    println!("hasher,keys,n,max_load_factor,bins,load_factor,occupied,max_chain,mean_chain,histogram,time"); // header
+    for &n in &[1000, 10_000, 100_000] {
+        // realistic key sets, each of which trips up some weak hasher:
+        // - dense ids have no entropy in their high bits
+        // - aligned addresses have none in their low bits either
+        // - ids in strings share long prefixes and differ only at the end
+        let sequential: Vec<u64> = (0..n).collect();
+        let mut random: Vec<u64> = (0..n).map(|_| rand.gen()).collect();
+        random.sort_unstable();
+        random.dedup();
+        let aligned: Vec<u64> = (0..n).map(|i| 0x7f00_0000_0000 + i * 4096).collect();
+        let names: Vec<String> = (0..n).map(|i| format!("user:{}", i)).collect();
+
+        // data for each row
+        for &max_load_factor in &[0.5, 0.75, 1.0, 2.0] {
+            run_hashers("sequential", &sequential, max_load_factor);
+            run_hashers("random", &random, max_load_factor);
+            run_hashers("aligned", &aligned, max_load_factor);
+            run_hashers("names", &names, max_load_factor);
+        }
+    }
+
+    Ok(())
+}
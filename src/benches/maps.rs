@@ -0,0 +1,86 @@
+use rand::prelude::*;
+use std::{collections::HashMap as StdMap, time::Instant};
+use tinystd::hashmap::{CuckooMap, HashMap, OpenMap, SwissMap};
+
+/// Just enough of a map to benchmark, so every implementation runs the exact
+/// same workloads
+trait Map: Default {
+    fn insert(&mut self, key: u64, value: u64) -> Option<u64>;
+    fn get(&self, key: &u64) -> Option<&u64>;
+    fn remove(&mut self, key: &u64) -> Option<u64>;
+}
+
+macro_rules! impl_map {
+    ($($map:ident),*) => {$(
+        impl Map for $map<u64, u64> {
+            fn insert(&mut self, key: u64, value: u64) -> Option<u64> {
+                $map::insert(self, key, value)
+            }
+            fn get(&self, key: &u64) -> Option<&u64> {
+                $map::get(self, key)
+            }
+            fn remove(&mut self, key: &u64) -> Option<u64> {
+                $map::remove(self, key)
+            }
+        }
+    )*};
+}
+
+impl_map!(StdMap, HashMap, OpenMap, SwissMap, CuckooMap);
+
+// The benchmarking begins below.
+/// Times each workload against a fresh map, printing one row per workload.
+/// `hits` are all in the map after the insert phase and `misses` never are.
+fn run_bench<M: Map>(name: &str, hits: &[u64], misses: &[u64]) {
+    let n = hits.len();
+    let mut map = M::default();
+
+    let time = Instant::now();
+    for &k in hits {
+        map.insert(k, k);
+    }
+    println!("{},insert,{},{}", name, n, time.elapsed().as_secs_f64());
+
+    let time = Instant::now();
+    for k in hits {
+        assert!(std::hint::black_box(map.get(k)).is_some());
+    }
+    println!("{},lookup,{},{}", name, n, time.elapsed().as_secs_f64());
+
+    let time = Instant::now();
+    for k in misses {
+        assert!(std::hint::black_box(map.get(k)).is_none());
+    }
+    println!("{},miss,{},{}", name, n, time.elapsed().as_secs_f64());
+
+    let time = Instant::now();
+    for k in hits {
+        assert!(map.remove(k).is_some());
+    }
+    println!("{},delete,{},{}", name, n, time.elapsed().as_secs_f64());
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut rand = rand::thread_rng();
+
+    println!("map,workload,n,time"); // header
+    for &n in &[10, 100, 1000, 10_000, 100_000, 1_000_000] {
+        for _ in 0..10 {
+            // even keys are inserted and odd keys are looked up as misses
+            let mut hits: Vec<u64> = (0..n).map(|_| rand.gen::<u64>() & !1).collect();
+            hits.sort_unstable();
+            hits.dedup();
+            hits.shuffle(&mut rand);
+            let misses: Vec<u64> = (0..hits.len()).map(|_| rand.gen::<u64>() | 1).collect();
+
+            // data for each row
+            run_bench::<StdMap<_, _>>("std", &hits, &misses);
+            run_bench::<HashMap<_, _>>("chaining", &hits, &misses);
+            run_bench::<OpenMap<_, _>>("robin-hood", &hits, &misses);
+            run_bench::<SwissMap<_, _>>("swiss", &hits, &misses);
+            run_bench::<CuckooMap<_, _>>("cuckoo", &hits, &misses);
+        }
+    }
+
+    Ok(())
+}
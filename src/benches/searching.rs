@@ -0,0 +1,86 @@
+mod evaluator;
+
+use evaluator::SortEvaluator;
+use rand::prelude::*;
+use std::{cell::Cell, rc::Rc};
+use tinystd::search::*;
+
+/// The number of keys looked up per row; each row reports the totals
+const LOOKUPS: usize = 1000;
+
+// Interpolation search needs to know where a key sits on the number line, which
+// (unlike a comparison) is not counted.
+impl tinystd::search::Numeric for SortEvaluator<usize> {
+    fn as_i128(&self) -> i128 {
+        self.t as i128
+    }
+}
+
+// The benchmarking begins below.
+fn run_bench<F>(search: F, keys: &[SortEvaluator<usize>], counter: &Cell<usize>) -> (usize, f64)
+where
+    F: Fn(&SortEvaluator<usize>) -> usize,
+{
+    counter.set(0);
+    let time = std::time::Instant::now();
+    for key in keys {
+        std::hint::black_box(search(key));
+    }
+    let took = time.elapsed();
+    (counter.get(), took.as_secs_f64())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut rand = rand::thread_rng();
+    let counter = Rc::new(Cell::new(0));
+    let wrap = |t| SortEvaluator {
+        t,
+        comps: Rc::clone(&counter),
+    };
+
+    println!("algorithm,n,comparisons,time"); // header
+    for &n in &[0, 1, 10, 100, 1000, 10_000, 100_000, 1_000_000] {
+        let mut raw: Vec<usize> = (0..n).map(|_| rand.gen_range(0..n.max(1) * 4)).collect();
+        raw.sort_unstable();
+        let values: Vec<_> = raw.iter().map(|&t| wrap(t)).collect();
+        let tree = Eytzinger::from_sorted(&values);
+
+        for _ in 0..10 {
+            // half of the keys are hits, the other half are (probably) misses
+            let keys: Vec<_> = (0..LOOKUPS)
+                .map(|i| match raw.choose(&mut rand) {
+                    Some(&t) if i % 2 == 0 => wrap(t),
+                    _ => wrap(rand.gen_range(0..n.max(1) * 4)),
+                })
+                .collect();
+
+            // data for each row
+            let took = run_bench(
+                |k| values.binary_search(k).unwrap_or_else(|i| i),
+                &keys,
+                &counter,
+            );
+            println!("std-binary,{},{},{}", n, took.0, took.1);
+            let took = run_bench(|k| lower_bound(&values, k), &keys, &counter);
+            println!("lower-bound,{},{},{}", n, took.0, took.1);
+            let took = run_bench(
+                |k| exponential_search(&values, k).unwrap_or_else(|i| i),
+                &keys,
+                &counter,
+            );
+            println!("exponential,{},{},{}", n, took.0, took.1);
+            let took = run_bench(
+                |k| interpolation_search(&values, k).unwrap_or_else(|i| i),
+                &keys,
+                &counter,
+            );
+            println!("interpolation,{},{},{}", n, took.0, took.1);
+            let took = run_bench(|k| branchless_lower_bound(&values, k), &keys, &counter);
+            println!("branchless,{},{},{}", n, took.0, took.1);
+            let took = run_bench(|k| tree.lower_bound(k), &keys, &counter);
+            println!("eytzinger,{},{},{}", n, took.0, took.1);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,397 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bounded caches, which evict pairs to stay within their capacity.
+//!
+//! Both caches pair a [`crate::hashmap::HashMap`] from keys to nodes with
+//! intrusive doubly linked lists through those nodes, so that every operation
+//! is *O(1)*. They differ in which pair goes first:
+//!
+//! - [`LruCache`] evicts the *least recently used* pair.
+//! - [`LfuCache`] evicts the *least frequently used* pair, breaking ties by
+//!   recency, as in [Shah, Mitra and Matani][1].
+//!
+//! By default each pair counts as one towards the capacity. A *weigher* can
+//! give pairs different weights instead, e.g. their size in bytes, and an
+//! *eviction listener* is told about each pair that is evicted.
+//!
+//! [`TtlMap`] bounds its pairs by age rather than by count: each one expires a
+//! while after it was inserted.
+//!
+//! ```
+//! use tinystd::cache::LruCache;
+//!
+//! let mut cache = LruCache::with_weigher(10, |_: &&str, v: &String| v.len());
+//! cache.put("a", "hello".to_string());
+//! cache.put("b", "world".to_string());
+//! cache.get("a"); // "a" is now the most recently used
+//! cache.put("c", "!".to_string()); // over 10 bytes, so "b" goes
+//! assert!(!cache.contains_key("b"));
+//! assert_eq!(cache.weight(), 6);
+//! assert_eq!(cache.stats().hits, 1);
+//! ```
+//!
+//! [1]: http://dhruvbird.com/lfu.pdf
+
+/// Defines the public methods that [`LruCache`] and [`LfuCache`] share, each
+/// forwarding to the cache's [`Core`]. The methods whose meaning depends on
+/// the eviction order (`put`, `get`, `get_mut` and `peek`) are written out by
+/// each cache instead.
+macro_rules! impl_cache {
+    ($cache:ident) => {
+        impl<K, V> $cache<K, V>
+        where
+            K: Eq + Hash + Clone,
+        {
+            /// Constructs an empty cache that holds at most `capacity` pairs.
+            ///
+            /// ## Panics
+            /// If `capacity` is zero.
+            pub fn new(capacity: usize) -> Self {
+                Self::with_weigher(capacity, |_, _| 1)
+            }
+
+            /// Constructs an empty cache that holds pairs up to a total weight
+            /// of `capacity`, as computed by `weigher`. A pair's weight is
+            /// computed once, when it is put in the cache.
+            ///
+            /// ## Panics
+            /// If `capacity` is zero.
+            pub fn with_weigher<F>(capacity: usize, weigher: F) -> Self
+            where
+                F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+            {
+                $cache {
+                    core: Core::new(capacity, Box::new(weigher)),
+                }
+            }
+
+            /// Calls `listener` with every pair evicted from now on, including
+            /// a pair too heavy to be put in the cache at all.
+            ///
+            /// ## Example:
+            /// ```
+            /// use std::sync::mpsc;
+            #[doc = concat!("use tinystd::cache::", stringify!($cache), ";")]
+            ///
+            /// let (tx, rx) = mpsc::channel();
+            #[doc = concat!(
+                                "let mut cache = ",
+                                stringify!($cache),
+                                "::new(2).on_evict(move |k, v| tx.send((k, v)).unwrap());"
+                            )]
+            /// cache.put(1, 'a');
+            /// cache.put(2, 'b');
+            /// cache.put(3, 'c');
+            /// assert_eq!(rx.try_recv(), Ok((1, 'a')));
+            /// ```
+            pub fn on_evict<F>(mut self, listener: F) -> Self
+            where
+                F: FnMut(K, V) + Send + 'static,
+            {
+                self.core.on_evict = Some(Box::new(listener));
+                self
+            }
+
+            /// The number of pairs in the cache
+            pub fn len(&self) -> usize {
+                self.core.map.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.core.map.is_empty()
+            }
+
+            /// The total weight the cache can hold
+            pub fn capacity(&self) -> usize {
+                self.core.capacity
+            }
+
+            /// The total weight of the pairs in the cache
+            pub fn weight(&self) -> usize {
+                self.core.weight
+            }
+
+            /// The cache's hit, miss and eviction counts
+            pub fn stats(&self) -> CacheStats {
+                self.core.stats
+            }
+
+            /// Changes the capacity, evicting pairs if the cache is now over it.
+            ///
+            /// ## Panics
+            /// If `capacity` is zero.
+            pub fn set_capacity(&mut self, capacity: usize) {
+                assert!(capacity > 0, "a cache needs a capacity of at least 1");
+                self.core.capacity = capacity;
+                self.core.make_room(0);
+            }
+
+            #[doc = concat!(
+                                "Returns `true` if the cache holds a value for `key`. Like [`",
+                                stringify!($cache),
+                                "::peek`], this doesn't count as a use."
+                            )]
+            pub fn contains_key<Q>(&self, key: &Q) -> bool
+            where
+                K: Borrow<Q>,
+                Q: Eq + Hash + ?Sized,
+            {
+                self.core.map.contains_key(key)
+            }
+
+            /// Removes the pair for `key`, returning its value. The eviction
+            /// listener is not called.
+            pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+            where
+                K: Borrow<Q>,
+                Q: Eq + Hash + ?Sized,
+            {
+                self.core.remove(key)
+            }
+
+            /// Removes every pair, without calling the eviction listener. The
+            /// statistics are kept.
+            pub fn clear(&mut self) {
+                self.core.clear();
+            }
+        }
+    };
+}
+
+pub mod lfu;
+mod list;
+pub mod lru;
+pub mod ttl;
+
+pub use lfu::LfuCache;
+pub use lru::LruCache;
+pub use ttl::{Clock, ManualClock, SystemClock, TtlMap};
+
+use crate::hashmap::HashMap;
+use list::Slab;
+use std::{borrow::Borrow, hash::Hash, mem};
+
+/// Computes the weight of a pair
+type Weigher<K, V> = Box<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+/// Called with each pair as it is evicted
+type EvictionListener<K, V> = Box<dyn FnMut(K, V) + Send>;
+
+/// Counts of what a cache has done since it was created
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found their key
+    pub hits: u64,
+    /// Lookups that didn't
+    pub misses: u64,
+    /// Pairs removed to make room. Pairs removed or replaced by the caller
+    /// don't count.
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// The fraction of lookups that were hits, or `0.0` before any lookups
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// A cache's eviction order: the lists that its pairs' nodes are linked into,
+/// and which node goes first. The nodes themselves live in the [`Core`]'s
+/// slab.
+trait Order: Default {
+    /// Links in node `i`, a pair that was just put in the cache.
+    fn push<T>(&mut self, nodes: &mut Slab<T>, i: usize);
+
+    /// Records a use of node `i`.
+    fn touch<T>(&mut self, nodes: &mut Slab<T>, i: usize);
+
+    /// Unlinks node `i`, which is about to be freed.
+    fn unlink<T>(&mut self, nodes: &mut Slab<T>, i: usize);
+
+    /// The node to evict next, if there are any
+    fn victim(&self) -> Option<usize>;
+}
+
+struct Item<K, V> {
+    key: K,
+    value: V,
+    weight: usize,
+}
+
+/// The bookkeeping that every cache does, whatever order it evicts in: a map
+/// from keys to nodes, the weights, the eviction listener and the statistics.
+struct Core<K, V, O> {
+    /// Where each key's node is in `items`
+    map: HashMap<K, usize>,
+    items: Slab<Item<K, V>>,
+    order: O,
+    capacity: usize,
+    weight: usize,
+    weigher: Weigher<K, V>,
+    on_evict: Option<EvictionListener<K, V>>,
+    stats: CacheStats,
+}
+
+impl<K, V, O> Core<K, V, O>
+where
+    K: Eq + Hash + Clone,
+    O: Order,
+{
+    fn new(capacity: usize, weigher: Weigher<K, V>) -> Self {
+        assert!(capacity > 0, "a cache needs a capacity of at least 1");
+        Core {
+            map: HashMap::new(),
+            items: Slab::default(),
+            order: O::default(),
+            capacity,
+            weight: 0,
+            weigher,
+            on_evict: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Puts a pair in the cache, evicting others as needed to make room. A
+    /// pair heavier than the whole capacity is turned away instead: it counts
+    /// as evicted, the listener is told about it, and any old value for its
+    /// key is removed, but every other pair stays.
+    fn put(&mut self, key: K, value: V) -> Option<V> {
+        let weight = (self.weigher)(&key, &value);
+        if weight > self.capacity {
+            let old = self.remove(&key);
+            self.evict(key, value);
+            return old;
+        }
+        if let Some(&i) = self.map.get(&key) {
+            let item = self.items.get_mut(i);
+            self.weight = self.weight - item.weight + weight;
+            item.weight = weight;
+            let old = mem::replace(&mut item.value, value);
+            self.order.touch(&mut self.items, i);
+            self.make_room(0);
+            return Some(old);
+        }
+        // evict first, so that the new pair can't be its own victim
+        self.make_room(weight);
+        let i = self.items.insert(Item {
+            key: key.clone(),
+            value,
+            weight,
+        });
+        self.order.push(&mut self.items, i);
+        self.map.insert(key, i);
+        self.weight += weight;
+        None
+    }
+
+    /// Finds the node for `key`, counting the use and the hit or miss
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.map.get(key) {
+            Some(&i) => {
+                self.stats.hits += 1;
+                self.order.touch(&mut self.items, i);
+                Some(i)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let &i = self.map.get(key)?;
+        Some(&self.items.get(i).value)
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.map.remove(key)?;
+        Some(self.take(i).value)
+    }
+
+    /// Removes and returns the next pair to be evicted, without calling the
+    /// eviction listener
+    fn pop(&mut self) -> Option<(K, V)> {
+        let i = self.order.victim()?;
+        let item = self.take(i);
+        self.map.remove(&item.key);
+        Some((item.key, item.value))
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.items.clear();
+        self.order = O::default();
+        self.weight = 0;
+    }
+
+    /// Unlinks and frees node `i`. The caller removes its key from the map.
+    fn take(&mut self, i: usize) -> Item<K, V> {
+        self.order.unlink(&mut self.items, i);
+        let item = self.items.take(i);
+        self.weight -= item.weight;
+        item
+    }
+
+    /// Evicts pairs in the cache's order until `incoming` more weight fits,
+    /// which it always does once the cache is empty
+    fn make_room(&mut self, incoming: usize) {
+        debug_assert!(incoming <= self.capacity);
+        while self.weight + incoming > self.capacity {
+            let (key, value) = self.pop().expect("an empty cache has room");
+            self.evict(key, value);
+        }
+    }
+
+    /// Counts an eviction and tells the listener about it
+    fn evict(&mut self, key: K, value: V) {
+        self.stats.evictions += 1;
+        if let Some(listener) = &mut self.on_evict {
+            listener(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_rate() {
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+        let stats = CacheStats {
+            hits: 3,
+            misses: 1,
+            evictions: 0,
+        };
+        assert_eq!(stats.hit_rate(), 0.75);
+    }
+}
@@ -0,0 +1,346 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A least frequently used cache with *O(1)* operations.
+//!
+//! Keeping the pairs sorted by use count would make every hit *O(log n)*.
+//! Instead, pairs with the same count share a *bucket*, and the buckets form a
+//! list in increasing order of count. A hit moves a pair from its bucket to
+//! the next one, which either has the count one higher already or is created
+//! right after it; a bucket that empties is removed. The victim is then always
+//! in the first bucket, and within a bucket pairs are kept by recency, so that
+//! ties go to the least recently used.
+use super::{
+    list::{List, Slab, NIL},
+    CacheStats, Core, Order,
+};
+use std::{
+    borrow::Borrow,
+    fmt::{self, Debug},
+    hash::Hash,
+};
+
+struct Bucket {
+    /// How many times each pair in the bucket has been used
+    count: u64,
+    /// Most recently used first
+    items: List,
+}
+
+#[derive(Default)]
+struct Frequency {
+    buckets: Slab<Bucket>,
+    /// Lowest count first
+    counts: List,
+    /// The bucket of each pair's node, indexed like the nodes
+    bucket_of: Vec<usize>,
+}
+
+impl Frequency {
+    fn new_bucket(&mut self, count: u64) -> usize {
+        self.buckets.insert(Bucket {
+            count,
+            items: List::default(),
+        })
+    }
+
+    /// How many times the pair in node `i` has been used
+    fn count(&self, i: usize) -> u64 {
+        self.buckets.get(self.bucket_of[i]).count
+    }
+
+    /// Removes bucket `b` if it no longer holds any pairs
+    fn drop_if_empty(&mut self, b: usize) {
+        if self.buckets.get(b).items.is_empty() {
+            self.buckets.unlink(&mut self.counts, b);
+            self.buckets.take(b);
+        }
+    }
+}
+
+impl Order for Frequency {
+    /// New pairs start in the bucket for a count of one
+    fn push<T>(&mut self, nodes: &mut Slab<T>, i: usize) {
+        let first = self.counts.head;
+        let bucket = if first != NIL && self.buckets.get(first).count == 1 {
+            first
+        } else {
+            let b = self.new_bucket(1);
+            self.buckets.push_front(&mut self.counts, b);
+            b
+        };
+        nodes.push_front(&mut self.buckets.get_mut(bucket).items, i);
+        if self.bucket_of.len() <= i {
+            self.bucket_of.resize(i + 1, NIL);
+        }
+        self.bucket_of[i] = bucket;
+    }
+
+    /// Moves node `i` to the bucket for one more use
+    fn touch<T>(&mut self, nodes: &mut Slab<T>, i: usize) {
+        let from = self.bucket_of[i];
+        let count = self.buckets.get(from).count + 1;
+        let next = self.buckets.next(from);
+        let to = if next != NIL && self.buckets.get(next).count == count {
+            next
+        } else {
+            let b = self.new_bucket(count);
+            self.buckets.insert_after(&mut self.counts, from, b);
+            b
+        };
+        nodes.unlink(&mut self.buckets.get_mut(from).items, i);
+        nodes.push_front(&mut self.buckets.get_mut(to).items, i);
+        self.bucket_of[i] = to;
+        self.drop_if_empty(from);
+    }
+
+    fn unlink<T>(&mut self, nodes: &mut Slab<T>, i: usize) {
+        let b = self.bucket_of[i];
+        nodes.unlink(&mut self.buckets.get_mut(b).items, i);
+        self.drop_if_empty(b);
+    }
+
+    /// The least recently used pair of the first bucket
+    fn victim(&self) -> Option<usize> {
+        if self.counts.is_empty() {
+            None
+        } else {
+            Some(self.buckets.get(self.counts.head).items.tail)
+        }
+    }
+}
+
+/// A cache that evicts the least frequently used pairs once their total
+/// weight exceeds its capacity. See the [module docs](self).
+///
+/// ## Example:
+/// ```
+/// use tinystd::cache::LfuCache;
+///
+/// let mut cache = LfuCache::new(2);
+/// cache.put("a", 1);
+/// cache.put("b", 2);
+/// cache.get("a");
+/// cache.get("b");
+/// cache.get("a");
+/// cache.put("c", 3); // "b" has been used less than "a"
+/// assert!(cache.contains_key("a"));
+/// assert!(!cache.contains_key("b"));
+/// ```
+pub struct LfuCache<K, V> {
+    core: Core<K, V, Frequency>,
+}
+
+impl_cache!(LfuCache);
+
+impl<K, V> LfuCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Puts `value` in the cache at `key`, evicting other pairs as needed to
+    /// make room. If the key was already present, its old value is replaced
+    /// and returned, and this counts as a use; otherwise the pair starts with
+    /// a count of one.
+    ///
+    /// A pair that is heavier than the whole capacity is not put in the cache:
+    /// it is evicted right away, along with any old value for its key, and no
+    /// other pair is touched.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        self.core.put(key, value)
+    }
+
+    /// Gets the value for `key`, counting a use. Counts towards the hits or
+    /// misses.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.core.lookup(key)?;
+        Some(&self.core.items.get(i).value)
+    }
+
+    /// Like [`LfuCache::get`], but the value can be changed in place. Its
+    /// weight is not recomputed.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.core.lookup(key)?;
+        Some(&mut self.core.items.get_mut(i).value)
+    }
+
+    /// Gets the value for `key` without counting a use, hit or miss.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.core.peek(key)
+    }
+
+    /// How many times the pair for `key` has been used, counting the `put`
+    /// that added it
+    pub fn frequency<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let &i = self.core.map.get(key)?;
+        Some(self.core.order.count(i))
+    }
+
+    /// Removes and returns the least frequently used pair, the least recently
+    /// used among ties. The eviction listener is not called.
+    pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+        self.core.pop()
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, least frequently used first
+impl<K, V> Debug for LfuCache<K, V>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Core { items, order, .. } = &self.core;
+        let mut map = f.debug_map();
+        for bucket in order.buckets.iter(&order.counts) {
+            for item in items.iter(&bucket.items) {
+                map.entry(&item.key, &item.value);
+            }
+        }
+        map.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn evicts_the_least_frequently_used() {
+        let mut cache = LfuCache::new(3);
+        for i in 0..3 {
+            cache.put(i, i);
+        }
+        for _ in 0..3 {
+            cache.get(&0);
+        }
+        cache.get(&2);
+        assert_eq!(cache.frequency(&0), Some(4));
+        assert_eq!(cache.frequency(&1), Some(1));
+        assert_eq!(cache.frequency(&2), Some(2));
+        cache.put(3, 3);
+        assert!(!cache.contains_key(&1));
+        // 3 is now the least frequently used
+        cache.put(4, 4);
+        assert!(!cache.contains_key(&3));
+        assert_eq!(format!("{:?}", cache), "{4: 4, 2: 2, 0: 0}");
+    }
+
+    #[test]
+    fn ties_go_to_the_least_recently_used() {
+        let mut cache = LfuCache::new(3);
+        cache.put('a', ());
+        cache.put('b', ());
+        cache.put('c', ());
+        cache.get(&'b');
+        cache.get(&'a');
+        cache.get(&'c');
+        // all three have a count of two; `b` was used longest ago
+        assert_eq!(cache.pop_lfu(), Some(('b', ())));
+        assert_eq!(cache.pop_lfu(), Some(('a', ())));
+        assert_eq!(cache.pop_lfu(), Some(('c', ())));
+        assert_eq!(cache.pop_lfu(), None);
+        assert!(cache.core.order.counts.is_empty());
+    }
+
+    #[test]
+    fn replacing_a_value_is_a_use() {
+        let mut cache = LfuCache::new(2);
+        cache.put(1, 'a');
+        cache.put(2, 'b');
+        assert_eq!(cache.put(1, 'c'), Some('a'));
+        assert_eq!(cache.frequency(&1), Some(2));
+        cache.put(3, 'd');
+        assert_eq!(cache.peek(&1), Some(&'c'));
+        assert!(!cache.contains_key(&2));
+    }
+
+    #[test]
+    fn weighs_pairs_and_tells_the_listener() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&evicted);
+        let mut cache = LfuCache::with_weigher(10, |_, v: &String| v.len())
+            .on_evict(move |k, _| log.lock().unwrap().push(k));
+        cache.put(1, "aaaa".to_string());
+        cache.put(2, "bbbb".to_string());
+        cache.get(&1);
+        cache.put(3, "cccccc".to_string());
+        assert_eq!(cache.weight(), 10);
+        assert_eq!(cache.remove(&3).as_deref(), Some("cccccc"));
+        // too heavy to ever fit, so turned away without evicting 1
+        cache.put(4, "x".repeat(11));
+        assert_eq!(cache.weight(), 4);
+        cache.set_capacity(3);
+        assert!(cache.is_empty());
+        assert_eq!(*evicted.lock().unwrap(), vec![2, 4, 1]);
+        assert_eq!(cache.stats().evictions, 3);
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut cache = LfuCache::new(2);
+        cache.put(1, 1);
+        cache.get(&1);
+        cache.get(&2);
+        if let Some(v) = cache.get_mut(&1) {
+            *v = 5;
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+        assert_eq!(cache.frequency(&1), Some(3));
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats(), stats);
+    }
+
+    #[test]
+    fn keeps_the_hot_keys_of_a_skewed_workload() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut cache = LfuCache::new(20);
+        for _ in 0..20_000 {
+            // keys 0..10 are drawn about half the time
+            let key = if rng.gen_bool(0.5) {
+                rng.gen_range(0..10)
+            } else {
+                rng.gen_range(10..10_000)
+            };
+            if cache.get(&key).is_none() {
+                cache.put(key, ());
+            }
+        }
+        for key in 0..10 {
+            assert!(cache.contains_key(&key), "hot key {} was evicted", key);
+        }
+        assert!(cache.stats().hit_rate() > 0.45);
+        // every count in the bucket list is distinct and increasing
+        let order = &cache.core.order;
+        let counts: Vec<_> = order.buckets.iter(&order.counts).map(|b| b.count).collect();
+        assert!(counts.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,234 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Intrusive doubly linked lists over a slab. The nodes live in one `Vec` and
+//! link to each other by index, so there are no per-node allocations and no
+//! `unsafe`; a [`List`] is just the indices of its two ends. Many lists can
+//! share one [`Slab`], which is how the LFU cache keeps one list per
+//! frequency.
+
+/// The index that links to nothing
+pub(super) const NIL: usize = usize::MAX;
+
+struct Node<T> {
+    /// `None` when the node is on the free list
+    value: Option<T>,
+    prev: usize,
+    next: usize,
+}
+
+/// The two ends of a list of nodes in a [`Slab`]
+#[derive(Debug, Clone, Copy)]
+pub(super) struct List {
+    pub(super) head: usize,
+    pub(super) tail: usize,
+}
+
+impl Default for List {
+    fn default() -> Self {
+        List {
+            head: NIL,
+            tail: NIL,
+        }
+    }
+}
+
+impl List {
+    pub(super) fn is_empty(&self) -> bool {
+        self.head == NIL
+    }
+}
+
+/// Storage for the nodes of any number of [`List`]s
+pub(super) struct Slab<T> {
+    nodes: Vec<Node<T>>,
+    /// Vacant nodes, reused before the `Vec` grows
+    free: Vec<usize>,
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Slab {
+            nodes: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<T> Slab<T> {
+    /// Stores `value` in a node that isn't in any list yet, returning its
+    /// index.
+    pub(super) fn insert(&mut self, value: T) -> usize {
+        let node = Node {
+            value: Some(value),
+            prev: NIL,
+            next: NIL,
+        };
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Frees node `i`, which must already be unlinked, returning its value.
+    pub(super) fn take(&mut self, i: usize) -> T {
+        self.free.push(i);
+        self.nodes[i].value.take().expect("node is occupied")
+    }
+
+    pub(super) fn get(&self, i: usize) -> &T {
+        self.nodes[i].value.as_ref().expect("node is occupied")
+    }
+
+    pub(super) fn get_mut(&mut self, i: usize) -> &mut T {
+        self.nodes[i].value.as_mut().expect("node is occupied")
+    }
+
+    /// The node after `i` in its list, or [`NIL`]
+    pub(super) fn next(&self, i: usize) -> usize {
+        self.nodes[i].next
+    }
+
+    /// Links node `i` in at the head of `list`.
+    pub(super) fn push_front(&mut self, list: &mut List, i: usize) {
+        self.nodes[i].prev = NIL;
+        self.nodes[i].next = list.head;
+        match list.head {
+            NIL => list.tail = i,
+            head => self.nodes[head].prev = i,
+        }
+        list.head = i;
+    }
+
+    /// Links node `i` in right after node `at`, which is in `list`.
+    pub(super) fn insert_after(&mut self, list: &mut List, at: usize, i: usize) {
+        let next = self.nodes[at].next;
+        self.nodes[i].prev = at;
+        self.nodes[i].next = next;
+        self.nodes[at].next = i;
+        match next {
+            NIL => list.tail = i,
+            next => self.nodes[next].prev = i,
+        }
+    }
+
+    /// Unlinks node `i` from `list`, leaving it in the slab.
+    pub(super) fn unlink(&mut self, list: &mut List, i: usize) {
+        let Node { prev, next, .. } = self.nodes[i];
+        match prev {
+            NIL => list.head = next,
+            prev => self.nodes[prev].next = next,
+        }
+        match next {
+            NIL => list.tail = prev,
+            next => self.nodes[next].prev = prev,
+        }
+        self.nodes[i].prev = NIL;
+        self.nodes[i].next = NIL;
+    }
+
+    /// The values of `list`, head first
+    pub(super) fn iter<'a>(&'a self, list: &List) -> Iter<'a, T> {
+        Iter {
+            slab: self,
+            next: list.head,
+        }
+    }
+
+    /// Frees every node.
+    pub(super) fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+    }
+}
+
+/// Created by [`Slab::iter`]
+pub(super) struct Iter<'a, T> {
+    slab: &'a Slab<T>,
+    next: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == NIL {
+            return None;
+        }
+        let i = self.next;
+        self.next = self.slab.next(i);
+        Some(self.slab.get(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(slab: &Slab<char>, list: &List) -> String {
+        slab.iter(list).collect()
+    }
+
+    #[test]
+    fn links_and_unlinks() {
+        let mut slab = Slab::default();
+        let mut list = List::default();
+        assert!(list.is_empty());
+        let a = slab.insert('a');
+        let b = slab.insert('b');
+        let c = slab.insert('c');
+        slab.push_front(&mut list, a);
+        slab.push_front(&mut list, c);
+        slab.insert_after(&mut list, c, b);
+        assert_eq!(values(&slab, &list), "cba");
+        slab.unlink(&mut list, b);
+        assert_eq!(values(&slab, &list), "ca");
+        slab.unlink(&mut list, a);
+        assert_eq!(list.tail, c);
+        slab.insert_after(&mut list, c, a);
+        assert_eq!(list.tail, a);
+        slab.unlink(&mut list, c);
+        slab.unlink(&mut list, a);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn reuses_freed_nodes() {
+        let mut slab = Slab::default();
+        let a = slab.insert('a');
+        slab.insert('b');
+        assert_eq!(slab.take(a), 'a');
+        assert_eq!(slab.insert('c'), a);
+        assert_eq!(*slab.get(a), 'c');
+        *slab.get_mut(a) = 'd';
+        assert_eq!(*slab.get(a), 'd');
+    }
+
+    #[test]
+    fn lists_share_a_slab() {
+        let mut slab = Slab::default();
+        let (mut odd, mut even) = (List::default(), List::default());
+        for c in "abcdef".chars() {
+            let i = slab.insert(c);
+            let list = if i % 2 == 0 { &mut even } else { &mut odd };
+            slab.push_front(list, i);
+        }
+        assert_eq!(values(&slab, &even), "eca");
+        assert_eq!(values(&slab, &odd), "fdb");
+    }
+}
@@ -0,0 +1,299 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A least recently used cache. The pairs are kept in a list ordered by use:
+//! every hit moves a pair to the head, and evictions take from the tail.
+use super::{
+    list::{self, List, Slab},
+    CacheStats, Core, Item, Order,
+};
+use std::{
+    borrow::Borrow,
+    fmt::{self, Debug},
+    hash::Hash,
+};
+
+/// Most recently used first
+#[derive(Default)]
+struct Recency {
+    list: List,
+}
+
+impl Order for Recency {
+    fn push<T>(&mut self, nodes: &mut Slab<T>, i: usize) {
+        nodes.push_front(&mut self.list, i);
+    }
+
+    /// Moves node `i` to the head of the list
+    fn touch<T>(&mut self, nodes: &mut Slab<T>, i: usize) {
+        nodes.unlink(&mut self.list, i);
+        nodes.push_front(&mut self.list, i);
+    }
+
+    fn unlink<T>(&mut self, nodes: &mut Slab<T>, i: usize) {
+        nodes.unlink(&mut self.list, i);
+    }
+
+    fn victim(&self) -> Option<usize> {
+        if self.list.is_empty() {
+            None
+        } else {
+            Some(self.list.tail)
+        }
+    }
+}
+
+/// A cache that evicts the least recently used pairs once their total weight
+/// exceeds its capacity. See the [module docs](super).
+pub struct LruCache<K, V> {
+    core: Core<K, V, Recency>,
+}
+
+impl_cache!(LruCache);
+
+impl<K, V> LruCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Puts `value` in the cache at `key`, as the most recently used pair,
+    /// evicting other pairs as needed to make room. If the key was already
+    /// present, its old value is replaced and returned.
+    ///
+    /// A pair that is heavier than the whole capacity is not put in the cache:
+    /// it is evicted right away, along with any old value for its key, and no
+    /// other pair is touched.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        self.core.put(key, value)
+    }
+
+    /// Gets the value for `key`, marking it as the most recently used. Counts
+    /// towards the hits or misses.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.core.lookup(key)?;
+        Some(&self.core.items.get(i).value)
+    }
+
+    /// Like [`LruCache::get`], but the value can be changed in place. Its
+    /// weight is not recomputed.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.core.lookup(key)?;
+        Some(&mut self.core.items.get_mut(i).value)
+    }
+
+    /// Gets the value for `key` without marking it as used or counting a hit
+    /// or miss.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.core.peek(key)
+    }
+
+    /// The least recently used pair, which is the next to be evicted
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        let item = self.core.items.get(self.core.order.victim()?);
+        Some((&item.key, &item.value))
+    }
+
+    /// Removes and returns the least recently used pair. The eviction listener
+    /// is not called.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        self.core.pop()
+    }
+
+    /// An iterator over `(&K, &V)` pairs, from the most to the least recently
+    /// used. Iterating doesn't count as a use.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            items: self.core.items.iter(&self.core.order.list),
+            remaining: self.len(),
+        }
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, most recently used first
+impl<K, V> Debug for LruCache<K, V>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Created by [`LruCache::iter`]
+pub struct Iter<'a, K, V> {
+    items: list::Iter<'a, Item<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.items.next()?;
+        self.remaining -= 1;
+        Some((&item.key, &item.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn keys<K: Eq + Hash + Copy, V>(cache: &LruCache<K, V>) -> Vec<K> {
+        cache.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = LruCache::new(3);
+        for i in 0..3 {
+            assert_eq!(cache.put(i, i * 10), None);
+        }
+        assert_eq!(keys(&cache), vec![2, 1, 0]);
+        assert_eq!(cache.get(&0), Some(&0));
+        assert_eq!(keys(&cache), vec![0, 2, 1]);
+        cache.put(3, 30);
+        assert_eq!(keys(&cache), vec![3, 0, 2]);
+        assert_eq!(cache.peek_lru(), Some((&2, &20)));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn replacing_a_value_is_a_use() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.put(1, 10), Some(1));
+        cache.put(3, 3);
+        assert_eq!(keys(&cache), vec![3, 1]);
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn peeking_is_not_a_use() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.peek(&1), Some(&1));
+        assert!(cache.contains_key(&1));
+        cache.put(3, 3);
+        assert!(!cache.contains_key(&1));
+        let stats = CacheStats {
+            evictions: 1,
+            ..CacheStats::default()
+        };
+        assert_eq!(cache.stats(), stats);
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.get(&1);
+        cache.get(&2);
+        if let Some(v) = cache.get_mut(&1) {
+            *v += 1;
+        }
+        assert_eq!(cache.peek(&1), Some(&2));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+    }
+
+    #[test]
+    fn weighs_pairs() {
+        let mut cache = LruCache::with_weigher(10, |_, v: &Vec<u8>| v.len());
+        cache.put(1, vec![0; 4]);
+        cache.put(2, vec![0; 4]);
+        assert_eq!(cache.weight(), 8);
+        cache.put(3, vec![0; 4]);
+        assert_eq!(cache.weight(), 8);
+        assert!(!cache.contains_key(&1));
+        // growing a pair in place evicts others to fit it
+        cache.put(3, vec![0; 9]);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.weight(), 9);
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn turns_away_pairs_heavier_than_the_capacity() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&evicted);
+        let mut cache = LruCache::with_weigher(10, |_, v: &Vec<u8>| v.len())
+            .on_evict(move |k, v: Vec<u8>| log.lock().unwrap().push((k, v.len())));
+        cache.put(1, vec![0; 4]);
+        cache.put(2, vec![0; 4]);
+        // a pair that can never fit leaves the others alone
+        assert_eq!(cache.put(3, vec![0; 11]), None);
+        assert_eq!(keys(&cache), vec![2, 1]);
+        assert_eq!(cache.weight(), 8);
+        // and replacing a pair with one that can't fit removes the old value
+        assert_eq!(cache.put(1, vec![0; 11]), Some(vec![0; 4]));
+        assert_eq!(keys(&cache), vec![2]);
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.stats().evictions, 2);
+        assert_eq!(*evicted.lock().unwrap(), vec![(3, 11), (1, 11)]);
+    }
+
+    #[test]
+    fn tells_the_listener_about_evictions_only() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&evicted);
+        let mut cache = LruCache::new(2).on_evict(move |k, v| log.lock().unwrap().push((k, v)));
+        cache.put(1, 'a');
+        cache.put(2, 'b');
+        cache.put(3, 'c');
+        cache.remove(&2);
+        cache.pop_lru();
+        cache.put(4, 'd');
+        cache.put(5, 'e');
+        cache.put(6, 'f');
+        cache.set_capacity(1);
+        assert_eq!(*evicted.lock().unwrap(), vec![(1, 'a'), (4, 'd'), (5, 'e')]);
+        assert_eq!(keys(&cache), vec![6]);
+    }
+
+    #[test]
+    fn reuses_nodes_after_clear_and_churn() {
+        let mut cache = LruCache::new(100);
+        for i in 0..10_000 {
+            cache.put(i % 150, i);
+        }
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.iter().len(), 100);
+        assert_eq!(cache.stats().evictions, 10_000 - 100);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1), None);
+        cache.put(1, 1);
+        assert_eq!(format!("{:?}", cache), "{1: 1}");
+    }
+}
@@ -0,0 +1,467 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A map whose pairs expire a while after they are inserted.
+//!
+//! Each pair gets a deadline. Nothing runs in the background: an expired pair
+//! is treated as absent by every lookup, removed when a lookup that can
+//! mutate the map finds it, and [`TtlMap::purge_expired`] sweeps out the rest.
+//!
+//! Time comes from a [`Clock`]. [`SystemClock`] is the real one;
+//! [`ManualClock`] only moves when told to, so tests can step past deadlines
+//! without sleeping:
+//!
+//! ```
+//! use std::time::Duration;
+//! use tinystd::cache::{ManualClock, TtlMap};
+//!
+//! let clock = ManualClock::new();
+//! let mut sessions = TtlMap::with_clock(Duration::from_secs(60), clock.clone());
+//! sessions.insert("alice", 1);
+//! clock.advance(Duration::from_secs(30));
+//! assert_eq!(sessions.get("alice"), Some(&1));
+//! clock.advance(Duration::from_secs(30));
+//! assert_eq!(sessions.get("alice"), None);
+//! ```
+use crate::hashmap::HashMap;
+use std::{
+    borrow::Borrow,
+    convert::TryFrom,
+    fmt::{self, Debug},
+    hash::Hash,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// A source of the current time
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that stands still until [`ManualClock::advance`] is called. Clones
+/// share the same time, so a test can keep one and hand the other to a map.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    start: Instant,
+    /// Nanoseconds since `start`
+    elapsed: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// A clock reading the current time, which it will keep until advanced
+    pub fn new() -> Self {
+        ManualClock {
+            start: Instant::now(),
+            elapsed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Moves this clock, and all its clones, forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        let nanos = u64::try_from(by.as_nanos()).expect("advanced too far");
+        self.elapsed.fetch_add(nanos, Ordering::SeqCst);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.elapsed.load(Ordering::SeqCst))
+    }
+}
+
+struct Timed<V> {
+    value: V,
+    /// `None` if the deadline is too far off to represent, i.e. never
+    deadline: Option<Instant>,
+}
+
+impl<V> Timed<V> {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+}
+
+/// A [`HashMap`] whose pairs expire. See the [module docs](self).
+pub struct TtlMap<K, V, C = SystemClock> {
+    map: HashMap<K, Timed<V>>,
+    /// How long pairs live unless inserted with their own TTL
+    ttl: Duration,
+    clock: C,
+}
+
+impl<K, V> TtlMap<K, V, SystemClock> {
+    /// Constructs an empty map whose pairs live for `ttl` by default.
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_clock(ttl, SystemClock)
+    }
+}
+
+impl<K, V, C> TtlMap<K, V, C>
+where
+    C: Clock,
+{
+    /// Constructs an empty map whose pairs live for `ttl` by default, as told
+    /// by `clock`.
+    pub fn with_clock(ttl: Duration, clock: C) -> Self {
+        TtlMap {
+            map: HashMap::new(),
+            ttl,
+            clock,
+        }
+    }
+
+    /// The map's [`Clock`]
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// How long pairs live unless inserted with their own TTL
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The number of pairs in the map, *including* expired pairs that haven't
+    /// been removed yet. Call [`TtlMap::purge_expired`] first for an exact
+    /// count.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Like [`TtlMap::len`], this counts expired pairs that haven't been
+    /// removed yet.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes every pair, expired or not.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Removes every expired pair, returning how many there were.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let len = self.map.len();
+        self.map.retain(|_, timed| !timed.is_expired(now));
+        len - self.map.len()
+    }
+
+    /// An iterator over the pairs that haven't expired, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let now = self.clock.now();
+        self.map
+            .iter()
+            .filter(move |(_, timed)| !timed.is_expired(now))
+            .map(|(k, timed)| (k, &timed.value))
+    }
+
+    fn deadline(&self, ttl: Duration) -> Option<Instant> {
+        self.clock.now().checked_add(ttl)
+    }
+}
+
+impl<K, V, C> TtlMap<K, V, C>
+where
+    K: Eq + Hash,
+    C: Clock,
+{
+    /// Inserts `V` at key `K`, to expire after the map's default TTL. If the
+    /// key was present and hadn't expired, its old value is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_with_ttl(key, value, self.ttl)
+    }
+
+    /// Inserts `V` at key `K`, to expire after `ttl`. If the key was present
+    /// and hadn't expired, its old value is returned.
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let now = self.clock.now();
+        let timed = Timed {
+            value,
+            deadline: now.checked_add(ttl),
+        };
+        self.map
+            .insert(key, timed)
+            .filter(|old| !old.is_expired(now))
+            .map(|old| old.value)
+    }
+
+    /// Gets the value for `key`, or any borrowed form of it, unless it has
+    /// expired, in which case it is removed.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_mut(key).map(|v| &*v)
+    }
+
+    /// Like [`TtlMap::get`], but the value can be changed in place. Its
+    /// deadline doesn't change.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.map.get(key)?.is_expired(self.clock.now()) {
+            self.map.remove(key);
+            return None;
+        }
+        self.map.get_mut(key).map(|timed| &mut timed.value)
+    }
+
+    /// Gets the value for `key` unless it has expired, without removing it if
+    /// it has.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let timed = self.map.get(key)?;
+        if timed.is_expired(self.clock.now()) {
+            None
+        } else {
+            Some(&timed.value)
+        }
+    }
+
+    /// Returns `true` if the map holds a value for `key` that hasn't expired.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// How long the pair for `key` has left, or `None` if it isn't in the map
+    /// or has expired. A pair whose deadline is too far off to represent has
+    /// [`Duration::MAX`] left.
+    pub fn time_to_live<Q>(&self, key: &Q) -> Option<Duration>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let now = self.clock.now();
+        let timed = self.map.get(key)?;
+        if timed.is_expired(now) {
+            return None;
+        }
+        Some(timed.deadline.map_or(Duration::MAX, |d| d - now))
+    }
+
+    /// Restarts the default TTL of the pair for `key`, returning `false` if it
+    /// isn't in the map or has already expired.
+    ///
+    /// ## Example:
+    /// ```
+    /// use std::time::Duration;
+    /// use tinystd::cache::{ManualClock, TtlMap};
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut m = TtlMap::with_clock(Duration::from_secs(10), clock.clone());
+    /// m.insert("a", ());
+    /// clock.advance(Duration::from_secs(8));
+    /// assert!(m.refresh("a"));
+    /// clock.advance(Duration::from_secs(8));
+    /// assert!(m.contains_key("a"));
+    /// ```
+    pub fn refresh<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let deadline = self.deadline(self.ttl);
+        match self.get_mut(key) {
+            Some(_) => {
+                if let Some(timed) = self.map.get_mut(key) {
+                    timed.deadline = deadline;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the pair for `key`, returning its value if it hadn't expired.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let now = self.clock.now();
+        self.map
+            .remove(key)
+            .filter(|timed| !timed.is_expired(now))
+            .map(|timed| timed.value)
+    }
+
+    /// Replaces the value for `key` without touching its deadline, returning
+    /// the old value, or `None` (and dropping `value`) if there is none.
+    pub fn replace<Q>(&mut self, key: &Q, value: V) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_mut(key).map(|old| mem::replace(old, value))
+    }
+}
+
+impl<K, V> Default for TtlMap<K, V, SystemClock> {
+    /// Pairs live for a minute by default.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+/// Prints the pairs that haven't expired as a map, e.g. `{"a": 1}`
+impl<K, V, C> Debug for TtlMap<K, V, C>
+where
+    K: Debug,
+    V: Debug,
+    C: Clock,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn map() -> (TtlMap<&'static str, u32, ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        (TtlMap::with_clock(10 * SECOND, clock.clone()), clock)
+    }
+
+    #[test]
+    fn manual_clocks_share_their_time() {
+        let clock = ManualClock::new();
+        let other = clock.clone();
+        let before = clock.now();
+        other.advance(SECOND);
+        assert_eq!(clock.now() - before, SECOND);
+        assert_eq!(clock.now(), other.now());
+    }
+
+    #[test]
+    fn pairs_expire_at_their_deadline() {
+        let (mut m, clock) = map();
+        m.insert("a", 1);
+        m.insert_with_ttl("b", 2, 20 * SECOND);
+        clock.advance(9 * SECOND);
+        assert_eq!(m.time_to_live("a"), Some(SECOND));
+        assert_eq!(m.get("a"), Some(&1));
+        clock.advance(SECOND);
+        assert_eq!(m.peek("a"), None);
+        assert!(!m.contains_key("a"));
+        assert_eq!(m.time_to_live("a"), None);
+        assert_eq!(m.get("b"), Some(&2));
+        clock.advance(10 * SECOND);
+        assert_eq!(m.get("b"), None);
+    }
+
+    #[test]
+    fn lookups_remove_expired_pairs_lazily() {
+        let (mut m, clock) = map();
+        m.insert("a", 1);
+        m.insert("b", 2);
+        clock.advance(10 * SECOND);
+        assert_eq!(m.peek("a"), None);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get("a"), None);
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.remove("b"), None);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn purging_sweeps_out_every_expired_pair() {
+        let (mut m, clock) = map();
+        for (i, key) in ["a", "b", "c", "d"].iter().enumerate() {
+            m.insert_with_ttl(key, i as u32, (i as u32 + 1) * SECOND);
+        }
+        m.insert_with_ttl("forever", 0, Duration::MAX);
+        clock.advance(2 * SECOND);
+        assert_eq!(m.iter().count(), 3);
+        assert_eq!(format!("{:?}", m).matches(':').count(), 3);
+        assert_eq!(m.purge_expired(), 2);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.purge_expired(), 0);
+        clock.advance(Duration::from_secs(1_000_000));
+        assert_eq!(m.purge_expired(), 2);
+        assert_eq!(m.time_to_live("forever"), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn reinserting_resets_the_deadline() {
+        let (mut m, clock) = map();
+        m.insert("a", 1);
+        clock.advance(5 * SECOND);
+        assert_eq!(m.insert("a", 2), Some(1));
+        clock.advance(5 * SECOND);
+        assert_eq!(m.get("a"), Some(&2));
+        clock.advance(5 * SECOND);
+        // the old value had expired, so there is nothing to give back
+        assert_eq!(m.insert("a", 3), None);
+    }
+
+    #[test]
+    fn replacing_and_refreshing() {
+        let (mut m, clock) = map();
+        m.insert("a", 1);
+        clock.advance(6 * SECOND);
+        assert_eq!(m.replace("a", 2), Some(1));
+        assert_eq!(m.time_to_live("a"), Some(4 * SECOND));
+        assert!(m.refresh("a"));
+        assert_eq!(m.time_to_live("a"), Some(10 * SECOND));
+        if let Some(v) = m.get_mut("a") {
+            *v += 1;
+        }
+        assert_eq!(m.remove("a"), Some(3));
+        assert!(!m.refresh("a"));
+        assert_eq!(m.replace("a", 4), None);
+    }
+
+    #[test]
+    fn the_system_clock_moves() {
+        let mut m = TtlMap::new(Duration::from_secs(3600));
+        m.insert(1, 1);
+        assert_eq!(m.get(&1), Some(&1));
+        let mut m = TtlMap::new(Duration::ZERO);
+        m.insert(1, 1);
+        assert_eq!(m.get(&1), None);
+        assert!(TtlMap::<u8, u8>::default().ttl() > Duration::ZERO);
+    }
+}
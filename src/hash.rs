@@ -0,0 +1,386 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A few [`Hasher`]s to plug into [`crate::hashmap::HashMap`] (or
+//! `std::collections::HashMap`) via their [`BuildHasher`]s:
+//!
+//! - [`FxHasher`], the multiply-and-rotate hash used inside `rustc`. Very fast,
+//!   but weak: a multiply only carries bits upwards, so keys whose low bits
+//!   repeat (e.g. multiples of 4096) get hashes whose low bits repeat too. The
+//!   maps in [`crate::hashmap`] mix every hash before indexing by its low bits,
+//!   so they cope; a structure that doesn't would put those keys in a handful
+//!   of bins. It's also unkeyed, so an attacker who controls the keys can
+//!   force every key into the same bin.
+//! - [`Fnv1aHasher`], the [Fowler–Noll–Vo][1] hash. Simple and good for short
+//!   keys, also unkeyed.
+//! - [`SipHasher13`], [SipHash][2] with 1 compression and 3 finalization
+//!   rounds: the same algorithm as `std`'s `DefaultHasher`. Keyed, so with a
+//!   random key ([`SipState::new`]) it resists HashDoS.
+//!
+//! [1]: https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+//! [2]: https://www.aumasson.jp/siphash/siphash.pdf
+use std::{
+    convert::TryInto,
+    hash::{BuildHasher, BuildHasherDefault, Hasher},
+};
+
+/// Builds [`FxHasher`]s
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+/// Builds [`Fnv1aHasher`]s
+pub type FnvBuildHasher = BuildHasherDefault<Fnv1aHasher>;
+
+/// The hash used inside `rustc`: each word is mixed in with a rotate, an xor
+/// and a multiply by a constant derived from the golden ratio. The low bits of
+/// the hash only depend on the low bits of the key, so mix the hash before
+/// slicing it up, as the maps in [`crate::hashmap`] do.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+const FX_SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl FxHasher {
+    #[inline]
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(FX_SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add_to_hash(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let mut rest = chunks.remainder();
+        if rest.len() >= 4 {
+            self.add_to_hash(u32::from_le_bytes(rest[..4].try_into().unwrap()) as u64);
+            rest = &rest[4..];
+        }
+        if rest.len() >= 2 {
+            self.add_to_hash(u16::from_le_bytes(rest[..2].try_into().unwrap()) as u64);
+            rest = &rest[2..];
+        }
+        if let Some(&byte) = rest.first() {
+            self.add_to_hash(byte as u64);
+        }
+    }
+
+    // integers skip the byte slicing entirely, which is where Fx gets its speed
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a: for each byte, xor it in and then multiply by the FNV prime.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1aHasher {
+    hash: u64,
+}
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Fnv1aHasher {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// The 64-bit finalizer of MurmurHash3: every bit of the input affects every
+/// bit of the output. Not a [`Hasher`] itself, but a cheap way to spread out
+/// the bits of a hash that some structure is about to slice up.
+pub(crate) fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// Builds [`SipHasher13`]s that all share the same 128-bit key. Each call to
+/// [`SipState::new`] picks a fresh random key, so two maps (or two runs of a
+/// program) will not agree on which keys collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SipState {
+    k0: u64,
+    k1: u64,
+}
+
+impl SipState {
+    /// A random key
+    pub fn new() -> Self {
+        SipState::with_keys(rand::random(), rand::random())
+    }
+
+    /// A fixed key, e.g. for reproducible hashing across processes
+    pub fn with_keys(k0: u64, k1: u64) -> Self {
+        SipState { k0, k1 }
+    }
+
+    /// The two halves of the key
+    pub fn keys(&self) -> (u64, u64) {
+        (self.k0, self.k1)
+    }
+}
+
+impl Default for SipState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`SipState`] with a fixed key, so that tests see the same hashes every run
+#[cfg(test)]
+pub(crate) fn test_hasher() -> SipState {
+    SipState::with_keys(0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210)
+}
+
+impl BuildHasher for SipState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::with_keys(self.k0, self.k1)
+    }
+}
+
+/// SipHash-1-3. Input is consumed in little-endian 64-bit words; a partial word
+/// is buffered in `tail` until it fills up or the hash is finished.
+#[derive(Debug, Clone, Copy)]
+pub struct SipHasher13 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    tail: u64,
+    /// The number of bytes buffered in `tail`
+    ntail: usize,
+    /// The total number of bytes written
+    length: usize,
+}
+
+impl SipHasher13 {
+    pub fn with_keys(k0: u64, k1: u64) -> Self {
+        SipHasher13 {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    #[inline]
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    /// One compression round per word: the "1" in SipHash-1-3
+    #[inline]
+    fn compress(&mut self, word: u64) {
+        self.v3 ^= word;
+        self.round();
+        self.v0 ^= word;
+    }
+}
+
+impl Hasher for SipHasher13 {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len();
+
+        // top up a partially filled word first
+        if self.ntail != 0 {
+            let take = (8 - self.ntail).min(bytes.len());
+            for (i, &byte) in bytes[..take].iter().enumerate() {
+                self.tail |= (byte as u64) << (8 * (self.ntail + i));
+            }
+            self.ntail += take;
+            bytes = &bytes[take..];
+            if self.ntail < 8 {
+                return;
+            }
+            self.compress(self.tail);
+            self.tail = 0;
+            self.ntail = 0;
+        }
+
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.compress(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for (i, &byte) in chunks.remainder().iter().enumerate() {
+            self.tail |= (byte as u64) << (8 * i);
+        }
+        self.ntail = chunks.remainder().len();
+    }
+
+    /// Three finalization rounds: the "3" in SipHash-1-3
+    fn finish(&self) -> u64 {
+        let mut state = *self;
+        let last = ((self.length as u64 & 0xff) << 56) | self.tail;
+        state.compress(last);
+        state.v2 ^= 0xff;
+        state.round();
+        state.round();
+        state.round();
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fnv(bytes: &[u8]) -> u64 {
+        let mut h = Fnv1aHasher::default();
+        h.write(bytes);
+        h.finish()
+    }
+
+    /// Reference values from the FNV authors' test suite
+    #[test]
+    fn fnv1a_reference_values() {
+        assert_eq!(fnv(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    /// `std`'s `DefaultHasher::new()` is SipHash-1-3 with an all-zero key
+    #[test]
+    fn siphash13_matches_std() {
+        use std::collections::hash_map::DefaultHasher;
+        let input: Vec<u8> = (0..64).collect();
+        for len in 0..input.len() {
+            let mut ours = SipState::with_keys(0, 0).build_hasher();
+            let mut theirs = DefaultHasher::new();
+            ours.write(&input[..len]);
+            theirs.write(&input[..len]);
+            assert_eq!(ours.finish(), theirs.finish(), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn siphash13_is_independent_of_write_boundaries() {
+        let input: Vec<u8> = (0..40).collect();
+        let mut whole = SipHasher13::with_keys(1, 2);
+        whole.write(&input);
+        for split in 0..input.len() {
+            let mut parts = SipHasher13::with_keys(1, 2);
+            parts.write(&input[..split]);
+            parts.write(&input[split..split + 3.min(input.len() - split)]);
+            parts.write(&input[(split + 3).min(input.len())..]);
+            assert_eq!(parts.finish(), whole.finish(), "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn sip_states_are_keyed() {
+        let a = SipState::with_keys(1, 2);
+        let b = SipState::with_keys(2, 1);
+        let hash = |s: &SipState| {
+            let mut h = s.build_hasher();
+            h.write_u64(42);
+            h.finish()
+        };
+        assert_eq!(hash(&a), hash(&SipState::with_keys(1, 2)));
+        assert_ne!(hash(&a), hash(&b));
+        assert_ne!(SipState::new(), SipState::new());
+    }
+
+    #[test]
+    fn fx_spreads_small_integers() {
+        let hashes: std::collections::HashSet<u64> = (0..1000u64)
+            .map(|i| {
+                let mut h = FxHasher::default();
+                h.write_u64(i);
+                h.finish()
+            })
+            .collect();
+        assert_eq!(hashes.len(), 1000);
+        let mut a = FxHasher::default();
+        a.write(b"hello world");
+        let mut b = FxHasher::default();
+        b.write(b"hello worle");
+        assert_ne!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn fx_low_bits_need_mixing() {
+        let low_bits = |mix: fn(u64) -> u64| {
+            (0..1000u64)
+                .map(|i| {
+                    let mut h = FxHasher::default();
+                    h.write_u64(i * 4096);
+                    mix(h.finish()) & 0xfff
+                })
+                .collect::<std::collections::HashSet<u64>>()
+                .len()
+        };
+        // multiples of 4096 all hash to multiples of 4096, so 4096 bins
+        // indexed by the raw hash would see just one of them in use
+        assert_eq!(low_bits(|h| h), 1);
+        assert!(low_bits(fmix64) > 800);
+    }
+}
@@ -9,125 +9,482 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+//! A separately chained hash map. Each key is hashed to one of a
+//! power-of-two number of bins, and each bin holds a small `Vec` of the pairs
+//! that landed there. When the average number of pairs per bin (the load
+//! factor) would exceed the map's maximum, the number of bins doubles.
+use crate::hash::fmix64;
 use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::{Index, RangeInclusive},
 };
 
-#[derive(Debug, Default, Hash, Clone)]
-struct Bin<K, V>
-where
-    (K, V): Copy,
-{
-    data: Vec<(K, V)>,
+#[cfg(test)]
+#[macro_use]
+mod conformance;
+pub mod bi_map;
+pub mod concurrent;
+pub mod cuckoo;
+pub mod hamt;
+pub mod index_map;
+mod iter;
+pub mod multi_map;
+pub mod open;
+#[cfg(feature = "serde")]
+mod serde;
+pub mod set;
+pub mod snapshot;
+pub mod swiss;
+
+pub use bi_map::BiMap;
+pub use concurrent::ConcurrentHashMap;
+pub use cuckoo::CuckooMap;
+pub use hamt::{ArcHamt, Hamt};
+pub use index_map::IndexMap;
+pub use iter::{Drain, ExtractIf, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+pub use multi_map::MultiMap;
+pub use open::OpenMap;
+pub use set::HashSet;
+pub use snapshot::SnapshotError;
+pub use swiss::SwissMap;
+
+/// The number of bins allocated by the first insert into an empty map
+const INITIAL_BINS: usize = 8;
+
+/// The same default as Java's (also separately chained) `HashMap`
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.75;
+
+/// The maximum load factors a map may have. Far outside them, a chained map is
+/// either mostly empty bins or one long list, and the arithmetic of sizing the
+/// table overflows.
+const LOAD_FACTORS: RangeInclusive<f64> = (1.0 / 64.0)..=64.0;
+
+/// A key-value pair, along with the full hash of its key. Keeping the hash
+/// around means that growing the table never rehashes a key, and that most
+/// mismatches in a bin are rejected without calling `K::eq`.
+#[derive(Debug, Hash, Clone)]
+struct Slot<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+#[derive(Debug, Hash, Clone)]
+struct Bin<K, V> {
+    data: Vec<Slot<K, V>>,
+}
+
+/// Implemented by hand, as deriving it would require `K: Default` and
+/// `V: Default`
+impl<K, V> Default for Bin<K, V> {
+    fn default() -> Self {
+        Bin { data: Vec::new() }
+    }
 }
 
 impl<K, V> Bin<K, V>
 where
     K: Eq,
-    (K, V): Copy,
 {
-    fn get(&self, key: K) -> Option<&V> {
-        for pair in &self.data {
-            if pair.0 == key {
-                return Some(&pair.1);
+    fn get<Q>(&self, hash: u64, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        for slot in &self.data {
+            if slot.hash == hash && slot.key.borrow() == key {
+                return Some(&slot.value);
             }
         }
         None
     }
-    fn set(&mut self, key: K, value: V) {
-        for mut pair in &mut self.data {
-            if pair.0 == key {
-                pair.1 = value;
-                return;
+    fn get_mut<Q>(&mut self, hash: u64, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        for slot in &mut self.data {
+            if slot.hash == hash && slot.key.borrow() == key {
+                return Some(&mut slot.value);
             }
         }
-        self.data.push((key, value));
+        None
     }
-    fn delete(&mut self, key: K) -> bool {
-        let prelen = self.data.len();
-        self.data = self
+    fn delete<Q>(&mut self, hash: u64, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        // order within a bin doesn't matter, so the pair can be swapped out
+        let i = self
             .data
             .iter()
-            .filter_map(|p| if p.0 != key { Some(*p) } else { None })
-            .collect();
-        let postlen = self.data.len();
-        if prelen == postlen {
-            false
-        } else {
-            true
-        }
+            .position(|slot| slot.hash == hash && slot.key.borrow() == key)?;
+        Some(self.data.swap_remove(i).value)
     }
 }
 
-#[derive(Debug)]
-pub struct HashMap<K, V>
-where
-    K: Copy,
-    V: Copy,
-{
+/// A hash map whose keys are hashed with the [`BuildHasher`] `S`. The default,
+/// `std`'s `RandomState`, is seeded randomly per map, which keeps an attacker
+/// from predicting which keys collide; [`crate::hash`] has faster (but
+/// unseeded) alternatives for trusted keys.
+#[derive(Clone)]
+pub struct HashMap<K, V, S = RandomState> {
+    /// Always empty or a power of two in length, so that a hash can be reduced
+    /// to a bin index with a mask rather than a modulo
     hash_table: Vec<Bin<K, V>>,
-    key_size: u64,
+    len: usize,
+    max_load_factor: f64,
+    hash_builder: S,
 }
 
-impl<K, V> HashMap<K, V>
-where
-    K: Clone + Copy + Default + Eq + Hash,
-    V: Clone + Copy + Default + Eq,
-{
-    /// Constructs a new `HashMap<K,V>` using 92821 as the prime by which to
-    /// modulo each key hash. This number was chosen based on the argument
-    /// presented [in this Stack Overflow][1] discussion.
-    ///
-    /// [1]: https://stackoverflow.com/questions/1835976/what-is-a-sensible-prime-for-hashcode-calculation
+/// How a [`HashMap`]'s pairs are spread over its bins. Created by
+/// [`HashMap::stats`].
+///
+/// A good hasher spreads the pairs like a uniformly random one would: at load
+/// factor α, the number of pairs in a bin is roughly Poisson distributed with
+/// mean α, so about `e^-α` of the bins are empty, and the longest chain grows
+/// only like `log n / log log n`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// The number of pairs
+    pub len: usize,
+    /// The number of bins
+    pub bins: usize,
+    /// The number of bins holding at least one pair
+    pub occupied_bins: usize,
+    /// Pairs per bin
+    pub load_factor: f64,
+    /// The most pairs in any one bin
+    pub max_chain: usize,
+    /// Pairs per occupied bin, i.e. the average length of a chain that a
+    /// lookup has to search
+    pub mean_chain: f64,
+    /// `histogram[i]` is the number of bins holding exactly `i` pairs
+    pub histogram: Vec<usize>,
+}
+
+impl<K, V> HashMap<K, V, RandomState> {
+    /// Constructs a new, empty `HashMap<K,V>`. No bins are allocated until the
+    /// first insert.
     pub fn new() -> Self {
-        let k = 92_821u64;
-        let cells = vec![Bin::default(); k as usize];
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Constructs a new, empty `HashMap<K,V>` that can hold at least
+    /// `capacity` pairs before it needs to grow.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let m = HashMap::<u32, u32>::with_capacity(100);
+    /// assert!(m.capacity() >= 100);
+    /// assert_eq!(m.len(), 0);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Constructs a new, empty map which hashes its keys with `hash_builder`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::{hash::FxBuildHasher, hashmap::HashMap};
+    ///
+    /// let mut m = HashMap::with_hasher(FxBuildHasher::default());
+    /// m.insert(1u64, "one");
+    /// assert_eq!(m.get(&1), Some(&"one"));
+    /// ```
+    pub fn with_hasher(hash_builder: S) -> Self {
         HashMap {
-            hash_table: cells,
-            key_size: k,
+            hash_table: Vec::new(),
+            len: 0,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            hash_builder,
+        }
+    }
+
+    /// Combines [`HashMap::with_capacity`] and [`HashMap::with_hasher`].
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes every pair, keeping the bins for reuse.
+    pub fn clear(&mut self) {
+        for bin in &mut self.hash_table {
+            bin.data.clear();
+        }
+        self.len = 0;
+    }
+
+    /// The number of pairs the map can hold before it has to grow
+    pub fn capacity(&self) -> usize {
+        (self.hash_table.len() as f64 * self.max_load_factor) as usize
+    }
+
+    /// The average number of pairs per bin above which the map grows
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor
+    }
+
+    /// Changes the maximum load factor, growing straight away if the map is now
+    /// over it. Chains get longer as the load factor goes up, so this trades
+    /// lookup speed for memory. Lowering the load factor never shrinks the
+    /// table; use [`HashMap::shrink_to_fit`] for that.
+    ///
+    /// ## Panics
+    /// If `max_load_factor` is outside `1/64..=64`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m = HashMap::<u32, u32>::with_capacity(64);
+    /// let bins = m.capacity();
+    /// m.set_max_load_factor(3.0);
+    /// assert!(m.capacity() > bins);
+    /// ```
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        assert!(
+            LOAD_FACTORS.contains(&max_load_factor),
+            "the maximum load factor must be between 1/64 and 64"
+        );
+        self.max_load_factor = max_load_factor;
+        self.reserve(0);
+    }
+
+    /// Makes room for at least `additional` more pairs without growing.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            self.resize(self.bins_for(needed).max(self.hash_table.len() * 2));
+        }
+    }
+
+    /// Shrinks the table to the smallest power-of-two number of bins that can
+    /// hold the current pairs. An empty map releases all of its bins.
+    pub fn shrink_to_fit(&mut self) {
+        let bins = if self.len == 0 {
+            0
+        } else {
+            self.bins_for(self.len)
+        };
+        if bins < self.hash_table.len() {
+            self.resize(bins);
+        }
+    }
+
+    /// The smallest power-of-two number of bins that keeps `len` pairs under
+    /// the maximum load factor
+    fn bins_for(&self, len: usize) -> usize {
+        let bins = (len as f64 / self.max_load_factor).ceil() as usize;
+        bins.max(INITIAL_BINS).next_power_of_two()
+    }
+
+    /// Moves every pair into a fresh table of `bins` bins
+    fn resize(&mut self, bins: usize) {
+        let mut table = Vec::with_capacity(bins);
+        table.resize_with(bins, Bin::default);
+        let old = mem::replace(&mut self.hash_table, table);
+        for slot in old.into_iter().flat_map(|bin| bin.data) {
+            let i = self.index(slot.hash);
+            self.hash_table[i].data.push(slot);
+        }
+    }
+
+    /// A snapshot of how the pairs are spread over the bins, for judging a
+    /// hasher or a load factor by what it actually does.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let m: HashMap<_, _> = (0..1000).map(|i| (i, i)).collect();
+    /// let stats = m.stats();
+    /// assert_eq!(stats.len, 1000);
+    /// assert!(stats.load_factor <= m.max_load_factor());
+    /// assert_eq!(stats.histogram.iter().sum::<usize>(), stats.bins);
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let mut histogram = vec![0; 1];
+        for bin in &self.hash_table {
+            let chain = bin.data.len();
+            if chain >= histogram.len() {
+                histogram.resize(chain + 1, 0);
+            }
+            histogram[chain] += 1;
+        }
+        let bins = self.hash_table.len();
+        let occupied_bins = bins - histogram[0];
+        Stats {
+            len: self.len,
+            bins,
+            occupied_bins,
+            load_factor: if bins == 0 {
+                0.0
+            } else {
+                self.len as f64 / bins as f64
+            },
+            max_chain: histogram.len() - 1,
+            mean_chain: if occupied_bins == 0 {
+                0.0
+            } else {
+                self.len as f64 / occupied_bins as f64
+            },
+            histogram,
         }
     }
 
-    /// Inserts `V` at key `K`.
+    /// The index of the bin that a hash belongs in. The table must not be
+    /// empty.
+    fn index(&self, hash: u64) -> usize {
+        (hash as usize) & (self.hash_table.len() - 1)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Keys are hashed through `Q` so that a borrowed form of the key (e.g.
+    /// `&str` for a `String` key) hashes the same as the owned key. This relies
+    /// on the contract of [`Borrow`], which requires the two to agree.
+    ///
+    /// The hash is then mixed with [`fmix64`], because a bin is picked by the
+    /// low bits alone, and a weak hasher like [`crate::hash::FxHasher`] leaves
+    /// those nearly constant for aligned integer keys (e.g. multiples of 4096).
+    /// The other maps here hash the same way.
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        fmix64(self.hash_builder.hash_one(key))
+    }
+
+    /// Inserts `V` at key `K`. If the key was already present, its old value is
+    /// replaced and returned.
     ///
     /// ## Example:
     /// ```
     /// use tinystd::hashmap::HashMap;
     ///
     /// let mut m = HashMap::<&str, i32>::new();
-    /// m.insert("foo", 1);
+    /// assert_eq!(m.insert("foo", 1), None);
     /// assert_eq!(m.get("foo"), Some(&1));
+    /// assert_eq!(m.insert("foo", 2), Some(1));
+    /// assert_eq!(m.get("foo"), Some(&2));
     /// ```
-    pub fn insert(&mut self, key: K, value: V) {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        let k = hasher.finish() % self.key_size;
-        self.hash_table[k as usize].set(key, value);
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+        if !self.hash_table.is_empty() {
+            let i = self.index(hash);
+            if let Some(old) = self.hash_table[i].get_mut(hash, &key) {
+                return Some(mem::replace(old, value));
+            }
+        }
+        // only a new pair needs room, so overwriting never grows the table
+        self.reserve(1);
+        let i = self.index(hash);
+        self.hash_table[i].data.push(Slot { hash, key, value });
+        self.len += 1;
+        None
     }
 
-    /// Gets an optional reference to `V` using key `K`. If `K` doesn't exist
-    /// in the map, `None` is returned.
+    /// Gets an optional reference to `V` using key `K`, or any borrowed form of
+    /// it. If `K` doesn't exist in the map, `None` is returned.
     ///
     /// ## Example:
     /// ```
     /// use tinystd::hashmap::HashMap;
     ///
-    /// let mut m = HashMap::<&str, i32>::new();
-    /// m.insert("foo", 1);
+    /// let mut m = HashMap::<String, i32>::new();
+    /// m.insert("foo".to_string(), 1);
     /// assert_eq!(m.get("foo"), Some(&1));
     /// assert_eq!(m.get("bar"), None);
     /// ```
-    pub fn get(&mut self, key: K) -> Option<&V> {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        let k = hasher.finish() % self.key_size;
-        self.hash_table[k as usize].get(key)
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.hash_table.is_empty() {
+            return None;
+        }
+        let hash = self.hash(key);
+        self.hash_table[self.index(hash)].get(hash, key)
+    }
+
+    /// Gets an optional mutable reference to `V` using key `K`, or any borrowed
+    /// form of it.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m = HashMap::<String, Vec<i32>>::new();
+    /// m.insert("foo".to_string(), vec![1]);
+    /// if let Some(v) = m.get_mut("foo") {
+    ///     v.push(2);
+    /// }
+    /// assert_eq!(m.get("foo"), Some(&vec![1, 2]));
+    /// ```
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.hash_table.is_empty() {
+            return None;
+        }
+        let hash = self.hash(key);
+        let i = self.index(hash);
+        self.hash_table[i].get_mut(hash, key)
     }
 
-    /// Deletes the backing data store for the `(K, V)` pair in the map. Returns
-    /// `true` if the key was found in the map --and thus deleted-- otherwise
-    /// returns `false` if the key was not found in the map.
+    /// Returns `true` if the map holds a value for `key`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m = HashMap::<String, ()>::new();
+    /// m.insert("foo".to_string(), ());
+    /// assert!(m.contains_key("foo"));
+    /// assert!(!m.contains_key("bar"));
+    /// ```
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Deletes the backing data store for the `(K, V)` pair in the map, looking
+    /// it up by `K` or any borrowed form of it. Returns
+    /// the removed value if the key was found in the map, otherwise returns
+    /// `None`.
     /// Example:
     /// ```
     /// use tinystd::hashmap::HashMap;
@@ -138,14 +495,472 @@ where
     ///
     /// // Check that "baz" really gets deleted, and that a double-delete is a no-op
     /// let deleted = m.remove("baz");
-    /// assert_eq!(deleted, true);
+    /// assert_eq!(deleted, Some(1));
     /// assert_eq!(m.get("baz"), None);
-    /// assert_eq!(m.remove("baz"), false);
+    /// assert_eq!(m.remove("baz"), None);
     /// ```
-    pub fn remove(&mut self, key: K) -> bool {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        let k = hasher.finish() % self.key_size;
-        self.hash_table[k as usize].delete(key)
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.hash_table.is_empty() {
+            return None;
+        }
+        let hash = self.hash(key);
+        let i = self.index(hash);
+        let removed = self.hash_table[i].delete(hash, key);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Gets the [`Entry`] for `key`, for in-place insertion or modification.
+    /// The key is hashed exactly once, however the entry is then used.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut counts = HashMap::new();
+    /// for word in "a b a c a b".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(counts.get("a"), Some(&3));
+    /// assert_eq!(counts.get("b"), Some(&2));
+    /// assert_eq!(counts.get("c"), Some(&1));
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&key);
+        let found = if self.hash_table.is_empty() {
+            None
+        } else {
+            let bin = self.index(hash);
+            self.hash_table[bin]
+                .data
+                .iter()
+                .position(|slot| slot.hash == hash && slot.key == key)
+                .map(|index| (bin, index))
+        };
+        match found {
+            Some((bin, index)) => Entry::Occupied(OccupiedEntry {
+                map: self,
+                bin,
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into a single pair of a [`HashMap`], which may or may not be
+/// present. Created by [`HashMap::entry`].
+pub enum Entry<'a, K, V, S = RandomState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// An entry for a key that is in the map
+pub struct OccupiedEntry<'a, K, V, S = RandomState> {
+    map: &'a mut HashMap<K, V, S>,
+    bin: usize,
+    /// The position of the pair within its bin
+    index: usize,
+}
+
+/// An entry for a key that is not in the map
+pub struct VacantEntry<'a, K, V, S = RandomState> {
+    map: &'a mut HashMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S> {
+    /// Inserts `default` if the entry is vacant, and returns a mutable
+    /// reference to the value either way.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], but only computes the value when it is needed.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], using `V::default()`.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied, leaving a vacant entry
+    /// untouched. Chains with the `or_*` methods:
+    ///
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m = HashMap::new();
+    /// m.entry("k").and_modify(|v| *v += 1).or_insert(10);
+    /// m.entry("k").and_modify(|v| *v += 1).or_insert(10);
+    /// assert_eq!(m.get("k"), Some(&11));
+    /// ```
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// The key this entry was created for
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    fn slot(&self) -> &Slot<K, V> {
+        &self.map.hash_table[self.bin].data[self.index]
+    }
+
+    fn slot_mut(&mut self) -> &mut Slot<K, V> {
+        &mut self.map.hash_table[self.bin].data[self.index]
+    }
+
+    /// The key already in the map
+    pub fn key(&self) -> &K {
+        &self.slot().key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.slot().value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.slot_mut().value
+    }
+
+    /// Converts the entry into a mutable reference that lives as long as the
+    /// borrow of the map
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.hash_table[self.bin].data[self.index].value
+    }
+
+    /// Replaces the value, returning the old one. The key is left untouched.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the pair from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the pair from the map, returning both the key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        let slot = self.map.hash_table[self.bin].data.swap_remove(self.index);
+        self.map.len -= 1;
+        (slot.key, slot.value)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
+    /// The key that would be inserted
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back ownership of the key
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the pair, returning a mutable reference to the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let map = self.map;
+        // only grow once the pair is known to be new, then find its bin in the
+        // table as it is after growing
+        map.reserve(1);
+        let i = map.index(self.hash);
+        let bin = &mut map.hash_table[i];
+        bin.data.push(Slot {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        map.len += 1;
+        &mut bin.data.last_mut().expect("just pushed").value
+    }
+}
+
+impl<K, V, S> Default for HashMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, in iteration order
+impl<K, V, S> Debug for HashMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal if they hold the same pairs, regardless of their
+/// capacities or the order in which the pairs were inserted
+impl<K, V, S> PartialEq for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+/// ## Panics
+/// If the key is not in the map. Use [`HashMap::get`] when that is possible.
+impl<K, Q, V, S> Index<&Q> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in HashMap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::FxBuildHasher;
+    use std::hash::Hasher;
+
+    map_conformance!(HashMap);
+
+    #[test]
+    fn stats_of_an_empty_map() {
+        let stats = HashMap::<u32, u32>::new().stats();
+        assert_eq!(stats.bins, 0);
+        assert_eq!(stats.load_factor, 0.0);
+        assert_eq!(stats.mean_chain, 0.0);
+        assert_eq!(stats.max_chain, 0);
+        assert_eq!(stats.histogram, vec![0]);
+    }
+
+    #[test]
+    fn stats_count_chains() {
+        let mut m = HashMap::with_capacity(6);
+        assert_eq!(m.hash_table.len(), 8);
+        // sort some keys by the bin they land in, then build chains of 3, 2
+        // and 1 by hand
+        let mut by_bin = vec![Vec::new(); 8];
+        for key in 0..1000u64 {
+            by_bin[m.index(m.hash(&key))].push(key);
+        }
+        for (keys, chain) in by_bin.iter().zip([3, 2, 1]) {
+            for &key in &keys[..chain] {
+                m.insert(key, ());
+            }
+        }
+        let stats = m.stats();
+        assert_eq!(stats.len, 6);
+        assert_eq!(stats.occupied_bins, 3);
+        assert_eq!(stats.load_factor, 0.75);
+        assert_eq!(stats.max_chain, 3);
+        assert_eq!(stats.mean_chain, 2.0);
+        assert_eq!(stats.histogram, vec![5, 1, 1, 1]);
+    }
+
+    #[test]
+    fn random_keys_spread_like_a_poisson_process() {
+        let m: HashMap<u64, ()> = (0..1 << 16).map(|i| (i * 4096, ())).collect();
+        let stats = m.stats();
+        let empty = stats.histogram[0] as f64 / stats.bins as f64;
+        let expected = (-stats.load_factor).exp();
+        assert!(
+            (empty - expected).abs() < 0.02,
+            "{} of the bins are empty",
+            empty
+        );
+        assert!(stats.max_chain < 12);
+    }
+
+    #[test]
+    fn aligned_keys_spread_even_with_fx() {
+        // Fx leaves the low 12 bits of these hashes all but constant, so
+        // without mixing they would share a handful of bins
+        let mut m = HashMap::with_hasher(FxBuildHasher::default());
+        for i in 0..100_000u64 {
+            m.insert(i * 4096, ());
+        }
+        let stats = m.stats();
+        let empty = stats.histogram[0] as f64 / stats.bins as f64;
+        assert!((empty - (-stats.load_factor).exp()).abs() < 0.02);
+        assert!(stats.max_chain < 12, "max chain {}", stats.max_chain);
+    }
+
+    #[test]
+    fn new_maps_do_not_allocate() {
+        let m = HashMap::<u64, u64>::new();
+        assert_eq!(m.capacity(), 0);
+        assert_eq!(m.hash_table.len(), 0);
+    }
+
+    #[test]
+    fn grows_past_the_load_factor() {
+        let mut m = HashMap::new();
+        for i in 0..10_000u32 {
+            assert_eq!(m.insert(i, i * 2), None);
+            assert!(m.len() <= m.capacity());
+            assert!(m.hash_table.len().is_power_of_two());
+        }
+        assert_eq!(m.len(), 10_000);
+        for i in 0..10_000u32 {
+            assert_eq!(m.get(&i), Some(&(i * 2)));
+        }
+        // the average chain stays short
+        assert!(m.len() as f64 / m.hash_table.len() as f64 <= m.max_load_factor());
+    }
+
+    #[test]
+    fn reserve_and_shrink_to_fit() {
+        let mut m = HashMap::with_capacity(1000);
+        let bins = m.hash_table.len();
+        for i in 0..1000 {
+            m.insert(i, ());
+        }
+        assert_eq!(
+            m.hash_table.len(),
+            bins,
+            "with_capacity should avoid resizes"
+        );
+
+        for i in 10..1000 {
+            assert_eq!(m.remove(&i), Some(()));
+        }
+        m.shrink_to_fit();
+        assert_eq!(m.len(), 10);
+        assert!(m.hash_table.len() < bins);
+        assert!(m.capacity() >= 10);
+        for i in 0..10 {
+            assert_eq!(m.get(&i), Some(&()));
+        }
+
+        for i in 0..10 {
+            m.remove(&i);
+        }
+        m.shrink_to_fit();
+        assert_eq!(m.capacity(), 0);
+        m.reserve(5);
+        assert!(m.capacity() >= 5);
+    }
+
+    #[test]
+    fn overwriting_at_capacity_does_not_grow() {
+        let mut m = HashMap::new();
+        m.insert(0, 0);
+        while m.len() < m.capacity() {
+            m.insert(m.len(), 0);
+        }
+        let bins = m.hash_table.len();
+        for i in 0..m.len() {
+            assert_eq!(m.insert(i, 1), Some(0));
+        }
+        assert_eq!(m.hash_table.len(), bins);
+        assert_eq!(m.len(), m.capacity());
+    }
+
+    #[test]
+    fn max_load_factor_is_configurable() {
+        let mut m = HashMap::new();
+        m.set_max_load_factor(4.0);
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        assert!(m.hash_table.len() <= 32);
+        m.set_max_load_factor(0.5);
+        assert!(m.hash_table.len() >= 200);
+        assert_eq!(m.get(&42), Some(&42));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_zero_load_factor() {
+        HashMap::<u8, u8>::new().set_max_load_factor(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "between 1/64 and 64")]
+    fn rejects_a_tiny_load_factor() {
+        HashMap::<u8, u8>::new().set_max_load_factor(1e-300);
+    }
+
+    #[test]
+    #[should_panic(expected = "between 1/64 and 64")]
+    fn rejects_a_huge_load_factor() {
+        HashMap::<u8, u8>::new().set_max_load_factor(1e300);
+    }
+
+    #[test]
+    fn accepts_the_extreme_load_factors() {
+        for &max_load_factor in &[1.0 / 64.0, 64.0] {
+            let mut m = HashMap::new();
+            m.set_max_load_factor(max_load_factor);
+            m.extend((0..1000).map(|i| (i, i)));
+            assert_eq!(m.len(), 1000);
+        }
+    }
+
+    #[test]
+    fn random_state_seeds_each_map() {
+        let a: HashMap<u64, ()> = HashMap::new();
+        let b: HashMap<u64, ()> = HashMap::new();
+        let hash = |s: &RandomState| {
+            let mut h = s.build_hasher();
+            h.write_u64(7);
+            h.finish()
+        };
+        assert_ne!(hash(a.hasher()), hash(b.hasher()));
     }
 }
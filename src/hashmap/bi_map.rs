@@ -0,0 +1,431 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A bidirectional map: a one-to-one correspondence between left values and
+//! right values, which can be looked up from either side.
+//!
+//! It is two [`HashMap`]s, one from left to right and one from right to
+//! left, kept in step: every pair is in both, so each side holds a clone of
+//! the other's values. No left value is ever paired with two right values, or
+//! the other way around: inserting a pair first removes any pair that shares
+//! either of its values, and reports what it removed as an [`Overwritten`].
+//!
+//! ```
+//! use tinystd::hashmap::{bi_map::Overwritten, BiMap};
+//!
+//! let mut codes = BiMap::new();
+//! codes.insert("Norway", "NO");
+//! codes.insert("Sweden", "SE");
+//! assert_eq!(codes.get_by_left("Norway"), Some(&"NO"));
+//! assert_eq!(codes.get_by_right("SE"), Some(&"Sweden"));
+//!
+//! // "SE" can only belong to one country
+//! let old = codes.insert("Senegal", "SE");
+//! assert_eq!(old, Overwritten::Right("Sweden", "SE"));
+//! assert_eq!(codes.get_by_left("Sweden"), None);
+//! ```
+use super::{HashMap, IntoIter, Iter, Keys};
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    iter::FromIterator,
+};
+
+/// A one-to-one map between `L`s and `R`s. See the [module docs](self).
+#[derive(Clone)]
+pub struct BiMap<L, R, S = RandomState> {
+    left: HashMap<L, R, S>,
+    right: HashMap<R, L, S>,
+}
+
+/// The pairs that [`BiMap::insert`] removed to keep the map one-to-one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overwritten<L, R> {
+    /// Neither value was in the map.
+    Neither,
+    /// The left value was paired with another right value; that pair was
+    /// removed.
+    Left(L, R),
+    /// The right value was paired with another left value; that pair was
+    /// removed.
+    Right(L, R),
+    /// The very same pair was already in the map, and was replaced.
+    Pair(L, R),
+    /// Both values were in different pairs, and both pairs were removed: the
+    /// one with the left value first.
+    Both((L, R), (L, R)),
+}
+
+impl<L, R> BiMap<L, R, RandomState> {
+    /// Constructs a new, empty `BiMap<L, R>`. Nothing is allocated until the
+    /// first insert.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<L, R, S> BiMap<L, R, S> {
+    /// Constructs a new, empty map which hashes both sides with
+    /// `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self
+    where
+        S: Clone,
+    {
+        BiMap {
+            left: HashMap::with_hasher(hash_builder.clone()),
+            right: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        self.left.hasher()
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    /// Removes every pair.
+    pub fn clear(&mut self) {
+        self.left.clear();
+        self.right.clear();
+    }
+
+    /// An iterator over the pairs, in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, L, R> {
+        self.left.iter()
+    }
+
+    /// An iterator over the left values, in arbitrary order.
+    pub fn left_values(&self) -> Keys<'_, L, R> {
+        self.left.keys()
+    }
+
+    /// An iterator over the right values, in arbitrary order.
+    pub fn right_values(&self) -> Keys<'_, R, L> {
+        self.right.keys()
+    }
+}
+
+impl<L, R, S> BiMap<L, R, S>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Pairs `left` with `right`, first removing any pair that holds either
+    /// of them, and returns what was removed.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::{bi_map::Overwritten, BiMap};
+    ///
+    /// let mut m = BiMap::new();
+    /// assert_eq!(m.insert(1, 'a'), Overwritten::Neither);
+    /// assert_eq!(m.insert(2, 'b'), Overwritten::Neither);
+    /// assert_eq!(m.insert(1, 'a'), Overwritten::Pair(1, 'a'));
+    /// assert_eq!(m.insert(1, 'b'), Overwritten::Both((1, 'a'), (2, 'b')));
+    /// assert_eq!(m.len(), 1);
+    /// ```
+    pub fn insert(&mut self, left: L, right: R) -> Overwritten<L, R> {
+        let by_left = self.remove_by_left(&left);
+        let by_right = self.remove_by_right(&right);
+        let overwritten = match (by_left, by_right) {
+            (None, None) => Overwritten::Neither,
+            // removing the pair by its left value took its right value too
+            (Some((l, r)), None) if r == right => Overwritten::Pair(l, r),
+            (Some((l, r)), None) => Overwritten::Left(l, r),
+            (None, Some((l, r))) => Overwritten::Right(l, r),
+            (Some(by_left), Some(by_right)) => Overwritten::Both(by_left, by_right),
+        };
+        self.left.insert(left.clone(), right.clone());
+        self.right.insert(right, left);
+        overwritten
+    }
+
+    /// Pairs `left` with `right` only if neither is in the map yet, handing
+    /// them back otherwise.
+    pub fn insert_no_overwrite(&mut self, left: L, right: R) -> Result<(), (L, R)> {
+        if self.left.contains_key(&left) || self.right.contains_key(&right) {
+            return Err((left, right));
+        }
+        self.left.insert(left.clone(), right.clone());
+        self.right.insert(right, left);
+        Ok(())
+    }
+
+    /// The right value paired with `left`
+    pub fn get_by_left<Q>(&self, left: &Q) -> Option<&R>
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.left.get(left)
+    }
+
+    /// The left value paired with `right`
+    pub fn get_by_right<Q>(&self, right: &Q) -> Option<&L>
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.right.get(right)
+    }
+
+    /// Returns `true` if `left` is paired with some right value.
+    pub fn contains_left<Q>(&self, left: &Q) -> bool
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.left.contains_key(left)
+    }
+
+    /// Returns `true` if `right` is paired with some left value.
+    pub fn contains_right<Q>(&self, right: &Q) -> bool
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.right.contains_key(right)
+    }
+
+    /// Removes the pair holding `left`, returning it.
+    pub fn remove_by_left<Q>(&mut self, left: &Q) -> Option<(L, R)>
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let right = self.left.remove(left)?;
+        let left = self.right.remove(&right).expect("both sides hold the pair");
+        Some((left, right))
+    }
+
+    /// Removes the pair holding `right`, returning it.
+    pub fn remove_by_right<Q>(&mut self, right: &Q) -> Option<(L, R)>
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let left = self.right.remove(right)?;
+        let right = self.left.remove(&left).expect("both sides hold the pair");
+        Some((left, right))
+    }
+
+    /// Keeps only the pairs for which `f` returns `true`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&L, &R) -> bool,
+    {
+        let right = &mut self.right;
+        self.left.retain(|l, r| {
+            let keep = f(l, r);
+            if !keep {
+                right.remove(r);
+            }
+            keep
+        });
+    }
+}
+
+/// Yields the pairs, dropping the right-to-left side.
+impl<L, R, S> IntoIterator for BiMap<L, R, S> {
+    type Item = (L, R);
+    type IntoIter = IntoIter<L, R>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.left.into_iter()
+    }
+}
+
+impl<'a, L, R, S> IntoIterator for &'a BiMap<L, R, S> {
+    type Item = (&'a L, &'a R);
+    type IntoIter = Iter<'a, L, R>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Later pairs overwrite earlier ones that share a value, as with
+/// [`BiMap::insert`].
+impl<L, R, S> FromIterator<(L, R)> for BiMap<L, R, S>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+    S: BuildHasher + Default + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = (L, R)>>(iter: I) -> Self {
+        let mut map = BiMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<L, R, S> Extend<(L, R)> for BiMap<L, R, S>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (L, R)>>(&mut self, iter: I) {
+        for (l, r) in iter {
+            self.insert(l, r);
+        }
+    }
+}
+
+impl<L, R, S> Default for BiMap<L, R, S>
+where
+    S: Default + Clone,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map from left to right values, e.g. `{1: 'a'}`
+impl<L, R, S> Debug for BiMap<L, R, S>
+where
+    L: Debug,
+    R: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// The right-to-left side holds the same pairs, so comparing one side is
+/// enough.
+impl<L, R, S> PartialEq for BiMap<L, R, S>
+where
+    L: Eq + Hash,
+    R: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.left == other.left
+    }
+}
+
+impl<L, R, S> Eq for BiMap<L, R, S>
+where
+    L: Eq + Hash,
+    R: Eq,
+    S: BuildHasher,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Checks that the two sides hold the same pairs
+    fn check<S: BuildHasher>(m: &BiMap<u8, u16, S>) {
+        assert_eq!(m.left.len(), m.right.len());
+        for (l, r) in m.iter() {
+            assert_eq!(m.get_by_right(r), Some(l));
+        }
+    }
+
+    #[test]
+    fn stays_one_to_one() {
+        let mut rng = StdRng::seed_from_u64(0xb1b1);
+        let mut m = BiMap::new();
+        for _ in 0..5000 {
+            let l = rng.gen_range(0..40u8);
+            let r = rng.gen_range(0..40u16);
+            match rng.gen_range(0..4) {
+                0 | 1 => {
+                    let old_right = m.get_by_left(&l).copied();
+                    let old_left = m.get_by_right(&r).copied();
+                    let expected = match (old_right, old_left) {
+                        (None, None) => Overwritten::Neither,
+                        (Some(r2), _) if r2 == r => Overwritten::Pair(l, r),
+                        (Some(r2), None) => Overwritten::Left(l, r2),
+                        (None, Some(l2)) => Overwritten::Right(l2, r),
+                        (Some(r2), Some(l2)) => Overwritten::Both((l, r2), (l2, r)),
+                    };
+                    assert_eq!(m.insert(l, r), expected);
+                    assert_eq!(m.get_by_left(&l), Some(&r));
+                    assert_eq!(m.get_by_right(&r), Some(&l));
+                }
+                2 => {
+                    let expected = m.get_by_left(&l).map(|&r| (l, r));
+                    assert_eq!(m.remove_by_left(&l), expected);
+                    assert!(!m.contains_left(&l));
+                }
+                _ => {
+                    let expected = m.get_by_right(&r).map(|&l| (l, r));
+                    assert_eq!(m.remove_by_right(&r), expected);
+                    assert!(!m.contains_right(&r));
+                }
+            }
+            check(&m);
+        }
+    }
+
+    #[test]
+    fn insert_no_overwrite_refuses_taken_values() {
+        let mut m = BiMap::new();
+        assert_eq!(m.insert_no_overwrite(1, 10), Ok(()));
+        assert_eq!(m.insert_no_overwrite(1, 20), Err((1, 20)));
+        assert_eq!(m.insert_no_overwrite(2, 10), Err((2, 10)));
+        assert_eq!(m.insert_no_overwrite(2, 20), Ok(()));
+        assert_eq!(m.len(), 2);
+        check(&m);
+    }
+
+    #[test]
+    fn retain_removes_both_sides() {
+        let mut m: BiMap<u8, u16> = (0..50).map(|i| (i, i as u16 * 3)).collect();
+        m.retain(|l, _| l % 2 == 0);
+        assert_eq!(m.len(), 25);
+        assert!(!m.contains_right(&3));
+        assert_eq!(m.get_by_right(&6), Some(&2));
+        check(&m);
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut m = BiMap::new();
+        m.insert("one".to_string(), vec![1u8]);
+        assert_eq!(m.get_by_left("one"), Some(&vec![1]));
+        assert_eq!(m.get_by_right(&[1u8][..]), Some(&"one".to_string()));
+        assert_eq!(
+            m.remove_by_right(&[1u8][..]),
+            Some(("one".to_string(), vec![1]))
+        );
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn collecting_overwrites() {
+        let m: BiMap<_, _> = vec![(1, 'a'), (2, 'a'), (2, 'b')].into_iter().collect();
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get_by_left(&2), Some(&'b'));
+        assert_eq!(format!("{:?}", m), "{2: 'b'}");
+        let mut pairs: Vec<_> = m.clone().into_iter().collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(2, 'b')]);
+        assert_eq!(m, m.clone());
+    }
+}
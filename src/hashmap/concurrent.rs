@@ -0,0 +1,523 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A hash map that can be shared between threads, by *lock striping* (as in
+//! Java's original [`ConcurrentHashMap`][1]).
+//!
+//! Wrapping a whole [`HashMap`] in one lock makes every thread wait for every
+//! other. Instead, the keys are split across a number of *shards*, each its own
+//! `HashMap` behind its own [`RwLock`]. A key's hash picks its shard, so
+//! threads working on different shards never contend, and threads only reading
+//! a shard share its lock.
+//!
+//! ```
+//! use std::{sync::Arc, thread};
+//! use tinystd::hashmap::ConcurrentHashMap;
+//!
+//! let m = Arc::new(ConcurrentHashMap::new());
+//! let handles: Vec<_> = (0..4)
+//!     .map(|t| {
+//!         let m = Arc::clone(&m);
+//!         thread::spawn(move || {
+//!             for i in 0..100 {
+//!                 m.insert(t * 100 + i, t);
+//!             }
+//!         })
+//!     })
+//!     .collect();
+//! for h in handles {
+//!     h.join().unwrap();
+//! }
+//! assert_eq!(m.len(), 400);
+//! assert_eq!(*m.get(&250).unwrap(), 2);
+//! ```
+//!
+//! A shard's `HashMap` picks a bin with the low bits of the hash, so the shard
+//! is picked with the high bits, which keeps the two choices independent.
+//!
+//! [1]: https://docs.oracle.com/javase/7/docs/api/java/util/concurrent/ConcurrentHashMap.html
+use super::{Entry, HashMap};
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    ops::{Deref, DerefMut},
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread,
+};
+
+/// How many shards to make per thread the machine can run at once. More shards
+/// than threads makes it less likely that two threads want the same one.
+const SHARDS_PER_THREAD: usize = 4;
+
+/// Locks a shard for reading. A panic while a shard is locked for writing
+/// (in an [`Entry`] closure, say) poisons its lock, but a [`HashMap`] is whole
+/// between any two of its own steps, so the poison is ignored rather than
+/// leaving the shard unusable for good.
+pub(in crate::hashmap) fn read<K, V, S>(
+    shard: &RwLock<HashMap<K, V, S>>,
+) -> RwLockReadGuard<'_, HashMap<K, V, S>> {
+    shard.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks a shard for writing, ignoring poison like [`read`]
+fn write<K, V, S>(shard: &RwLock<HashMap<K, V, S>>) -> RwLockWriteGuard<'_, HashMap<K, V, S>> {
+    shard.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A [`HashMap`] split into independently locked shards. See the
+/// [module docs](self).
+///
+/// A panic in a closure the map calls with a shard locked, like the one given
+/// to [`ConcurrentHashMap::entry`], doesn't poison the map: whatever the
+/// closure did to its [`Entry`] before panicking stays done, and every other
+/// thread carries on.
+pub struct ConcurrentHashMap<K, V, S = RandomState> {
+    /// A power of two in length
+    pub(in crate::hashmap) shards: Box<[RwLock<HashMap<K, V, S>>]>,
+    /// Hashes keys to pick their shard. Each shard has a clone of it, too.
+    hash_builder: S,
+}
+
+impl<K, V> ConcurrentHashMap<K, V, RandomState> {
+    /// Constructs a new, empty map with a few shards per available thread.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Constructs a new, empty map with at least `shards` shards.
+    ///
+    /// ## Panics
+    /// If `shards` is zero.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K, V, S> ConcurrentHashMap<K, V, S>
+where
+    S: Clone,
+{
+    /// Constructs a new, empty map with a few shards per available thread,
+    /// which hashes its keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards_and_hasher(threads * SHARDS_PER_THREAD, hash_builder)
+    }
+
+    /// Combines [`ConcurrentHashMap::with_shards`] and
+    /// [`ConcurrentHashMap::with_hasher`].
+    pub fn with_shards_and_hasher(shards: usize, hash_builder: S) -> Self {
+        assert!(shards > 0, "a ConcurrentHashMap needs at least one shard");
+        let shards = (0..shards.next_power_of_two())
+            .map(|_| RwLock::new(HashMap::with_hasher(hash_builder.clone())))
+            .collect();
+        ConcurrentHashMap {
+            shards,
+            hash_builder,
+        }
+    }
+}
+
+impl<K, V, S> ConcurrentHashMap<K, V, S> {
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of shards, always a power of two
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The number of pairs in the map. The shards are counted one at a time,
+    /// so while other threads are inserting or removing, this is only a
+    /// snapshot of a moving target.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read(shard).len()).sum()
+    }
+
+    /// Like [`ConcurrentHashMap::len`], returns `true` only if every shard was
+    /// empty when it was looked at.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read(shard).is_empty())
+    }
+
+    /// Removes every pair, one shard at a time.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            write(shard).clear();
+        }
+    }
+
+    /// Consumes the map, returning its shards.
+    pub fn into_shards(self) -> Vec<HashMap<K, V, S>> {
+        self.shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| shard.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect()
+    }
+}
+
+impl<K, V, S> ConcurrentHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// The shard that `key`, or any borrowed form of it, belongs to
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> &RwLock<HashMap<K, V, S>> {
+        let hash = self.hash_builder.hash_one(key);
+        &self.shards[(hash >> 32) as usize & (self.shards.len() - 1)]
+    }
+
+    /// Inserts `V` at key `K`, locking only the key's shard. If the key was
+    /// already present, its old value is replaced and returned.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        write(self.shard(&key)).insert(key, value)
+    }
+
+    /// A read guard on the value for `key`, or any borrowed form of it. Other
+    /// threads can still read the key's shard, but can't write to it until the
+    /// guard is dropped.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::ConcurrentHashMap;
+    ///
+    /// let m = ConcurrentHashMap::new();
+    /// m.insert("a", vec![1, 2, 3]);
+    /// assert_eq!(m.get("a").map(|v| v.len()), Some(3));
+    /// assert!(m.get("b").is_none());
+    /// ```
+    pub fn get<Q>(&self, key: &Q) -> Option<Ref<'_, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let guard = read(self.shard(key));
+        let value: *const V = guard.get(key)?;
+        Some(Ref {
+            _guard: guard,
+            value,
+        })
+    }
+
+    /// A write guard on the value for `key`, or any borrowed form of it. No
+    /// other thread can use the key's shard until the guard is dropped.
+    pub fn get_mut<Q>(&self, key: &Q) -> Option<RefMut<'_, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut guard = write(self.shard(key));
+        let value: *mut V = guard.get_mut(key)?;
+        Some(RefMut {
+            _guard: guard,
+            value,
+        })
+    }
+
+    /// A clone of the value for `key`, which doesn't hold the shard's lock
+    /// any longer than the lookup.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Clone,
+    {
+        read(self.shard(key)).get(key).cloned()
+    }
+
+    /// Returns `true` if the map holds a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        read(self.shard(key)).contains_key(key)
+    }
+
+    /// Removes the pair for `key`, returning its value if it was present.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        write(self.shard(key)).remove(key)
+    }
+
+    /// Calls `f` with the [`Entry`] for `key`, holding the key's shard locked
+    /// for writing meanwhile, so that a read-modify-write is atomic.
+    /// Should `f` panic, the shard is unlocked as the panic unwinds, and the
+    /// map stays usable; see [`ConcurrentHashMap`].
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::ConcurrentHashMap;
+    ///
+    /// let counts = ConcurrentHashMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.entry(word, |e| *e.or_insert(0) += 1);
+    /// }
+    /// assert_eq!(counts.get_cloned("the"), Some(2));
+    /// ```
+    pub fn entry<F, R>(&self, key: K, f: F) -> R
+    where
+        F: FnOnce(Entry<'_, K, V, S>) -> R,
+    {
+        let mut shard = write(self.shard(&key));
+        f(shard.entry(key))
+    }
+
+    /// Keeps only the pairs for which `f` returns `true`, one shard at a time.
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for shard in self.shards.iter() {
+            write(shard).retain(&mut f);
+        }
+    }
+}
+
+impl<K, V, S> Default for ConcurrentHashMap<K, V, S>
+where
+    S: Clone + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, reading one shard at a time
+impl<K, V, S> Debug for ConcurrentHashMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for shard in self.shards.iter() {
+            map.entries(read(shard).iter());
+        }
+        map.finish()
+    }
+}
+
+/// A read-locked value in a [`ConcurrentHashMap`]. Created by
+/// [`ConcurrentHashMap::get`].
+pub struct Ref<'a, K, V, S> {
+    _guard: RwLockReadGuard<'a, HashMap<K, V, S>>,
+    value: *const V,
+}
+
+impl<K, V, S> Deref for Ref<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // SAFETY: `value` points into the shard, which can't change while
+        // `_guard` holds it locked for reading.
+        unsafe { &*self.value }
+    }
+}
+
+impl<K, V: Debug, S> Debug for Ref<'_, K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A write-locked value in a [`ConcurrentHashMap`]. Created by
+/// [`ConcurrentHashMap::get_mut`].
+pub struct RefMut<'a, K, V, S> {
+    _guard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    value: *mut V,
+}
+
+impl<K, V, S> Deref for RefMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // SAFETY: `value` points into the shard, which nothing else can touch
+        // while `_guard` holds it locked for writing.
+        unsafe { &*self.value }
+    }
+}
+
+impl<K, V, S> DerefMut for RefMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        // SAFETY: as for `deref`, and `&mut self` makes this the only
+        // reference handed out at a time.
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<K, V: Debug, S> Debug for RefMut<'_, K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    const THREADS: usize = 8;
+
+    #[test]
+    fn rounds_shards_up_to_a_power_of_two() {
+        let m = ConcurrentHashMap::<u32, u32>::with_shards(5);
+        assert_eq!(m.shards(), 8);
+        assert_eq!(ConcurrentHashMap::<u32, u32>::with_shards(1).shards(), 1);
+        assert!(ConcurrentHashMap::<u32, u32>::new().shards() >= SHARDS_PER_THREAD);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one shard")]
+    fn rejects_zero_shards() {
+        ConcurrentHashMap::<u32, u32>::with_shards(0);
+    }
+
+    #[test]
+    fn single_threaded_basics() {
+        let m = ConcurrentHashMap::with_shards(4);
+        assert!(m.is_empty());
+        assert_eq!(m.insert("a".to_string(), 1), None);
+        assert_eq!(m.insert("a".to_string(), 2), Some(1));
+        assert_eq!(m.insert("b".to_string(), 3), None);
+        assert_eq!(m.len(), 2);
+        assert_eq!(*m.get("a").unwrap(), 2);
+        *m.get_mut("b").unwrap() += 10;
+        assert_eq!(m.get_cloned("b"), Some(13));
+        assert!(m.contains_key("a"));
+        assert_eq!(m.remove("a"), Some(2));
+        assert!(!m.contains_key("a"));
+        assert_eq!(format!("{:?}", m), r#"{"b": 13}"#);
+        m.clear();
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn keys_spread_over_the_shards() {
+        let m = ConcurrentHashMap::with_shards(8);
+        for i in 0..8000 {
+            m.insert(i, ());
+        }
+        for shard in m.into_shards() {
+            assert!(shard.len() > 500, "a shard holds only {}", shard.len());
+        }
+    }
+
+    #[test]
+    fn readers_share_a_shard() {
+        let m = ConcurrentHashMap::with_shards(1);
+        m.insert(1, 1);
+        m.insert(2, 2);
+        // with a single shard, both guards are on the same lock
+        let a = m.get(&1).unwrap();
+        let b = m.get(&2).unwrap();
+        assert_eq!(*a + *b, 3);
+    }
+
+    #[test]
+    fn stress_disjoint_writers_and_readers() {
+        let m = ConcurrentHashMap::with_shards(16);
+        let per_thread = 5000;
+        let barrier = Barrier::new(THREADS);
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (m, barrier) = (&m, &barrier);
+                s.spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(t as u64);
+                    barrier.wait();
+                    for i in 0..per_thread {
+                        let key = t * per_thread + i;
+                        assert_eq!(m.insert(key, key), None);
+                        // read back something this thread already wrote
+                        let old = t * per_thread + rng.gen_range(0..=i);
+                        assert_eq!(m.get_cloned(&old), Some(old));
+                        if i % 3 == 0 {
+                            assert_eq!(m.remove(&key), Some(key));
+                            m.insert(key, key);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(m.len(), THREADS * per_thread);
+        for key in 0..THREADS * per_thread {
+            assert_eq!(*m.get(&key).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn stress_contended_entries() {
+        let m = ConcurrentHashMap::with_shards(4);
+        let rounds = 2000;
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for i in 0..rounds {
+                        m.entry(i % 10, |e| *e.or_insert(0) += 1);
+                        if let Some(mut v) = m.get_mut(&(i % 7 + 100)) {
+                            *v += 1;
+                        } else {
+                            m.entry(i % 7 + 100, |e| *e.or_insert(0) += 1);
+                        }
+                    }
+                });
+            }
+        });
+        let total: usize = (0..10).map(|k| m.get_cloned(&k).unwrap()).sum();
+        assert_eq!(total, THREADS * rounds);
+        let total: usize = (100..107).map(|k| m.get_cloned(&k).unwrap()).sum();
+        assert_eq!(total, THREADS * rounds);
+        m.retain(|k, _| *k < 100);
+        assert_eq!(m.len(), 10);
+    }
+
+    #[test]
+    fn a_panicking_entry_closure_does_not_poison_the_map() {
+        let m = Arc::new(ConcurrentHashMap::with_shards(1));
+        m.insert(1, 1);
+        let shared = Arc::clone(&m);
+        let result = thread::spawn(move || {
+            shared.entry(2, |e| {
+                e.or_insert(2);
+                panic!("the closure gave up");
+            })
+        })
+        .join();
+        assert!(result.is_err());
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get_cloned(&2), Some(2));
+        m.insert(3, 3);
+        m.retain(|k, _| *k != 1);
+        assert_eq!(format!("{:?}", m).len(), "{2: 2, 3: 3}".len());
+        let mut pairs: Vec<_> = Arc::try_unwrap(m)
+            .ok()
+            .unwrap()
+            .into_shards()
+            .into_iter()
+            .flatten()
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(2, 2), (3, 3)]);
+    }
+}
@@ -0,0 +1,493 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests that every map in [`super`] has to pass, however it is laid out.
+//!
+//! `map_conformance!(Map)` expands to a `conformance` module of tests, where
+//! `Map` and its `Entry` type must be in scope at the call site. Tests of a
+//! map's internals (its table size, its invariants) stay with the map, but can
+//! share the degenerate hashers here to force collisions.
+
+use std::hash::Hasher;
+
+/// Sends every key to the same place, so every operation has to get past
+/// collisions
+#[derive(Default)]
+pub(in crate::hashmap) struct Zero;
+
+impl Hasher for Zero {
+    fn write(&mut self, _: &[u8]) {}
+
+    fn finish(&self) -> u64 {
+        0
+    }
+}
+
+/// Hashes everything to its low byte: that of the last `u64` written, or the
+/// last byte of anything else, so tests can pick which keys collide. There are
+/// only 256 distinct hashes however many keys there are.
+#[derive(Default)]
+pub(in crate::hashmap) struct LowByte(u64);
+
+impl Hasher for LowByte {
+    fn finish(&self) -> u64 {
+        self.0 & 0xff
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0 << 8 | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+macro_rules! map_conformance {
+    ($map:ident) => {
+        mod conformance {
+            use super::*;
+            use crate::hashmap::conformance::Zero;
+            use rand::{rngs::StdRng, Rng, SeedableRng};
+            use std::hash::BuildHasherDefault;
+
+            fn squares(n: u32) -> $map<u32, u32> {
+                (0..n).map(|i| (i, i * i)).collect()
+            }
+
+            #[test]
+            fn owned_keys_and_values() {
+                let mut m: $map<String, Vec<u8>> = $map::new();
+                assert_eq!(m.insert("a".to_string(), vec![1, 2]), None);
+                assert_eq!(m.insert("b".to_string(), vec![]), None);
+                assert_eq!(m.insert("a".to_string(), vec![3]), Some(vec![1, 2]));
+                assert_eq!(m.get("a"), Some(&vec![3]));
+                assert_eq!(m.remove("b"), Some(vec![]));
+                assert_eq!(m.remove("b"), None);
+                assert_eq!(m.get("b"), None);
+            }
+
+            #[test]
+            fn grows_from_empty() {
+                let mut m = $map::new();
+                assert_eq!(m.capacity(), 0);
+                for i in 0..10_000u32 {
+                    assert_eq!(m.insert(i, i * 2), None);
+                    assert!(m.len() <= m.capacity());
+                }
+                assert_eq!(m.len(), 10_000);
+                for i in 0..10_000u32 {
+                    assert_eq!(m.get(&i), Some(&(i * 2)));
+                }
+            }
+
+            #[test]
+            fn with_capacity_and_reserve() {
+                let mut m = $map::with_capacity(1000);
+                let capacity = m.capacity();
+                assert!(capacity >= 1000);
+                for i in 0..1000 {
+                    m.insert(i, ());
+                }
+                assert_eq!(m.capacity(), capacity, "with_capacity should avoid resizes");
+                m.reserve(1000);
+                assert!(m.capacity() >= 2000);
+
+                m.retain(|&k, _| k < 10);
+                m.shrink_to_fit();
+                assert!(m.capacity() < capacity);
+                assert!(m.capacity() >= 10);
+                for i in 0..10 {
+                    assert_eq!(m.get(&i), Some(&()));
+                }
+            }
+
+            #[test]
+            fn lookups_through_shared_references() {
+                let mut m: $map<String, usize> = $map::new();
+                for word in "the quick brown fox".split(' ') {
+                    m.insert(word.to_string(), word.len());
+                }
+                let shared = &m;
+                assert_eq!(shared.get("quick"), Some(&5));
+                assert!(shared.contains_key("fox"));
+                assert!(!shared.contains_key("dog"));
+
+                *m.get_mut("fox").unwrap() += 1;
+                assert_eq!(m.get("fox"), Some(&4));
+                assert_eq!(m.get_mut("dog"), None);
+            }
+
+            #[test]
+            fn lookups_on_an_empty_map() {
+                let mut m = $map::<String, ()>::new();
+                assert_eq!(m.get("a"), None);
+                assert_eq!(m.get_mut("a"), None);
+                assert_eq!(m.remove("a"), None);
+                assert!(!m.contains_key("a"));
+            }
+
+            #[test]
+            fn entry_groups_values() {
+                let mut groups: $map<usize, Vec<&str>> = $map::new();
+                for word in "one two three four five six".split(' ') {
+                    groups.entry(word.len()).or_default().push(word);
+                }
+                assert_eq!(groups.len(), 3);
+                assert_eq!(groups.get(&3), Some(&vec!["one", "two", "six"]));
+                assert_eq!(groups.get(&4), Some(&vec!["four", "five"]));
+                assert_eq!(groups.get(&5), Some(&vec!["three"]));
+            }
+
+            #[test]
+            fn occupied_and_vacant_entries() {
+                let mut m: $map<String, i32> = $map::new();
+                match m.entry("a".to_string()) {
+                    Entry::Vacant(e) => {
+                        assert_eq!(e.key(), "a");
+                        *e.insert(1) += 1;
+                    }
+                    Entry::Occupied(_) => unreachable!(),
+                }
+                assert_eq!(m.get("a"), Some(&2));
+
+                match m.entry("a".to_string()) {
+                    Entry::Occupied(mut e) => {
+                        assert_eq!(e.key(), "a");
+                        assert_eq!(e.insert(5), 2);
+                        assert_eq!(e.get(), &5);
+                        assert_eq!(e.remove(), 5);
+                    }
+                    Entry::Vacant(_) => unreachable!(),
+                }
+                assert_eq!(m.len(), 0);
+                assert!(!m.contains_key("a"));
+
+                assert_eq!(*m.entry("b".to_string()).or_insert_with(|| 7), 7);
+                assert_eq!(m.entry("b".to_string()).key(), "b");
+                assert_eq!(*m.entry("b".to_string()).or_insert(0), 7);
+                m.entry("b".to_string())
+                    .and_modify(|v| *v *= 2)
+                    .or_insert(0);
+                assert_eq!(m["b"], 14);
+                assert_eq!(m.len(), 1);
+            }
+
+            #[test]
+            fn entries_survive_growth() {
+                let mut m = $map::new();
+                for i in 0..1000u32 {
+                    *m.entry(i % 100).or_insert(0) += 1;
+                    *m.entry(1000 + i).or_insert(0) += 1;
+                }
+                assert_eq!(m.len(), 1100);
+                for i in 0..100 {
+                    assert_eq!(m.get(&i), Some(&10));
+                }
+                for i in 1000..2000 {
+                    assert_eq!(m.get(&i), Some(&1));
+                }
+            }
+
+            #[test]
+            fn a_full_map_only_grows_for_new_keys() {
+                let mut m = $map::new();
+                m.insert(0, 0);
+                while m.len() < m.capacity() {
+                    m.insert(m.len(), 0);
+                }
+                let capacity = m.capacity();
+                for i in 0..m.len() {
+                    assert_eq!(m.insert(i, 1), Some(0));
+                    *m.entry(i).or_insert(0) += 1;
+                    assert!(matches!(m.entry(i), Entry::Occupied(_)));
+                }
+                assert_eq!(m.capacity(), capacity);
+
+                let new = m.len();
+                *m.entry(new).or_insert(0) += 1;
+                assert!(m.capacity() > capacity);
+                assert_eq!(m.get(&new), Some(&1));
+                assert_eq!(m.get(&0), Some(&2));
+            }
+
+            #[test]
+            fn equality_ignores_capacity_and_order() {
+                let a: $map<_, _> = (0..100).map(|i| (i, i.to_string())).collect();
+                let mut b = $map::with_capacity(1000);
+                for i in (0..100).rev() {
+                    b.insert(i, i.to_string());
+                }
+                assert_eq!(a, b);
+                b.insert(0, "zero".to_string());
+                assert_ne!(a, b);
+                b.remove(&0);
+                assert_ne!(a, b);
+
+                let mut c = a.clone();
+                assert_eq!(a, c);
+                c.clear();
+                assert!(c.is_empty());
+                assert_eq!(c, $map::new());
+            }
+
+            #[test]
+            fn debug_prints_as_a_map() {
+                let mut m = $map::new();
+                assert_eq!(format!("{:?}", m), "{}");
+                m.insert("a", 1);
+                assert_eq!(format!("{:?}", m), r#"{"a": 1}"#);
+            }
+
+            #[test]
+            fn index_by_borrowed_key() {
+                let mut m = $map::new();
+                m.insert("a".to_string(), 1);
+                assert_eq!(m["a"], 1);
+            }
+
+            #[test]
+            #[should_panic(expected = "key not found")]
+            fn index_panics_on_missing_keys() {
+                let m = $map::<String, ()>::new();
+                m["a"]
+            }
+
+            #[test]
+            fn every_hasher_in_the_crate_works() {
+                use crate::hash::{FnvBuildHasher, FxBuildHasher, SipState};
+
+                fn exercise<S: BuildHasher>(mut m: $map<String, usize, S>) {
+                    for i in 0..1000 {
+                        m.insert(i.to_string(), i);
+                    }
+                    for i in 0..1000 {
+                        assert_eq!(m.get(i.to_string().as_str()), Some(&i));
+                    }
+                    assert_eq!(m.remove("500"), Some(500));
+                    assert_eq!(m.len(), 999);
+                }
+                exercise($map::with_hasher(FxBuildHasher::default()));
+                exercise($map::with_hasher(FnvBuildHasher::default()));
+                exercise($map::with_capacity_and_hasher(10, SipState::new()));
+                exercise($map::new());
+            }
+
+            #[test]
+            fn aligned_keys_with_a_weak_hasher() {
+                use crate::hash::FxBuildHasher;
+
+                // Fx leaves the low bits of these hashes all but constant, so a
+                // map that indexed by them directly would degrade to a scan
+                let mut m = $map::with_hasher(FxBuildHasher::default());
+                for i in 0..1 << 14u64 {
+                    m.insert(i * 4096, i);
+                }
+                assert_eq!(m.len(), 1 << 14);
+                for i in 0..1 << 14u64 {
+                    assert_eq!(m.get(&(i * 4096)), Some(&i));
+                }
+            }
+
+            #[test]
+            fn survives_total_collisions() {
+                let mut m = $map::with_hasher(BuildHasherDefault::<Zero>::default());
+                for i in 0..200u32 {
+                    m.insert(i, i);
+                }
+                for i in (0..200).step_by(3) {
+                    assert_eq!(m.remove(&i), Some(i));
+                }
+                for i in 0..200 {
+                    assert_eq!(m.get(&i), if i % 3 == 0 { None } else { Some(&i) });
+                }
+                assert_eq!(m.iter().count(), m.len());
+            }
+
+            #[test]
+            fn values_need_not_be_eq() {
+                let mut m = $map::new();
+                m.insert(1u8, 0.5f64);
+                m.insert(2u8, f64::NAN);
+                assert_eq!(m.get(&1), Some(&0.5));
+                assert!(m.get(&2).unwrap().is_nan());
+            }
+
+            #[test]
+            fn iterators_visit_every_pair_once() {
+                let mut m = squares(100);
+                assert_eq!(m.iter().len(), 100);
+                let mut keys: Vec<_> = m.keys().copied().collect();
+                keys.sort_unstable();
+                assert_eq!(keys, (0..100).collect::<Vec<_>>());
+                assert_eq!(m.values().map(|&v| v as u64).sum::<u64>(), 328_350);
+
+                for (k, v) in &mut m {
+                    *v += k;
+                }
+                for v in m.values_mut() {
+                    *v += 1;
+                }
+                for (k, v) in &m {
+                    assert_eq!(*v, k * k + k + 1);
+                }
+
+                let mut owned: Vec<_> = m.into_iter().collect();
+                owned.sort_unstable();
+                assert_eq!(owned.len(), 100);
+                assert_eq!(owned[10], (10, 111));
+            }
+
+            #[test]
+            fn iterating_an_empty_map() {
+                let mut m = $map::<u8, u8>::new();
+                assert_eq!(m.iter().next(), None);
+                assert_eq!(m.iter_mut().next(), None);
+                assert_eq!(m.drain().next(), None);
+                assert_eq!(m.extract_if(|_, _| true).next(), None);
+                assert_eq!(m.into_iter().next(), None);
+            }
+
+            #[test]
+            fn dropping_a_partial_drain_still_empties_the_map() {
+                let mut m = squares(50);
+                let capacity = m.capacity();
+                let taken: Vec<_> = m.drain().take(5).collect();
+                assert_eq!(taken.len(), 5);
+                assert!(m.is_empty());
+                assert_eq!(m.capacity(), capacity);
+                assert_eq!(m.iter().count(), 0);
+                m.insert(1, 1);
+                assert_eq!(m.iter().collect::<Vec<_>>(), vec![(&1, &1)]);
+            }
+
+            #[test]
+            fn a_drain_can_move_to_another_thread() {
+                fn send_and_sync<T: Send + Sync>(t: T) -> T {
+                    t
+                }
+                let mut m = squares(50);
+                let drain = send_and_sync(m.drain());
+                let mut pairs = std::thread::scope(|s| {
+                    s.spawn(move || drain.collect::<Vec<_>>()).join().unwrap()
+                });
+                pairs.sort();
+                assert_eq!(pairs, (0..50).map(|i| (i, i * i)).collect::<Vec<_>>());
+                assert!(m.is_empty());
+            }
+
+            /// `mem::forget` is safe, so a map must survive its `Drain` never
+            /// being dropped: it is left empty, but still a working map
+            #[test]
+            fn leaking_a_drain_leaves_an_empty_map() {
+                let mut m = squares(50);
+                let mut drain = m.drain();
+                assert_eq!(drain.next().map(|(k, v)| v - k * k), Some(0));
+                std::mem::forget(drain);
+                assert!(m.is_empty());
+                assert_eq!(m.iter().count(), 0);
+                assert_eq!(m.get(&3), None);
+                assert_eq!(m.insert(3, 99), None);
+                assert_eq!(m.len(), 1);
+                assert_eq!(m.iter().collect::<Vec<_>>(), vec![(&3, &99)]);
+                m.extend((0..50).map(|i| (i, i)));
+                assert_eq!(m.len(), 50);
+                assert_eq!(m.iter().count(), 50);
+            }
+
+            #[test]
+            fn dropping_a_partial_extract_if_keeps_the_rest() {
+                let mut m = squares(50);
+                let taken: Vec<_> = m.extract_if(|_, _| true).take(5).collect();
+                assert_eq!(taken.len(), 5);
+                assert_eq!(m.len(), 45);
+                assert_eq!(m.iter().count(), 45);
+                for (k, _) in taken {
+                    assert!(!m.contains_key(&k));
+                }
+            }
+
+            #[test]
+            fn extract_if_tests_every_pair_once() {
+                let mut m = squares(1000);
+                let mut tested = 0;
+                let odd = m
+                    .extract_if(|k, _| {
+                        tested += 1;
+                        k % 2 == 1
+                    })
+                    .count();
+                assert_eq!(tested, 1000);
+                assert_eq!(odd, 500);
+                assert!(m.keys().all(|k| k % 2 == 0));
+            }
+
+            #[test]
+            fn retain_can_modify_kept_values() {
+                let mut m = squares(10);
+                m.retain(|&k, v| {
+                    *v += 1;
+                    k < 3
+                });
+                assert_eq!(m.len(), 3);
+                assert_eq!(m[&2], 5);
+            }
+
+            #[test]
+            fn extend_overwrites_and_accepts_references() {
+                let mut m = squares(3);
+                m.extend(vec![(1, 100), (5, 25)]);
+                let other = squares(8);
+                m.extend(&other);
+                assert_eq!(m.len(), 8);
+                assert_eq!(m[&1], 1);
+                assert_eq!(m[&7], 49);
+            }
+
+            /// Random operations, checked step by step against `std`'s map
+            #[test]
+            fn agrees_with_std_under_random_operations() {
+                let mut rng = StdRng::seed_from_u64(0x5eed);
+                let mut ours = $map::new();
+                let mut theirs = std::collections::HashMap::new();
+                for _ in 0..20_000 {
+                    // a small key space, so that hits, misses and overwrites
+                    // are all common
+                    let key: u16 = rng.gen_range(0..512);
+                    match rng.gen_range(0..6) {
+                        0 | 1 => assert_eq!(ours.insert(key, key), theirs.insert(key, key)),
+                        2 => assert_eq!(ours.remove(&key), theirs.remove(&key)),
+                        3 => assert_eq!(ours.get(&key), theirs.get(&key)),
+                        4 => {
+                            *ours.entry(key).or_insert(0) += 1;
+                            *theirs.entry(key).or_insert(0) += 1;
+                        }
+                        _ => {
+                            if rng.gen_ratio(1, 100) {
+                                ours.retain(|k, _| k % 7 != 0);
+                                theirs.retain(|k, _| k % 7 != 0);
+                            }
+                        }
+                    }
+                    assert_eq!(ours.len(), theirs.len());
+                }
+                let mut ours: Vec<_> = ours.into_iter().collect();
+                let mut theirs: Vec<_> = theirs.into_iter().collect();
+                ours.sort_unstable();
+                theirs.sort_unstable();
+                assert_eq!(ours, theirs);
+            }
+        }
+    };
+}
@@ -0,0 +1,944 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A bucketized [cuckoo hash][1] map, with a [stash][2].
+//!
+//! Every key may live in exactly one of [`HASHES`] buckets, each picked by its
+//! own hash function, and each bucket holds [`SLOTS`] pairs. A lookup never
+//! looks anywhere else (besides a tiny stash, below), so it costs at most
+//! `HASHES * SLOTS` comparisons however full the table is or however the keys
+//! were inserted.
+//!
+//! The work moves to inserts instead. When every slot of a new key's buckets
+//! is taken, the key evicts one of the pairs there, which moves to *its*
+//! other bucket, possibly evicting another pair in turn, and so on, like a
+//! cuckoo chick pushing eggs out of the nest. With two choices of four-slot
+//! buckets these walks stay short up to a load factor well over 90%.
+//!
+//! A walk that goes on too long has most likely run into a cycle. Rather than
+//! rebuilding the whole table straight away, the pair left over is parked in
+//! the stash, which every lookup also searches. Only when the stash grows past
+//! a handful of pairs is the table rebuilt with fresh hash functions. A small
+//! stash makes those rebuilds astronomically rare.
+//!
+//! All of the hash functions are derived from the one 64-bit hash that the
+//! map's [`BuildHasher`] produces for a key, by mixing it with a per-function
+//! seed. A rebuild only changes the seeds, so keys are never rehashed. It also
+//! means that keys the `BuildHasher` can't tell apart land in the same
+//! buckets whatever the seeds, and no rebuild or larger table can separate
+//! them. With a hasher that bad they pile up in the stash, and rather than let
+//! every lookup degrade to a linear search of it, inserting panics once more
+//! than [`MAX_OVERFULL_STASH`] pairs are stuck there.
+//!
+//! [1]: https://en.wikipedia.org/wiki/Cuckoo_hashing
+//! [2]: https://www.eecs.harvard.edu/~michaelm/postscripts/esa2008full.pdf
+use super::Slot;
+use crate::hash::fmix64;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::Index,
+};
+
+mod iter;
+
+use super::open::SlotTable;
+pub use super::open::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+pub use iter::ExtractIf;
+
+/// The number of hash functions, and so of buckets a key may live in
+pub const HASHES: usize = 2;
+
+/// The number of pairs in a bucket
+pub const SLOTS: usize = 4;
+
+/// The smallest number of buckets a map allocates
+const MIN_BUCKETS: usize = 4;
+
+/// Comfortably below the ~98% at which two choices of four-slot buckets start
+/// to fail
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.9;
+
+/// How many evictions an insert tries before giving up on the walk and
+/// stashing the pair it is left holding
+const MAX_KICKS: usize = 256;
+
+/// How many pairs the stash holds before the table is rebuilt with new hash
+/// functions
+const MAX_STASH: usize = 4;
+
+/// How many sets of hash functions a rebuild tries at each table size
+const REHASH_ATTEMPTS: usize = 4;
+
+/// How many times over a rebuild may double the table past what the load
+/// factor needs, when no set of hash functions brings the stash back under
+/// [`MAX_STASH`], before settling for an overfull stash
+const REHASH_GROWTHS: usize = 2;
+
+/// The most pairs the stash may hold after a rebuild has failed to shrink it.
+/// Past this an insert panics instead of searching an ever longer stash.
+pub const MAX_OVERFULL_STASH: usize = 1 << 10;
+
+/// The seeds of a new map's hash functions
+fn initial_seeds() -> [u64; HASHES] {
+    let mut seeds = [0; HASHES];
+    reseed(&mut seeds);
+    seeds
+}
+
+/// Steps every seed to a new one, each differently, so that no two of them
+/// can ever become equal
+fn reseed(seeds: &mut [u64; HASHES]) {
+    for (i, seed) in seeds.iter_mut().enumerate() {
+        *seed = fmix64(seed.wrapping_add(0x9e37_79b9_7f4a_7c15_u64.wrapping_mul(i as u64 + 1)));
+    }
+}
+
+/// A hash map with the same API as [`super::HashMap`], whose lookups search at
+/// most [`HASHES`] buckets of [`SLOTS`] pairs. See the [module docs](self).
+#[derive(Clone)]
+pub struct CuckooMap<K, V, S = RandomState> {
+    /// `buckets * SLOTS` slots, a bucket at a time, followed by the stash.
+    /// A pair removed from the stash leaves a hole that the next stashed pair
+    /// fills.
+    slots: Vec<Option<Slot<K, V>>>,
+    /// Always zero or a power of two, at least [`MIN_BUCKETS`]
+    buckets: usize,
+    len: usize,
+    seeds: [u64; HASHES],
+    /// The stash size above which the next insert rebuilds the table. Only
+    /// more than [`MAX_STASH`] if a rebuild failed to bring it under that, and
+    /// never more than [`MAX_OVERFULL_STASH`].
+    stash_limit: usize,
+    max_load_factor: f64,
+    hash_builder: S,
+}
+
+impl<K, V> CuckooMap<K, V, RandomState> {
+    /// Constructs a new, empty `CuckooMap<K,V>`. No buckets are allocated until
+    /// the first insert.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Constructs a new, empty `CuckooMap<K,V>` that can hold at least
+    /// `capacity` pairs before it needs to grow.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::CuckooMap;
+    ///
+    /// let m = CuckooMap::<u32, u32>::with_capacity(100);
+    /// assert!(m.capacity() >= 100);
+    /// assert_eq!(m.len(), 0);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> CuckooMap<K, V, S> {
+    /// Constructs a new, empty map which hashes its keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        CuckooMap {
+            slots: Vec::new(),
+            buckets: 0,
+            len: 0,
+            seeds: initial_seeds(),
+            stash_limit: MAX_STASH,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            hash_builder,
+        }
+    }
+
+    /// Combines [`CuckooMap::with_capacity`] and [`CuckooMap::with_hasher`].
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes every pair, keeping the table for reuse.
+    pub fn clear(&mut self) {
+        self.slots.truncate(self.table_len());
+        for slot in &mut self.slots {
+            *slot = None;
+        }
+        self.len = 0;
+        self.stash_limit = MAX_STASH;
+    }
+
+    /// The number of pairs the map can hold before it has to grow
+    pub fn capacity(&self) -> usize {
+        (self.table_len() as f64 * self.max_load_factor) as usize
+    }
+
+    /// The fraction of slots in use above which the map grows
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor
+    }
+
+    /// Changes the maximum load factor, growing straight away if the map is now
+    /// over it. Lowering the load factor never shrinks the table.
+    ///
+    /// The closer to one, the longer inserts spend evicting pairs, and the more
+    /// often they end up in the stash. Lookups are no slower.
+    ///
+    /// ## Panics
+    /// If `max_load_factor` is not greater than zero and at most one.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor <= 1.0,
+            "the maximum load factor must be greater than zero and at most one"
+        );
+        self.max_load_factor = max_load_factor;
+        self.reserve(0);
+    }
+
+    /// Makes room for at least `additional` more pairs without growing.
+    ///
+    /// ## Panics
+    /// If the new size overflows `usize`, or if more than
+    /// [`MAX_OVERFULL_STASH`] pairs are stuck in the stash; see the
+    /// [module docs](self).
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            self.resize(self.buckets_for(needed).max(self.buckets * 2));
+        } else if self.stash_len() > self.stash_limit {
+            // the pairs fit, the hash functions just can't place them: try
+            // some others without growing
+            self.rehash();
+        }
+    }
+
+    /// Shrinks the table to the smallest power-of-two number of buckets that
+    /// can hold the current pairs. An empty map releases its whole table.
+    pub fn shrink_to_fit(&mut self) {
+        let buckets = if self.len == 0 {
+            0
+        } else {
+            self.buckets_for(self.len)
+        };
+        if buckets < self.buckets {
+            self.resize(buckets);
+        }
+    }
+
+    /// The smallest power-of-two number of buckets that keeps `len` pairs
+    /// under the maximum load factor
+    fn buckets_for(&self, len: usize) -> usize {
+        let slots = (len as f64 / self.max_load_factor).ceil() as usize;
+        slots.div_ceil(SLOTS).max(MIN_BUCKETS).next_power_of_two()
+    }
+
+    /// The number of slots before the stash
+    fn table_len(&self) -> usize {
+        self.buckets * SLOTS
+    }
+
+    fn stash_len(&self) -> usize {
+        self.slots[self.table_len()..].iter().flatten().count()
+    }
+
+    /// Moves every pair into a fresh table of `buckets` buckets
+    fn resize(&mut self, buckets: usize) {
+        let mut table = Vec::with_capacity(buckets * SLOTS);
+        table.resize_with(buckets * SLOTS, || None);
+        let old = mem::replace(&mut self.slots, table);
+        self.buckets = buckets;
+        for slot in old.into_iter().flatten() {
+            self.place(slot);
+        }
+    }
+
+    /// Rebuilds the table with new hash functions until the stash is back
+    /// under [`MAX_STASH`], doubling the table if no functions manage it at the
+    /// current size, up to [`REHASH_GROWTHS`] doublings past what the load
+    /// factor needs. If that keeps failing, the keys' hashes are most likely
+    /// too alike for any choice of functions to help, so the stash is left to
+    /// grow, and only tried again once it has doubled, up to
+    /// [`MAX_OVERFULL_STASH`].
+    ///
+    /// ## Panics
+    /// If more than [`MAX_OVERFULL_STASH`] pairs are still stashed. The map is
+    /// left intact.
+    fn rehash(&mut self) {
+        let most_buckets = self.buckets_for(self.len) << REHASH_GROWTHS;
+        loop {
+            for _ in 0..REHASH_ATTEMPTS {
+                reseed(&mut self.seeds);
+                self.resize(self.buckets);
+                if self.stash_len() <= MAX_STASH {
+                    self.stash_limit = MAX_STASH;
+                    return;
+                }
+            }
+            if self.buckets >= most_buckets {
+                break;
+            }
+            self.resize(self.buckets * 2);
+            if self.stash_len() <= MAX_STASH {
+                self.stash_limit = MAX_STASH;
+                return;
+            }
+        }
+        let stashed = self.stash_len();
+        assert!(
+            stashed <= MAX_OVERFULL_STASH,
+            "{} keys can't be told apart by the map's hasher",
+            stashed
+        );
+        self.stash_limit = (stashed * 2).min(MAX_OVERFULL_STASH);
+    }
+
+    /// The bucket that hash function `i` picks for `hash`
+    fn bucket(&self, hash: u64, i: usize) -> usize {
+        fmix64(hash ^ self.seeds[i]) as usize & (self.buckets - 1)
+    }
+
+    /// Every slot that a pair with this hash may live in, outside the stash
+    fn candidates(&self, hash: u64) -> impl Iterator<Item = usize> + '_ {
+        (0..HASHES).flat_map(move |i| {
+            let start = self.bucket(hash, i) * SLOTS;
+            start..start + SLOTS
+        })
+    }
+
+    /// Inserts a pair whose key is known not to be in the map, evicting other
+    /// pairs to make room if need be. Returns the slot it ends up in. The
+    /// table must not be empty.
+    fn place(&mut self, slot: Slot<K, V>) -> usize {
+        let mut carried = slot;
+        // where the new pair is, or `None` while it is the one being carried
+        let mut placed = None;
+        // the bucket the carried pair was just evicted from
+        let mut from = None;
+        for kick in 0..MAX_KICKS {
+            let free = self
+                .candidates(carried.hash)
+                .find(|&i| self.slots[i].is_none());
+            if let Some(i) = free {
+                self.slots[i] = Some(carried);
+                return placed.unwrap_or(i);
+            }
+            // Evict a pseudo-randomly chosen pair from a bucket other than the
+            // one the carried pair just left, or it would only move back.
+            // Choosing the same way every time could loop forever.
+            let r = fmix64(carried.hash ^ kick as u64) as usize;
+            let mut buckets = [0; HASHES];
+            let mut n = 0;
+            for h in 0..HASHES {
+                let b = self.bucket(carried.hash, h);
+                if Some(b) != from {
+                    buckets[n] = b;
+                    n += 1;
+                }
+            }
+            let bucket = if n == 0 {
+                // every hash function picked the bucket it came from
+                from.expect("a new pair has somewhere to go")
+            } else {
+                buckets[r % n]
+            };
+            let i = bucket * SLOTS + (r / HASHES) % SLOTS;
+            let victim = self.slots[i].replace(carried).expect("buckets are full");
+            placed = match placed {
+                None => Some(i),
+                // the new pair itself was evicted
+                Some(p) if p == i => None,
+                p => p,
+            };
+            carried = victim;
+            from = Some(bucket);
+        }
+        let i = self.stash(carried);
+        placed.unwrap_or(i)
+    }
+
+    /// Puts a pair in the first hole in the stash, or on the end of it.
+    /// Returns its slot.
+    fn stash(&mut self, slot: Slot<K, V>) -> usize {
+        let table_len = self.table_len();
+        match self.slots[table_len..].iter().position(Option::is_none) {
+            Some(hole) => {
+                self.slots[table_len + hole] = Some(slot);
+                table_len + hole
+            }
+            None => {
+                self.slots.push(Some(slot));
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// The pair in slot `i`, which must be occupied
+    fn slot(&self, i: usize) -> &Slot<K, V> {
+        self.slots[i].as_ref().expect("slot is occupied")
+    }
+
+    fn slot_mut(&mut self, i: usize) -> &mut Slot<K, V> {
+        self.slots[i].as_mut().expect("slot is occupied")
+    }
+
+    /// Removes the pair in slot `i`, which must be occupied. A slot freed in the
+    /// table is refilled from the stash if any stashed pair may live there.
+    fn remove_index(&mut self, i: usize) -> Slot<K, V> {
+        self.len -= 1;
+        let slot = self.slots[i].take().expect("slot is occupied");
+        let table_len = self.table_len();
+        if i < table_len {
+            let bucket = i / SLOTS;
+            let movable = (table_len..self.slots.len()).find(|&j| match &self.slots[j] {
+                Some(s) => (0..HASHES).any(|h| self.bucket(s.hash, h) == bucket),
+                None => false,
+            });
+            if let Some(j) = movable {
+                self.slots[i] = self.slots[j].take();
+            }
+        }
+        slot
+    }
+}
+
+impl<K, V, S> CuckooMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// See [`super::HashMap`] on hashing borrowed forms of a key.
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hash_builder.hash_one(key)
+    }
+
+    /// The slot holding `key`, if it is in the map: one of its candidates, or
+    /// somewhere in the stash
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.buckets == 0 {
+            return None;
+        }
+        let matches = |&i: &usize| match &self.slots[i] {
+            Some(slot) => slot.hash == hash && slot.key.borrow() == key,
+            None => false,
+        };
+        self.candidates(hash)
+            .find(matches)
+            .or_else(|| (self.table_len()..self.slots.len()).find(matches))
+    }
+
+    /// Inserts `V` at key `K`. If the key was already present, its old value is
+    /// replaced and returned.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::CuckooMap;
+    ///
+    /// let mut m = CuckooMap::<&str, i32>::new();
+    /// assert_eq!(m.insert("foo", 1), None);
+    /// assert_eq!(m.insert("foo", 2), Some(1));
+    /// assert_eq!(m.get("foo"), Some(&2));
+    /// ```
+    ///
+    /// ## Panics
+    /// If more than [`MAX_OVERFULL_STASH`] pairs are stuck in the stash, which
+    /// only a hasher that can't tell the keys apart leads to; see the
+    /// [module docs](self).
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+        if let Some(i) = self.find(hash, &key) {
+            return Some(mem::replace(&mut self.slot_mut(i).value, value));
+        }
+        self.reserve(1);
+        self.place(Slot { hash, key, value });
+        self.len += 1;
+        None
+    }
+
+    /// Gets an optional reference to `V` using key `K`, or any borrowed form of
+    /// it.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(&self.slot(i).value)
+    }
+
+    /// Gets an optional mutable reference to `V` using key `K`, or any borrowed
+    /// form of it.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(&mut self.slot_mut(i).value)
+    }
+
+    /// Returns `true` if the map holds a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(self.hash(key), key).is_some()
+    }
+
+    /// Removes the pair for `key`, or any borrowed form of it, returning the
+    /// value if the key was in the map.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::CuckooMap;
+    ///
+    /// let mut m = CuckooMap::<&str, i32>::new();
+    /// m.insert("baz", 1);
+    /// assert_eq!(m.remove("baz"), Some(1));
+    /// assert_eq!(m.remove("baz"), None);
+    /// ```
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(self.remove_index(i).value)
+    }
+
+    /// Gets the [`Entry`] for `key`, for in-place insertion or modification.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::CuckooMap;
+    ///
+    /// let mut counts = CuckooMap::new();
+    /// for word in "a b a c a b".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(counts["a"], 3);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into a single pair of a [`CuckooMap`], which may or may not be
+/// present. Created by [`CuckooMap::entry`].
+pub enum Entry<'a, K, V, S = RandomState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// An entry for a key that is in the map
+pub struct OccupiedEntry<'a, K, V, S = RandomState> {
+    map: &'a mut CuckooMap<K, V, S>,
+    /// The slot holding the pair
+    index: usize,
+}
+
+/// An entry for a key that is not in the map
+pub struct VacantEntry<'a, K, V, S = RandomState> {
+    map: &'a mut CuckooMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S> {
+    /// Inserts `default` if the entry is vacant, and returns a mutable
+    /// reference to the value either way.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], but only computes the value when it is needed.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], using `V::default()`.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied, leaving a vacant entry
+    /// untouched.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// The key this entry was created for
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    /// The key already in the map
+    pub fn key(&self) -> &K {
+        &self.map.slot(self.index).key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.slot(self.index).value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.slot_mut(self.index).value
+    }
+
+    /// Converts the entry into a mutable reference that lives as long as the
+    /// borrow of the map
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slot_mut(self.index).value
+    }
+
+    /// Replaces the value, returning the old one. The key is left untouched.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the pair from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the pair from the map, returning both the key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        let slot = self.map.remove_index(self.index);
+        (slot.key, slot.value)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
+    /// The key that would be inserted
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back ownership of the key
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the pair, returning a mutable reference to the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let map = self.map;
+        // only grow (or rebuild an overfull stash) once the pair is known to
+        // be new
+        map.reserve(1);
+        let i = map.place(Slot {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        map.len += 1;
+        &mut map.slot_mut(i).value
+    }
+}
+
+impl<K, V, S> Default for CuckooMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, in iteration order
+impl<K, V, S> Debug for CuckooMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal if they hold the same pairs, regardless of their
+/// capacities or the order in which the pairs were inserted
+impl<K, V, S> PartialEq for CuckooMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for CuckooMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+/// ## Panics
+/// If the key is not in the map. Use [`CuckooMap::get`] when that is possible.
+impl<K, Q, V, S> Index<&Q> for CuckooMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in CuckooMap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashmap::conformance::{LowByte, Zero};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{
+        hash::BuildHasherDefault,
+        panic::{self, AssertUnwindSafe},
+    };
+
+    map_conformance!(CuckooMap);
+
+    /// Every pair in the table is in one of its own buckets, and the counts
+    /// add up
+    fn assert_invariants<K, V, S>(m: &CuckooMap<K, V, S>) {
+        assert!(m.buckets.count_ones() <= 1);
+        let table_len = m.table_len();
+        assert!(m.slots.len() >= table_len);
+        for (i, slot) in m.slots[..table_len].iter().enumerate() {
+            if let Some(slot) = slot {
+                let bucket = i / SLOTS;
+                assert!((0..HASHES).any(|h| m.bucket(slot.hash, h) == bucket));
+            }
+        }
+        assert_eq!(m.slots.iter().flatten().count(), m.len());
+    }
+
+    #[test]
+    fn invariants_hold_through_inserts_and_removes() {
+        let mut m = CuckooMap::new();
+        for i in 0..5000u32 {
+            m.insert(i, i);
+            if i % 3 == 0 {
+                m.remove(&(i / 2));
+            }
+        }
+        assert_invariants(&m);
+        m.retain(|k, _| k % 5 != 0);
+        assert_invariants(&m);
+        let buckets = m.buckets;
+        let _ = m.drain().take(3).count();
+        assert!(m.is_empty());
+        assert_eq!(m.buckets, buckets);
+        assert_eq!(m.slots.len(), buckets * SLOTS);
+        assert_invariants(&m);
+        m.extend((0..100).map(|i| (i, i)));
+        std::mem::forget(m.drain());
+        assert_eq!((m.buckets, m.slots.len()), (0, 0));
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn the_stash_stays_small_up_to_the_load_factor() {
+        let mut rng = StdRng::seed_from_u64(0xc0c0);
+        for &load in &[0.5, 0.9, 0.95] {
+            let mut m = CuckooMap::with_capacity(1 << 14);
+            m.set_max_load_factor(load);
+            let n = m.capacity();
+            let buckets = m.buckets;
+            for _ in 0..n {
+                m.insert(rng.gen::<u64>(), ());
+            }
+            assert_eq!(m.buckets, buckets, "filling up to capacity should not grow");
+            assert!(m.stash_len() <= MAX_STASH + 1);
+            assert_eq!(m.stash_limit, MAX_STASH);
+            assert_invariants(&m);
+        }
+    }
+
+    #[test]
+    fn a_completely_full_table_still_works() {
+        let mut m = CuckooMap::with_capacity(1000);
+        m.set_max_load_factor(1.0);
+        let n = m.capacity();
+        for i in 0..n {
+            m.insert(i, i);
+        }
+        // the table may have had to grow to empty the stash
+        assert!(m.stash_len() <= MAX_STASH + 1);
+        for i in 0..n {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn bad_hash_functions_are_replaced() {
+        // with equal seeds, every key has a single bucket, and the stash fills
+        // up straight away
+        let mut m = CuckooMap::with_capacity(1000);
+        let seed = m.seeds[0];
+        m.seeds = [seed; HASHES];
+        for i in 0..1000u32 {
+            m.insert(i, i);
+        }
+        assert_ne!(m.seeds[0], m.seeds[1]);
+        assert!(m.stash_len() <= MAX_STASH + 1);
+        for i in 0..1000 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn removing_from_the_table_refills_it_from_the_stash() {
+        // every key wants the same one or two buckets
+        let mut m = CuckooMap::with_hasher(BuildHasherDefault::<Zero>::default());
+        for i in 0..10u32 {
+            m.insert(i, ());
+        }
+        let table = m.len() - m.stash_len();
+        assert!(m.stash_len() > 0);
+        let in_table = (0..10)
+            .find(|i| m.find(0, i).unwrap() < m.table_len())
+            .unwrap();
+        let stashed = m.stash_len();
+        m.remove(&in_table);
+        assert_eq!(m.stash_len(), stashed - 1);
+        assert_eq!(m.len() - m.stash_len(), table);
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn keys_with_few_hashes_still_fit_in_the_table() {
+        let mut m = CuckooMap::with_hasher(BuildHasherDefault::<LowByte>::default());
+        for i in 0..1024u64 {
+            m.insert(i, i);
+        }
+        // 256 hashes, with two buckets' worth of room for each one's four keys
+        assert!(m.stash_len() <= MAX_STASH + 1);
+        assert_eq!(m.stash_limit, MAX_STASH);
+        for i in 0..1024 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn indistinguishable_keys_grow_the_stash_but_not_the_table() {
+        let mut m = CuckooMap::with_hasher(BuildHasherDefault::<Zero>::default());
+        for i in 0..1000u64 {
+            m.insert(i, i);
+        }
+        // every key has the same two buckets
+        assert!(m.len() - m.stash_len() <= HASHES * SLOTS);
+        assert!(m.stash_limit > MAX_STASH && m.stash_limit <= MAX_OVERFULL_STASH);
+        assert!(m.buckets <= m.buckets_for(1000) << REHASH_GROWTHS);
+        for i in 0..1000 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn too_many_indistinguishable_keys_panic_and_leave_the_map_intact() {
+        let mut m = CuckooMap::with_hasher(BuildHasherDefault::<Zero>::default());
+        let n = (MAX_OVERFULL_STASH + 2 * HASHES * SLOTS) as u64;
+        let inserted = panic::catch_unwind(AssertUnwindSafe(|| {
+            for i in 0..n {
+                m.insert(i, i);
+            }
+        }));
+        assert!(inserted.is_err());
+        assert!(m.len() > MAX_OVERFULL_STASH && m.len() < n as usize);
+        for i in 0..m.len() as u64 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn clear_empties_the_stash() {
+        let mut m = CuckooMap::with_hasher(BuildHasherDefault::<Zero>::default());
+        for i in 0..20u32 {
+            m.insert(i, ());
+        }
+        let capacity = m.capacity();
+        m.clear();
+        assert_eq!(m.slots.len(), m.table_len());
+        assert_eq!(m.capacity(), capacity);
+        m.insert(1, ());
+        assert_invariants(&m);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_load_factor_over_one() {
+        CuckooMap::<u8, u8>::new().set_max_load_factor(1.5);
+    }
+}
@@ -0,0 +1,799 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A persistent hash map: a [hash array mapped trie][1] (HAMT), as used by
+//! Clojure and Scala for their immutable maps.
+//!
+//! The trie branches 32 ways on 5 bits of the hash at a time. Rather than
+//! allocating 32 children per node, each *branch* keeps a 32-bit bitmap of
+//! which children it has, and a dense `Vec` of just those; a child's position
+//! in the `Vec` is the number of set bits below its own. Keys whose whole
+//! hashes are equal end up together in a *collision* node.
+//!
+//! Nodes are never changed once built. [`Hamt::insert`] and [`Hamt::remove`]
+//! copy only the nodes on the path from the root to the key, at most 13 of
+//! them, and return a new map that shares every other node with the old one.
+//! The old map is left intact, so keeping a version around is as cheap as
+//! cloning it, which only bumps a reference count:
+//!
+//! ```
+//! use tinystd::hashmap::Hamt;
+//!
+//! let v1: Hamt<_, _> = (0..100).map(|i| (i, i)).collect();
+//! let v2 = v1.insert(100, 100).remove(&0);
+//! assert_eq!(v1.get(&0), Some(&0));
+//! assert_eq!(v1.get(&100), None);
+//! assert_eq!(v2.get(&0), None);
+//! assert_eq!(v2.get(&100), Some(&100));
+//! ```
+//!
+//! The nodes are shared through a [`SharedPointer`]: by default the crate's
+//! [`Rc`], and for maps that are shared across threads the crate's [`Arc`]
+//! (see [`ArcHamt`]).
+//!
+//! [1]: https://infoscience.epfl.ch/record/64398/files/idealhashtrees.pdf
+use crate::{arc::Arc, rc::Rc};
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    iter::{FromIterator, FusedIterator},
+    ops::Deref,
+    ptr, slice,
+};
+
+/// How many bits of the hash each level of the trie consumes
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+/// A family of reference counted pointers that nodes can be shared through
+pub trait SharedPointer {
+    type Pointer<T>: Clone + Deref<Target = T>;
+
+    fn new<T>(value: T) -> Self::Pointer<T>;
+}
+
+/// Shares nodes through the crate's [`Rc`]
+#[derive(Debug)]
+pub enum RcPointer {}
+
+impl SharedPointer for RcPointer {
+    type Pointer<T> = Rc<T>;
+
+    fn new<T>(value: T) -> Rc<T> {
+        Rc::from(value)
+    }
+}
+
+/// Shares nodes through the crate's [`Arc`]
+#[derive(Debug)]
+pub enum ArcPointer {}
+
+impl SharedPointer for ArcPointer {
+    type Pointer<T> = Arc<T>;
+
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::from(value)
+    }
+}
+
+enum Node<K, V, P: SharedPointer> {
+    Branch {
+        /// Bit `i` is set if there is a child for hash fragment `i`
+        bitmap: u32,
+        /// One per set bit, in order
+        children: Vec<Child<K, V, P>>,
+    },
+    /// Pairs whose keys all have the same hash
+    Collision { hash: u64, pairs: Vec<(K, V)> },
+}
+
+enum Child<K, V, P: SharedPointer> {
+    Leaf { hash: u64, key: K, value: V },
+    Node(P::Pointer<Node<K, V, P>>),
+}
+
+/// Implemented by hand, as deriving it would require `P: Clone`
+impl<K: Clone, V: Clone, P: SharedPointer> Clone for Node<K, V, P> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Branch { bitmap, children } => Node::Branch {
+                bitmap: *bitmap,
+                children: children.clone(),
+            },
+            Node::Collision { hash, pairs } => Node::Collision {
+                hash: *hash,
+                pairs: pairs.clone(),
+            },
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, P: SharedPointer> Clone for Child<K, V, P> {
+    fn clone(&self) -> Self {
+        match self {
+            Child::Leaf { hash, key, value } => Child::Leaf {
+                hash: *hash,
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Child::Node(node) => Child::Node(node.clone()),
+        }
+    }
+}
+
+/// The 5-bit fragment of `hash` that picks a child at depth `shift`
+fn fragment(hash: u64, shift: u32) -> u32 {
+    ((hash >> shift) & MASK) as u32
+}
+
+impl<K, V, P: SharedPointer> Node<K, V, P> {
+    fn empty() -> Self {
+        Node::Branch {
+            bitmap: 0,
+            children: Vec::new(),
+        }
+    }
+
+    /// A branch at depth `shift` holding just `a` and `b`, whose hashes differ
+    fn join(shift: u32, a: Child<K, V, P>, a_hash: u64, b: Child<K, V, P>, b_hash: u64) -> Self {
+        let (i, j) = (fragment(a_hash, shift), fragment(b_hash, shift));
+        if i == j {
+            let node = Self::join(shift + BITS, a, a_hash, b, b_hash);
+            return Node::Branch {
+                bitmap: 1 << i,
+                children: vec![Child::Node(P::new(node))],
+            };
+        }
+        Node::Branch {
+            bitmap: (1 << i) | (1 << j),
+            children: if i < j { vec![a, b] } else { vec![b, a] },
+        }
+    }
+}
+
+impl<K, V, P: SharedPointer> Child<K, V, P> {
+    /// Leaves and collisions don't depend on their depth, so a branch left
+    /// with only one of them can be replaced by it
+    fn can_move_up(&self) -> bool {
+        match self {
+            Child::Leaf { .. } => true,
+            Child::Node(node) => matches!(**node, Node::Collision { .. }),
+        }
+    }
+}
+
+impl<K, V, P> Node<K, V, P>
+where
+    K: Eq + Clone,
+    V: Clone,
+    P: SharedPointer,
+{
+    fn get<Q>(&self, hash: u64, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let mut node = self;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Branch { bitmap, children } => {
+                    let bit = 1 << fragment(hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    match &children[(bitmap & (bit - 1)).count_ones() as usize] {
+                        Child::Leaf {
+                            hash: h,
+                            key: k,
+                            value,
+                        } => {
+                            return if *h == hash && k.borrow() == key {
+                                Some(value)
+                            } else {
+                                None
+                            };
+                        }
+                        Child::Node(child) => {
+                            node = child;
+                            shift += BITS;
+                        }
+                    }
+                }
+                Node::Collision { hash: h, pairs } => {
+                    if *h != hash {
+                        return None;
+                    }
+                    return pairs
+                        .iter()
+                        .find(|(k, _)| k.borrow() == key)
+                        .map(|(_, v)| v);
+                }
+            }
+        }
+    }
+
+    /// A copy of this node, at depth `shift`, with the pair added or replaced,
+    /// and whether it was added
+    fn insert(&self, shift: u32, hash: u64, key: K, value: V) -> (Self, bool) {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << fragment(hash, shift);
+                let pos = (bitmap & (bit - 1)).count_ones() as usize;
+                let mut children = children.clone();
+                if bitmap & bit == 0 {
+                    children.insert(pos, Child::Leaf { hash, key, value });
+                    let node = Node::Branch {
+                        bitmap: bitmap | bit,
+                        children,
+                    };
+                    return (node, true);
+                }
+                let (child, added) = match children.remove(pos) {
+                    Child::Leaf {
+                        hash: h,
+                        key: k,
+                        value: v,
+                    } => {
+                        if h == hash && k == key {
+                            (Child::Leaf { hash, key, value }, false)
+                        } else if h == hash {
+                            let pairs = vec![(k, v), (key, value)];
+                            (Child::Node(P::new(Node::Collision { hash, pairs })), true)
+                        } else {
+                            let old = Child::Leaf {
+                                hash: h,
+                                key: k,
+                                value: v,
+                            };
+                            let new = Child::Leaf { hash, key, value };
+                            let node = Self::join(shift + BITS, old, h, new, hash);
+                            (Child::Node(P::new(node)), true)
+                        }
+                    }
+                    Child::Node(node) => {
+                        let (node, added) = node.insert(shift + BITS, hash, key, value);
+                        (Child::Node(P::new(node)), added)
+                    }
+                };
+                children.insert(pos, child);
+                let node = Node::Branch {
+                    bitmap: *bitmap,
+                    children,
+                };
+                (node, added)
+            }
+            Node::Collision { hash: h, pairs } if *h == hash => {
+                let mut pairs = pairs.clone();
+                let added = match pairs.iter_mut().find(|(k, _)| *k == key) {
+                    Some(pair) => {
+                        pair.1 = value;
+                        false
+                    }
+                    None => {
+                        pairs.push((key, value));
+                        true
+                    }
+                };
+                (Node::Collision { hash, pairs }, added)
+            }
+            Node::Collision { hash: h, .. } => {
+                // the new key only shares a prefix of its hash with the
+                // colliding keys, so they part ways at some depth below here
+                let collision = Child::Node(P::new(self.clone()));
+                let leaf = Child::Leaf { hash, key, value };
+                (Self::join(shift, collision, *h, leaf, hash), true)
+            }
+        }
+    }
+
+    /// What becomes of this node, at depth `shift`, without `key`: `None` if
+    /// the key isn't in it, otherwise the child to replace it with, if any
+    fn remove<Q>(&self, shift: u32, hash: u64, key: &Q) -> Option<Option<Child<K, V, P>>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << fragment(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                let pos = (bitmap & (bit - 1)).count_ones() as usize;
+                let replacement = match &children[pos] {
+                    Child::Leaf {
+                        hash: h, key: k, ..
+                    } if *h == hash && k.borrow() == key => None,
+                    Child::Leaf { .. } => return None,
+                    Child::Node(node) => node.remove(shift + BITS, hash, key)?,
+                };
+                let mut children = children.clone();
+                let mut bitmap = *bitmap;
+                match replacement {
+                    Some(child) => children[pos] = child,
+                    None => {
+                        children.remove(pos);
+                        bitmap &= !bit;
+                    }
+                }
+                // below the root, a branch is only worth keeping if it still
+                // tells apart at least two things
+                if shift > 0 && children.len() <= 1 {
+                    match children.pop() {
+                        None => return Some(None),
+                        Some(child) if child.can_move_up() => return Some(Some(child)),
+                        Some(child) => children.push(child),
+                    }
+                }
+                Some(Some(Child::Node(P::new(Node::Branch { bitmap, children }))))
+            }
+            Node::Collision { hash: h, pairs } => {
+                if *h != hash {
+                    return None;
+                }
+                let pos = pairs.iter().position(|(k, _)| k.borrow() == key)?;
+                let mut pairs = pairs.clone();
+                pairs.remove(pos);
+                if pairs.len() == 1 {
+                    let (key, value) = pairs.pop().expect("one pair is left");
+                    return Some(Some(Child::Leaf { hash, key, value }));
+                }
+                Some(Some(Child::Node(P::new(Node::Collision { hash, pairs }))))
+            }
+        }
+    }
+}
+
+/// A persistent hash map. See the [module docs](self).
+pub struct Hamt<K, V, P: SharedPointer = RcPointer, S = RandomState> {
+    /// Always a branch
+    root: P::Pointer<Node<K, V, P>>,
+    len: usize,
+    hash_builder: S,
+}
+
+/// A [`Hamt`] whose nodes are shared through the crate's [`Arc`], so that
+/// versions of it can be sent to and shared between threads. Construct one with
+/// `ArcHamt::default()`.
+pub type ArcHamt<K, V, S = RandomState> = Hamt<K, V, ArcPointer, S>;
+
+impl<K, V> Hamt<K, V, RcPointer, RandomState> {
+    /// Constructs a new, empty `Hamt<K,V>`.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, P: SharedPointer, S> Hamt<K, V, P, S> {
+    /// Constructs a new, empty map which hashes its keys with `hash_builder`.
+    /// Every version derived from it shares a clone of the hasher.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Hamt {
+            root: P::new(Node::empty()),
+            len: 0,
+            hash_builder,
+        }
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the two maps share their whole trie, i.e. one is a
+    /// clone of the other, or an update that changed nothing.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(&*self.root, &*other.root)
+    }
+
+    /// An iterator over `(&K, &V)` pairs, in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V, P> {
+        let children = match &*self.root {
+            Node::Branch { children, .. } => children.iter(),
+            Node::Collision { .. } => unreachable!("the root is always a branch"),
+        };
+        Iter {
+            stack: vec![children],
+            pairs: [].iter(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over the keys, in arbitrary order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// An iterator over the values, in arbitrary order.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, P, S> Hamt<K, V, P, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    P: SharedPointer,
+    S: BuildHasher + Clone,
+{
+    /// Gets an optional reference to `V` using key `K`, or any borrowed form of
+    /// it.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.root.get(self.hash_builder.hash_one(key), key)
+    }
+
+    /// Returns `true` if the map holds a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// A new map with `V` at key `K`, replacing any value already there. This
+    /// map is unchanged.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let hash = self.hash_builder.hash_one(&key);
+        let (root, added) = self.root.insert(0, hash, key, value);
+        Hamt {
+            root: P::new(root),
+            len: self.len + added as usize,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+
+    /// A new map without `key`. This map is unchanged. If the key isn't in it,
+    /// the new map shares the whole trie with it.
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let root = match self.root.remove(0, hash, key) {
+            None => return self.clone(),
+            Some(Some(Child::Node(root))) => root,
+            Some(_) => unreachable!("the root is never replaced by a leaf"),
+        };
+        Hamt {
+            root,
+            len: self.len - 1,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+/// Cloning a map is *O(1)*: the clone shares the whole trie.
+impl<K, V, P: SharedPointer, S: Clone> Clone for Hamt<K, V, P, S> {
+    fn clone(&self) -> Self {
+        Hamt {
+            root: self.root.clone(),
+            len: self.len,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K, V, P: SharedPointer, S: Default> Default for Hamt<K, V, P, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`
+impl<K, V, P, S> Debug for Hamt<K, V, P, S>
+where
+    K: Debug,
+    V: Debug,
+    P: SharedPointer,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal if they hold the same pairs. Maps that share their trie
+/// are compared without looking at it.
+impl<K, V, P, S> PartialEq for Hamt<K, V, P, S>
+where
+    K: Eq + Hash + Clone,
+    V: PartialEq + Clone,
+    P: SharedPointer,
+    S: BuildHasher + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        self.ptr_eq(other) || self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, P, S> Eq for Hamt<K, V, P, S>
+where
+    K: Eq + Hash + Clone,
+    V: Eq + Clone,
+    P: SharedPointer,
+    S: BuildHasher + Clone,
+{
+}
+
+/// Created by [`Hamt::iter`]. Walks the trie depth first.
+pub struct Iter<'a, K, V, P: SharedPointer> {
+    /// The children left to visit in each branch on the way down
+    stack: Vec<slice::Iter<'a, Child<K, V, P>>>,
+    /// The pairs left to visit in the current collision node
+    pairs: slice::Iter<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<'a, K, V, P: SharedPointer> Iterator for Iter<'a, K, V, P> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.pairs.next() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+            match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(Child::Leaf { key, value, .. }) => {
+                    self.remaining -= 1;
+                    return Some((key, value));
+                }
+                Some(Child::Node(node)) => match &**node {
+                    Node::Branch { children, .. } => self.stack.push(children.iter()),
+                    Node::Collision { pairs, .. } => self.pairs = pairs.iter(),
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, P: SharedPointer> ExactSizeIterator for Iter<'_, K, V, P> {}
+impl<K, V, P: SharedPointer> FusedIterator for Iter<'_, K, V, P> {}
+
+impl<'a, K, V, P: SharedPointer, S> IntoIterator for &'a Hamt<K, V, P, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, P, S> FromIterator<(K, V)> for Hamt<K, V, P, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    P: SharedPointer,
+    S: BuildHasher + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Hamt::default(), |map, (k, v)| map.insert(k, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashmap::conformance::LowByte;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{collections::HashMap as StdMap, hash::BuildHasherDefault, thread};
+
+    type Colliding = Hamt<u64, u64, RcPointer, BuildHasherDefault<LowByte>>;
+
+    /// Every branch's bitmap matches its children, and no branch below the
+    /// root could have been replaced by its only child
+    fn assert_canonical<K, V, P: SharedPointer>(node: &Node<K, V, P>, shift: u32) -> usize {
+        match node {
+            Node::Branch { bitmap, children } => {
+                assert_eq!(bitmap.count_ones() as usize, children.len());
+                if shift > 0 {
+                    assert!(!children.is_empty());
+                    assert!(children.len() > 1 || !children[0].can_move_up());
+                }
+                children
+                    .iter()
+                    .map(|child| match child {
+                        Child::Leaf { .. } => 1,
+                        Child::Node(node) => assert_canonical(node, shift + BITS),
+                    })
+                    .sum()
+            }
+            Node::Collision { pairs, .. } => {
+                assert!(pairs.len() > 1);
+                pairs.len()
+            }
+        }
+    }
+
+    #[test]
+    fn empty_maps() {
+        let m = Hamt::<u32, u32>::new();
+        assert!(m.is_empty());
+        assert_eq!(m.get(&1), None);
+        assert_eq!(m.iter().count(), 0);
+        assert!(m.remove(&1).ptr_eq(&m));
+        assert_eq!(format!("{:?}", m), "{}");
+    }
+
+    #[test]
+    fn old_versions_remain_intact() {
+        let mut versions = vec![Hamt::new()];
+        for i in 0..200u32 {
+            let next = versions.last().unwrap().insert(i, i * 2);
+            versions.push(next);
+        }
+        for i in 0..200u32 {
+            let next = versions.last().unwrap().remove(&i);
+            versions.push(next);
+        }
+        for (n, version) in versions.iter().enumerate() {
+            // version `n` has keys 0..n for n <= 200, then loses them in order
+            let present = if n <= 200 {
+                0..n as u32
+            } else {
+                n as u32 - 200..200
+            };
+            assert_eq!(version.len(), present.len());
+            for i in 0..200u32 {
+                let expected = if present.contains(&i) {
+                    Some(i * 2)
+                } else {
+                    None
+                };
+                assert_eq!(
+                    version.get(&i),
+                    expected.as_ref(),
+                    "key {} in version {}",
+                    i,
+                    n
+                );
+            }
+            assert_eq!(assert_canonical(&version.root, 0), version.len());
+        }
+    }
+
+    #[test]
+    fn updates_share_unchanged_nodes() {
+        let m: Hamt<u32, u32> = (0..10_000).map(|i| (i, i)).collect();
+        let updated = m.insert(10_000, 0);
+        let children = |m: &Hamt<u32, u32>| match &*m.root {
+            Node::Branch { children, .. } => children
+                .iter()
+                .map(|child| match child {
+                    Child::Node(node) => &**node as *const _,
+                    Child::Leaf { .. } => ptr::null(),
+                })
+                .collect::<Vec<_>>(),
+            Node::Collision { .. } => unreachable!(),
+        };
+        let (before, after) = (children(&m), children(&updated));
+        assert_eq!(before.len(), 32);
+        let shared = before.iter().zip(&after).filter(|(a, b)| a == b).count();
+        assert_eq!(shared, 31);
+        assert!(!m.ptr_eq(&updated));
+        assert!(m.ptr_eq(&m.clone()));
+    }
+
+    #[test]
+    fn replacing_a_value() {
+        let m = Hamt::new().insert("a", 1);
+        let n = m.insert("a", 2);
+        assert_eq!(n.len(), 1);
+        assert_eq!(m.get("a"), Some(&1));
+        assert_eq!(n.get("a"), Some(&2));
+        assert_ne!(m, n);
+        assert_eq!(n, Hamt::new().insert("a", 2));
+    }
+
+    #[test]
+    fn colliding_hashes() {
+        // 1, 257 and 513 share the whole hash 1; 1 + 32 shares its low 5 bits
+        let m = Colliding::default().insert(1, 1).insert(257, 257);
+        assert!(matches!(&*m.root, Node::Branch { children, .. }
+            if matches!(&children[0], Child::Node(n) if matches!(**n, Node::Collision { .. }))));
+        let m = m.insert(513, 513).insert(33, 33).insert(257, 0);
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.get(&257), Some(&0));
+        assert_eq!(m.get(&769), None);
+        assert_eq!(assert_canonical(&m.root, 0), 4);
+
+        let n = m.remove(&257).remove(&513);
+        assert_eq!(assert_canonical(&n.root, 0), 2);
+        assert_eq!(n.get(&1), Some(&1));
+        assert_eq!(n.get(&33), Some(&33));
+        let n = n.remove(&33).remove(&1);
+        assert!(n.is_empty());
+        assert_eq!(m.len(), 4);
+    }
+
+    #[test]
+    fn agrees_with_std_across_versions() {
+        let mut rng = StdRng::seed_from_u64(44);
+        let mut versions: Vec<(Colliding, StdMap<u64, u64>)> = vec![Default::default()];
+        for _ in 0..3000 {
+            // branch off a random earlier version
+            let (hamt, mut std) = versions[rng.gen_range(0..versions.len())].clone();
+            let key = rng.gen_range(0..600);
+            let hamt = if rng.gen_bool(0.6) {
+                std.insert(key, key);
+                hamt.insert(key, key)
+            } else {
+                std.remove(&key);
+                hamt.remove(&key)
+            };
+            assert_eq!(hamt.len(), std.len());
+            versions.push((hamt, std));
+        }
+        for (hamt, std) in &versions {
+            assert_eq!(assert_canonical(&hamt.root, 0), std.len());
+            let mut pairs: Vec<_> = hamt.iter().map(|(k, v)| (*k, *v)).collect();
+            let mut expected: Vec<_> = std.iter().map(|(k, v)| (*k, *v)).collect();
+            pairs.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(pairs, expected);
+        }
+    }
+
+    #[test]
+    fn versions_can_be_shared_between_threads() {
+        let base: ArcHamt<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let versions: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..4u32)
+                .map(|t| {
+                    let base = &base;
+                    s.spawn(move || {
+                        (0..1000)
+                            .step_by(4)
+                            .fold(base.clone(), |m, i| m.remove(&(i + t)))
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(base.len(), 1000);
+        for (t, version) in versions.iter().enumerate() {
+            assert_eq!(version.len(), 750);
+            assert!(version.keys().all(|k| k % 4 != t as u32));
+        }
+        let empty = ArcHamt::<u32, u32>::default();
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,1006 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A hash map that remembers insertion order, after the [`indexmap`][1] crate.
+//!
+//! The pairs live in a dense `Vec`, in order, so iteration is as fast as
+//! iterating a `Vec` and the same on every run. Next to it sits a hash index:
+//! an open-addressed (Robin Hood, as in [`super::open`]) table of positions in
+//! the `Vec`. A lookup hashes the key, finds its position in the index, and
+//! then reads the pair straight out of the `Vec`.
+//!
+//! Since every pair has a position, pairs can also be looked up, removed, and
+//! sorted by position:
+//!
+//! ```
+//! use tinystd::{hashmap::IndexMap, sort::Quick};
+//!
+//! let mut m = IndexMap::new();
+//! m.insert("b", 2);
+//! m.insert("c", 3);
+//! m.insert("a", 1);
+//! assert_eq!(m.keys().collect::<Vec<_>>(), vec![&"b", &"c", &"a"]);
+//! assert_eq!(m.get_index_of("c"), Some(1));
+//!
+//! m.sort_keys(&Quick);
+//! assert_eq!(m.get_index(0), Some((&"a", &1)));
+//! ```
+//!
+//! [1]: https://docs.rs/indexmap
+use super::Slot;
+use crate::hash::fmix64;
+use crate::sort::Sorter;
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::{Index, IndexMut},
+};
+
+mod iter;
+
+pub use iter::{Drain, ExtractIf, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+
+/// The smallest non-empty index
+const INITIAL_SLOTS: usize = 8;
+
+/// Where a pair is in the entries, and its hash, so that probing the index
+/// never has to look at the entries themselves
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    index: usize,
+    hash: u64,
+}
+
+/// The hash index: a Robin Hood table of [`Pos`]itions, with backward-shift
+/// deletion. It knows nothing about keys; callers pass in a closure to check
+/// whether the pair at a position is the one they are looking for.
+#[derive(Debug, Clone, Default)]
+struct Indices {
+    /// Always empty or a power of two in length
+    table: Vec<Option<Pos>>,
+}
+
+impl Indices {
+    fn with_slots(slots: usize) -> Self {
+        let mut table = Vec::with_capacity(slots);
+        table.resize_with(slots, || None);
+        Indices { table }
+    }
+
+    /// The smallest index that can hold `len` positions
+    fn slots_for(len: usize) -> usize {
+        let mut slots = INITIAL_SLOTS;
+        while Self::capacity_of(slots) < len {
+            slots *= 2;
+        }
+        slots
+    }
+
+    /// Filling at most 7/8 of the slots keeps probes short and guarantees an
+    /// empty slot to end them
+    fn capacity_of(slots: usize) -> usize {
+        slots / 8 * 7
+    }
+
+    fn capacity(&self) -> usize {
+        Self::capacity_of(self.table.len())
+    }
+
+    fn mask(&self) -> usize {
+        self.table.len() - 1
+    }
+
+    fn probe_distance(&self, hash: u64, at: usize) -> usize {
+        at.wrapping_sub(hash as usize) & self.mask()
+    }
+
+    /// The slot holding the position for which `is_match` returns `true`
+    fn find<F>(&self, hash: u64, mut is_match: F) -> Option<usize>
+    where
+        F: FnMut(usize) -> bool,
+    {
+        if self.table.is_empty() {
+            return None;
+        }
+        let mut at = hash as usize & self.mask();
+        let mut dist = 0;
+        while let Some(pos) = self.table[at] {
+            if self.probe_distance(pos.hash, at) < dist {
+                return None;
+            }
+            if pos.hash == hash && is_match(pos.index) {
+                return Some(at);
+            }
+            at = (at + 1) & self.mask();
+            dist += 1;
+        }
+        None
+    }
+
+    /// The slot holding `index`, which must be in the index
+    fn find_index(&self, hash: u64, index: usize) -> usize {
+        self.find(hash, |i| i == index)
+            .expect("every entry is in the index")
+    }
+
+    /// Adds a position whose key is known not to be in the index yet. There
+    /// must be room for it.
+    fn insert(&mut self, pos: Pos) {
+        let mask = self.mask();
+        let mut carry = pos;
+        let mut at = carry.hash as usize & mask;
+        let mut dist = 0;
+        loop {
+            match self.table[at] {
+                None => {
+                    self.table[at] = Some(carry);
+                    return;
+                }
+                Some(resident) => {
+                    let resident_dist = self.probe_distance(resident.hash, at);
+                    if resident_dist < dist {
+                        self.table[at] = Some(carry);
+                        carry = resident;
+                        dist = resident_dist;
+                    }
+                }
+            }
+            at = (at + 1) & mask;
+            dist += 1;
+        }
+    }
+
+    /// Removes the position in slot `at`, shifting the rest of its cluster
+    /// back (see [`super::open`])
+    fn erase(&mut self, at: usize) {
+        let mask = self.mask();
+        self.table[at] = None;
+        let mut hole = at;
+        loop {
+            let next = (hole + 1) & mask;
+            match self.table[next] {
+                Some(pos) if self.probe_distance(pos.hash, next) > 0 => {
+                    self.table[hole] = self.table[next].take();
+                    hole = next;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// An index of `slots` slots over `entries`
+    fn build<K, V>(slots: usize, entries: &[Slot<K, V>]) -> Self {
+        let mut indices = Indices::with_slots(slots);
+        for (index, slot) in entries.iter().enumerate() {
+            indices.insert(Pos {
+                index,
+                hash: slot.hash,
+            });
+        }
+        indices
+    }
+
+    fn clear(&mut self) {
+        for pos in &mut self.table {
+            *pos = None;
+        }
+    }
+}
+
+/// Rebuilds the index over the entries when dropped. Whatever reorders or
+/// removes entries with a caller's closure holds one of these, so that the
+/// index is rebuilt even if the closure panics partway through.
+struct Reindex<'a, K, V> {
+    entries: &'a mut Vec<Slot<K, V>>,
+    indices: &'a mut Indices,
+}
+
+impl<K, V> Drop for Reindex<'_, K, V> {
+    fn drop(&mut self) {
+        *self.indices = Indices::build(self.indices.table.len(), self.entries);
+    }
+}
+
+/// A hash map that iterates in insertion order. See the [module docs](self).
+#[derive(Clone)]
+pub struct IndexMap<K, V, S = RandomState> {
+    /// The pairs, in order
+    entries: Vec<Slot<K, V>>,
+    indices: Indices,
+    hash_builder: S,
+}
+
+impl<K, V> IndexMap<K, V, RandomState> {
+    /// Constructs a new, empty `IndexMap<K,V>`. Nothing is allocated until the
+    /// first insert.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Constructs a new, empty `IndexMap<K,V>` that can hold at least
+    /// `capacity` pairs before it needs to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S> {
+    /// Constructs a new, empty map which hashes its keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        IndexMap {
+            entries: Vec::new(),
+            indices: Indices::default(),
+            hash_builder,
+        }
+    }
+
+    /// Combines [`IndexMap::with_capacity`] and [`IndexMap::with_hasher`].
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Borrows the entries, to be indexed afresh once the borrow ends
+    fn reindex(&mut self) -> Reindex<'_, K, V> {
+        Reindex {
+            entries: &mut self.entries,
+            indices: &mut self.indices,
+        }
+    }
+
+    /// Removes every pair, keeping the allocations for reuse.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+    }
+
+    /// The number of pairs the map can hold before either the entries or the
+    /// index has to grow
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity().min(self.indices.capacity())
+    }
+
+    /// Makes room for at least `additional` more pairs without growing.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self
+            .len()
+            .checked_add(additional)
+            .expect("capacity overflow");
+        self.entries.reserve(additional);
+        if needed > self.indices.capacity() {
+            let slots = Indices::slots_for(needed).max(self.indices.table.len() * 2);
+            self.indices = Indices::build(slots, &self.entries);
+        }
+    }
+
+    /// Shrinks the entries and the index to fit the current pairs. An empty
+    /// map releases everything.
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        let slots = if self.is_empty() {
+            0
+        } else {
+            Indices::slots_for(self.len())
+        };
+        if slots < self.indices.table.len() {
+            self.indices = Indices::build(slots, &self.entries);
+        }
+    }
+
+    /// The pair at position `index`, if there is one.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|slot| (&slot.key, &slot.value))
+    }
+
+    /// The pair at position `index`, with a mutable value, if there is one.
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries
+            .get_mut(index)
+            .map(|slot| (&slot.key, &mut slot.value))
+    }
+
+    /// The first pair, in order
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    /// The last pair, in order
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.get_index(self.len().wrapping_sub(1))
+    }
+
+    /// Removes the pair at position `index` by moving the last pair into its
+    /// place. This is *O(1)*, but it changes the order: use
+    /// [`IndexMap::shift_remove_index`] to keep it.
+    pub fn swap_remove_index(&mut self, index: usize) -> Option<(K, V)> {
+        let hash = self.entries.get(index)?.hash;
+        let at = self.indices.find_index(hash, index);
+        self.indices.erase(at);
+        let last = self.len() - 1;
+        let removed = self.entries.swap_remove(index);
+        if index != last {
+            // the last pair moved, so its position has to follow it
+            let at = self.indices.find_index(self.entries[index].hash, last);
+            self.indices.table[at] = Some(Pos {
+                index,
+                hash: self.entries[index].hash,
+            });
+        }
+        Some((removed.key, removed.value))
+    }
+
+    /// Removes the pair at position `index` by shifting every later pair down
+    /// one position. This keeps the order, but it is *O(n)*.
+    pub fn shift_remove_index(&mut self, index: usize) -> Option<(K, V)> {
+        let hash = self.entries.get(index)?.hash;
+        let at = self.indices.find_index(hash, index);
+        self.indices.erase(at);
+        let removed = self.entries.remove(index);
+        for pos in self.indices.table.iter_mut().flatten() {
+            if pos.index > index {
+                pos.index -= 1;
+            }
+        }
+        Some((removed.key, removed.value))
+    }
+
+    /// Sorts the pairs by key with `sorter`.
+    pub fn sort_keys<T>(&mut self, sorter: &T)
+    where
+        T: Sorter,
+        K: Ord,
+    {
+        self.sort_by(sorter, |k1, _, k2, _| k1.cmp(k2));
+    }
+
+    /// Sorts the pairs with `sorter`, comparing them with `compare`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::{hashmap::IndexMap, sort::Insertion};
+    ///
+    /// let mut scores: IndexMap<_, _> = vec![("a", 3), ("b", 9), ("c", 5)].into_iter().collect();
+    /// // highest score first
+    /// scores.sort_by(&Insertion { smart: true }, |_, v1, _, v2| v2.cmp(v1));
+    /// assert_eq!(scores.keys().collect::<Vec<_>>(), vec![&"b", &"c", &"a"]);
+    /// ```
+    pub fn sort_by<T, F>(&mut self, sorter: &T, mut compare: F)
+    where
+        T: Sorter,
+        F: FnMut(&K, &V, &K, &V) -> Ordering,
+    {
+        // every position may change, so the index starts over once `map` is
+        // dropped, whether or not `compare` panics
+        let map = self.reindex();
+        sorter.sort_by(map.entries, |a, b| {
+            compare(&a.key, &a.value, &b.key, &b.value)
+        });
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// See [`super::HashMap`] on hashing borrowed forms of a key, and on why
+    /// the hash is mixed.
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        fmix64(self.hash_builder.hash_one(key))
+    }
+
+    /// The position of `key` in the entries, if it is in the map
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let entries = &self.entries;
+        let at = self
+            .indices
+            .find(hash, |i| entries[i].key.borrow() == key)?;
+        self.indices.table[at].map(|pos| pos.index)
+    }
+
+    /// Adds a pair whose key is known not to be in the map, at the end. The
+    /// index must have room for it.
+    fn push(&mut self, hash: u64, key: K, value: V) -> usize {
+        let index = self.entries.len();
+        self.entries.push(Slot { hash, key, value });
+        self.indices.insert(Pos { index, hash });
+        index
+    }
+
+    /// Inserts `V` at key `K`. A new key goes at the end; an existing key
+    /// keeps its position, and its old value is replaced and returned.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::IndexMap;
+    ///
+    /// let mut m = IndexMap::new();
+    /// m.insert("a", 1);
+    /// m.insert("b", 2);
+    /// assert_eq!(m.insert("a", 3), Some(1));
+    /// assert_eq!(m.iter().collect::<Vec<_>>(), vec![(&"a", &3), (&"b", &2)]);
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+        if let Some(i) = self.find(hash, &key) {
+            return Some(mem::replace(&mut self.entries[i].value, value));
+        }
+        self.reserve(1);
+        self.push(hash, key, value);
+        None
+    }
+
+    /// Gets an optional reference to `V` using key `K`, or any borrowed form of
+    /// it.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.get_index_of(key)?;
+        Some(&self.entries[i].value)
+    }
+
+    /// Gets an optional mutable reference to `V` using key `K`, or any borrowed
+    /// form of it.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.get_index_of(key)?;
+        Some(&mut self.entries[i].value)
+    }
+
+    /// The position of `key`, or any borrowed form of it, if it is in the map.
+    pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(self.hash(key), key)
+    }
+
+    /// Returns `true` if the map holds a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_index_of(key).is_some()
+    }
+
+    /// Removes the pair for `key` like [`IndexMap::swap_remove`], which is
+    /// fast but moves the last pair into the gap. Use
+    /// [`IndexMap::shift_remove`] to keep the order.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.swap_remove(key)
+    }
+
+    /// Removes the pair for `key`, moving the last pair into its position.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::IndexMap;
+    ///
+    /// let mut m: IndexMap<_, _> = vec![("a", 1), ("b", 2), ("c", 3)].into_iter().collect();
+    /// assert_eq!(m.swap_remove("a"), Some(1));
+    /// assert_eq!(m.keys().collect::<Vec<_>>(), vec![&"c", &"b"]);
+    /// ```
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.get_index_of(key)?;
+        self.swap_remove_index(i).map(|(_, v)| v)
+    }
+
+    /// Removes the pair for `key`, shifting every later pair down one
+    /// position.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::IndexMap;
+    ///
+    /// let mut m: IndexMap<_, _> = vec![("a", 1), ("b", 2), ("c", 3)].into_iter().collect();
+    /// assert_eq!(m.shift_remove("a"), Some(1));
+    /// assert_eq!(m.keys().collect::<Vec<_>>(), vec![&"b", &"c"]);
+    /// ```
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.get_index_of(key)?;
+        self.shift_remove_index(i).map(|(_, v)| v)
+    }
+
+    /// Gets the [`Entry`] for `key`, for in-place insertion or modification.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into a single pair of an [`IndexMap`], which may or may not be
+/// present. Created by [`IndexMap::entry`].
+pub enum Entry<'a, K, V, S = RandomState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// An entry for a key that is in the map
+pub struct OccupiedEntry<'a, K, V, S = RandomState> {
+    map: &'a mut IndexMap<K, V, S>,
+    index: usize,
+}
+
+/// An entry for a key that is not in the map
+pub struct VacantEntry<'a, K, V, S = RandomState> {
+    map: &'a mut IndexMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Inserts `default` if the entry is vacant, and returns a mutable
+    /// reference to the value either way.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], but only computes the value when it is needed.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], using `V::default()`.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied, leaving a vacant entry
+    /// untouched.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// The key this entry was created for
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// The position the pair has, or would have once inserted
+    pub fn index(&self) -> usize {
+        match self {
+            Entry::Occupied(e) => e.index(),
+            Entry::Vacant(e) => e.index(),
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    /// The key already in the map
+    pub fn key(&self) -> &K {
+        &self.map.entries[self.index].key
+    }
+
+    /// The position of the pair
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.index].value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.index].value
+    }
+
+    /// Converts the entry into a mutable reference that lives as long as the
+    /// borrow of the map
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.index].value
+    }
+
+    /// Replaces the value, returning the old one. The key is left untouched.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the pair from the map like [`IndexMap::swap_remove`],
+    /// returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the pair from the map like [`IndexMap::swap_remove`],
+    /// returning both the key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        self.swap_remove_entry()
+    }
+
+    /// Removes the pair from the map like [`IndexMap::swap_remove`].
+    pub fn swap_remove_entry(self) -> (K, V) {
+        self.map
+            .swap_remove_index(self.index)
+            .expect("an occupied entry's pair is in the map")
+    }
+
+    /// Removes the pair from the map like [`IndexMap::shift_remove`].
+    pub fn shift_remove_entry(self) -> (K, V) {
+        self.map
+            .shift_remove_index(self.index)
+            .expect("an occupied entry's pair is in the map")
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// The key that would be inserted
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back ownership of the key
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// The position the pair would have: the end of the map
+    pub fn index(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Inserts the pair at the end of the map, returning a mutable reference
+    /// to the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let map = self.map;
+        // only grow once the pair is known to be new
+        map.reserve(1);
+        let i = map.push(self.hash, self.key, value);
+        &mut map.entries[i].value
+    }
+}
+
+impl<K, V, S> Default for IndexMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, in order
+impl<K, V, S> Debug for IndexMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal if they hold the same pairs, regardless of their order.
+/// Compare [`IndexMap::iter`]s to take the order into account.
+impl<K, V, S> PartialEq for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+/// ## Panics
+/// If the key is not in the map. Use [`IndexMap::get`] when that is possible.
+impl<K, Q, V, S> Index<&Q> for IndexMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in IndexMap")
+    }
+}
+
+/// Indexes the values by position, e.g. `map[0]` is the first value.
+///
+/// ## Panics
+/// If `index` is out of bounds.
+impl<K, V, S> Index<usize> for IndexMap<K, V, S> {
+    type Output = V;
+
+    fn index(&self, index: usize) -> &V {
+        &self.entries[index].value
+    }
+}
+
+/// ## Panics
+/// If `index` is out of bounds.
+impl<K, V, S> IndexMut<usize> for IndexMap<K, V, S> {
+    fn index_mut(&mut self, index: usize) -> &mut V {
+        &mut self.entries[index].value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort::{Bubble, Quick};
+    use std::panic::{self, AssertUnwindSafe};
+
+    map_conformance!(IndexMap);
+
+    fn letters() -> IndexMap<char, usize> {
+        "qwerty".chars().enumerate().map(|(i, c)| (c, i)).collect()
+    }
+
+    fn keys<V>(m: &IndexMap<char, V>) -> String {
+        m.keys().collect()
+    }
+
+    /// Every position is in the index exactly once, next to the right hash
+    fn assert_indexed<K, V, S>(m: &IndexMap<K, V, S>) {
+        let mut seen = vec![false; m.len()];
+        for pos in m.indices.table.iter().flatten() {
+            assert!(!seen[pos.index], "position {} indexed twice", pos.index);
+            seen[pos.index] = true;
+            assert_eq!(pos.hash, m.entries[pos.index].hash);
+        }
+        assert!(seen.into_iter().all(|s| s));
+    }
+
+    #[test]
+    fn iterates_in_insertion_order() {
+        let mut m = letters();
+        assert_eq!(keys(&m), "qwerty");
+        m.insert('w', 100);
+        m.insert('a', 6);
+        assert_eq!(keys(&m), "qwertya");
+        assert_eq!(m.values().next_back(), Some(&6));
+        assert_eq!(m.first(), Some((&'q', &0)));
+        assert_eq!(m.last(), Some((&'a', &6)));
+        assert_eq!(m[1], 100);
+        m[1] = 1;
+        assert_eq!(m[&'w'], 1);
+    }
+
+    #[test]
+    fn positions() {
+        let mut m = letters();
+        assert_eq!(m.get_index_of(&'e'), Some(2));
+        assert_eq!(m.get_index_of(&'z'), None);
+        assert_eq!(m.get_index(5), Some((&'y', &5)));
+        assert_eq!(m.get_index(6), None);
+        if let Some((_, v)) = m.get_index_mut(0) {
+            *v = 10;
+        }
+        assert_eq!(m[&'q'], 10);
+    }
+
+    #[test]
+    fn swap_and_shift_removal() {
+        let mut m = letters();
+        assert_eq!(m.swap_remove(&'w'), Some(1));
+        assert_eq!(keys(&m), "qyert");
+        assert_indexed(&m);
+        assert_eq!(m.shift_remove(&'y'), Some(5));
+        assert_eq!(keys(&m), "qert");
+        assert_indexed(&m);
+        assert_eq!(m.get_index_of(&'t'), Some(3));
+        assert_eq!(m.swap_remove_index(3), Some(('t', 4)));
+        assert_eq!(m.shift_remove_index(0), Some(('q', 0)));
+        assert_eq!(m.shift_remove_index(5), None);
+        assert_eq!(keys(&m), "er");
+        assert_indexed(&m);
+        assert_eq!(m[&'r'], 3);
+    }
+
+    #[test]
+    fn entries_know_their_position() {
+        let mut m = letters();
+        assert_eq!(m.entry('e').index(), 2);
+        assert_eq!(m.entry('z').index(), 6);
+        match m.entry('e') {
+            Entry::Occupied(e) => assert_eq!(e.shift_remove_entry(), ('e', 2)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(keys(&m), "qwrty");
+        *m.entry('z').or_default() += 1;
+        assert_eq!(keys(&m), "qwrtyz");
+        assert_indexed(&m);
+    }
+
+    #[test]
+    fn sorting_with_the_crates_sorters() {
+        let mut m = letters();
+        m.sort_keys(&Quick);
+        assert_eq!(keys(&m), "eqrtwy");
+        assert_indexed(&m);
+        assert_eq!(m[&'q'], 0);
+        assert_eq!(m.get_index_of(&'y'), Some(5));
+
+        m.sort_by(&Bubble, |_, v1, _, v2| v2.cmp(v1));
+        assert_eq!(keys(&m), "ytrewq");
+        assert_indexed(&m);
+    }
+
+    #[test]
+    fn a_panicking_closure_leaves_the_map_indexed() {
+        let mut m = letters();
+        let mut calls = 0;
+        let sorted = panic::catch_unwind(AssertUnwindSafe(|| {
+            m.sort_by(&Bubble, |k1, _, k2, _| {
+                calls += 1;
+                assert!(calls < 5, "comparator gave up");
+                k1.cmp(k2)
+            })
+        }));
+        assert!(sorted.is_err());
+        assert_eq!(m.len(), 6);
+        assert_indexed(&m);
+
+        let mut seen = 0;
+        let retained = panic::catch_unwind(AssertUnwindSafe(|| {
+            m.retain(|_, _| {
+                seen += 1;
+                assert!(seen < 4, "predicate gave up");
+                seen % 2 == 0
+            })
+        }));
+        assert!(retained.is_err());
+        assert_eq!(m.len(), 4);
+        assert_indexed(&m);
+        m.insert('a', 9);
+        assert_indexed(&m);
+    }
+
+    #[test]
+    fn leaking_an_extract_if_leaves_an_empty_map() {
+        let mut m: IndexMap<u32, ()> = (0..20).map(|i| (i, ())).collect();
+        let mut extract = m.extract_if(|k, _| k % 2 == 0);
+        assert_eq!(extract.next(), Some((0, ())));
+        assert!(extract.next().is_some());
+        mem::forget(extract);
+        assert!(m.is_empty());
+        assert_eq!(m.get(&1), None);
+        m.insert(1, ());
+        assert_eq!(m.len(), 1);
+        assert_indexed(&m);
+    }
+
+    #[test]
+    fn retain_drain_and_extract_if_keep_the_order() {
+        let mut m: IndexMap<u32, ()> = (0..20).map(|i| (i, ())).collect();
+        m.retain(|k, _| k % 2 == 0);
+        assert_eq!(
+            m.keys().copied().collect::<Vec<_>>(),
+            (0..20).step_by(2).collect::<Vec<_>>()
+        );
+        assert_indexed(&m);
+
+        let taken: Vec<_> = m.extract_if(|k, _| k % 4 == 0).map(|(k, _)| k).collect();
+        assert_eq!(taken, vec![0, 4, 8, 12, 16]);
+        assert_eq!(
+            m.keys().copied().collect::<Vec<_>>(),
+            vec![2, 6, 10, 14, 18]
+        );
+        assert_indexed(&m);
+        assert_eq!(m.get_index_of(&14), Some(3));
+
+        let drained: Vec<_> = m.drain().map(|(k, _)| k).collect();
+        assert_eq!(drained, vec![2, 6, 10, 14, 18]);
+        assert!(m.is_empty());
+        assert!(!m.contains_key(&2));
+    }
+
+    #[test]
+    fn the_index_stays_consistent_under_churn() {
+        let mut m = IndexMap::new();
+        for i in 0..3000u32 {
+            m.insert(i, i);
+            match i % 5 {
+                0 => {
+                    m.swap_remove(&(i / 2));
+                }
+                1 => {
+                    m.shift_remove(&(i / 3));
+                }
+                _ => {}
+            }
+        }
+        assert_indexed(&m);
+        for (i, (k, _)) in m.iter().enumerate() {
+            assert_eq!(m.get_index_of(k), Some(i));
+        }
+    }
+}
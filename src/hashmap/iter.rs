@@ -0,0 +1,476 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Iteration over a [`HashMap`]. Every iterator walks the bins in order and
+//! each bin's pairs in order, so the order is arbitrary but stable for as long
+//! as the map isn't modified.
+use super::*;
+use std::{
+    iter::{FromIterator, FusedIterator},
+    slice, vec,
+};
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// An iterator over `(&K, &V)` pairs, in arbitrary order.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let m: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+    /// let mut pairs: Vec<_> = m.iter().collect();
+    /// pairs.sort();
+    /// assert_eq!(pairs, vec![(&"a", &1), (&"b", &2)]);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            bins: self.hash_table.iter(),
+            slots: [].iter(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over `(&K, &mut V)` pairs, in arbitrary order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            bins: self.hash_table.iter_mut(),
+            slots: [].iter_mut(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over the keys, in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator over the values, in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator over mutable references to the values, in arbitrary order.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+    /// for v in m.values_mut() {
+    ///     *v *= 10;
+    /// }
+    /// assert_eq!(m["a"], 10);
+    /// assert_eq!(m["b"], 20);
+    /// ```
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Removes every pair from the map, yielding them as an iterator. The bins
+    /// are kept, so the map's capacity is unchanged. Pairs that are not
+    /// consumed before the iterator is dropped are still removed.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m: HashMap<_, _> = (0..10).map(|i| (i, i * i)).collect();
+    /// let capacity = m.capacity();
+    /// let mut drained: Vec<_> = m.drain().collect();
+    /// drained.sort();
+    /// assert_eq!(drained[3], (3, 9));
+    /// assert!(m.is_empty());
+    /// assert_eq!(m.capacity(), capacity);
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        // the map is left empty (and without bins) until the `Drain` hands
+        // them back, so it stays consistent even if the `Drain` is leaked
+        let remaining = mem::replace(&mut self.len, 0);
+        Drain {
+            bins: mem::take(&mut self.hash_table),
+            bin: 0,
+            table: &mut self.hash_table,
+            remaining,
+        }
+    }
+
+    /// Keeps only the pairs for which `f` returns `true`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m: HashMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// m.retain(|k, _| k % 2 == 0);
+    /// assert_eq!(m.len(), 5);
+    /// assert!(m.contains_key(&4));
+    /// assert!(!m.contains_key(&5));
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        // Count removals as they happen, so `len` stays right if `f` panics.
+        let len = &mut self.len;
+        for bin in &mut self.hash_table {
+            bin.data.retain_mut(|slot| {
+                let keep = f(&slot.key, &mut slot.value);
+                if !keep {
+                    *len -= 1;
+                }
+                keep
+            });
+        }
+    }
+
+    /// Removes and yields the pairs for which `pred` returns `true`, lazily:
+    /// each call to `next` walks forward to the next matching pair. Pairs that
+    /// are not reached before the iterator is dropped stay in the map.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashMap;
+    ///
+    /// let mut m: HashMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// let mut odd: Vec<_> = m.extract_if(|k, _| k % 2 == 1).collect();
+    /// odd.sort();
+    /// assert_eq!(odd, vec![(1, 1), (3, 3), (5, 5), (7, 7), (9, 9)]);
+    /// assert_eq!(m.len(), 5);
+    /// ```
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        ExtractIf {
+            bins: &mut self.hash_table,
+            len: &mut self.len,
+            bin: 0,
+            index: 0,
+            pred,
+        }
+    }
+}
+
+/// Created by [`HashMap::iter`]
+pub struct Iter<'a, K, V> {
+    bins: slice::Iter<'a, Bin<K, V>>,
+    slots: slice::Iter<'a, Slot<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(slot) = self.slots.next() {
+                self.remaining -= 1;
+                return Some((&slot.key, &slot.value));
+            }
+            self.slots = self.bins.next()?.data.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// Implemented by hand, as deriving it would require `K: Clone` and `V: Clone`
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            bins: self.bins.clone(),
+            slots: self.slots.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+/// Created by [`HashMap::iter_mut`]
+pub struct IterMut<'a, K, V> {
+    bins: slice::IterMut<'a, Bin<K, V>>,
+    slots: slice::IterMut<'a, Slot<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(slot) = self.slots.next() {
+                self.remaining -= 1;
+                return Some((&slot.key, &mut slot.value));
+            }
+            self.slots = self.bins.next()?.data.iter_mut();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// Created by [`HashMap::keys`]
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// Created by [`HashMap::values`]
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// Created by [`HashMap::values_mut`]
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+/// Created by [`HashMap::into_iter`](IntoIterator::into_iter)
+pub struct IntoIter<K, V> {
+    bins: vec::IntoIter<Bin<K, V>>,
+    slots: vec::IntoIter<Slot<K, V>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(slot) = self.slots.next() {
+                self.remaining -= 1;
+                return Some((slot.key, slot.value));
+            }
+            self.slots = self.bins.next()?.data.into_iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// Created by [`HashMap::drain`]. Empties each bin from its end, so that the
+/// bins keep their allocations.
+pub struct Drain<'a, K, V> {
+    /// The map's bins, taken out of it for as long as the `Drain` lives
+    bins: Vec<Bin<K, V>>,
+    /// The bin being emptied
+    bin: usize,
+    /// Where the emptied bins go back to
+    table: &'a mut Vec<Bin<K, V>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for Drain<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(slot) = self.bins.get_mut(self.bin)?.data.pop() {
+                self.remaining -= 1;
+                return Some((slot.key, slot.value));
+            }
+            self.bin += 1;
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Drain<'_, K, V> {}
+impl<K, V> FusedIterator for Drain<'_, K, V> {}
+
+/// Removes whatever wasn't yielded, and gives the emptied bins back to the
+/// map
+impl<K, V> Drop for Drain<'_, K, V> {
+    fn drop(&mut self) {
+        for bin in &mut self.bins[self.bin..] {
+            bin.data.clear();
+        }
+        mem::swap(self.table, &mut self.bins);
+    }
+}
+
+/// Created by [`HashMap::extract_if`]
+pub struct ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    bins: &'a mut Vec<Bin<K, V>>,
+    /// The map's length, kept up to date as pairs are removed
+    len: &'a mut usize,
+    bin: usize,
+    /// The next position to test within the current bin
+    index: usize,
+    pred: F,
+}
+
+impl<K, V, F> Iterator for ExtractIf<'_, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(bin) = self.bins.get_mut(self.bin) {
+            while let Some(slot) = bin.data.get_mut(self.index) {
+                if (self.pred)(&slot.key, &mut slot.value) {
+                    // the last pair moves into `index`, so don't advance
+                    let slot = bin.data.swap_remove(self.index);
+                    *self.len -= 1;
+                    return Some((slot.key, slot.value));
+                }
+                self.index += 1;
+            }
+            self.bin += 1;
+            self.index = 0;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(*self.len))
+    }
+}
+
+impl<K, V, F> FusedIterator for ExtractIf<'_, K, V, F> where F: FnMut(&K, &mut V) -> bool {}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            bins: self.hash_table.into_iter(),
+            slots: Vec::new().into_iter(),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = HashMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // the lower bound might all be duplicates, so this can over-reserve;
+        // it is still cheaper than growing several times along the way
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S> Extend<(&'a K, &'a V)> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Copy,
+    V: Copy,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
@@ -0,0 +1,463 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A multimap: a [`HashMap`] from each key to a `Vec` of its values, for
+//! one-to-many lookups.
+//!
+//! A key's values are kept in the order they were inserted, repeats and all.
+//! A key is only in the map while it has at least one value, so
+//! [`MultiMap::len`] counts values and [`MultiMap::keys_len`] counts keys.
+//!
+//! ```
+//! use tinystd::hashmap::MultiMap;
+//!
+//! let mut authors = MultiMap::new();
+//! authors.insert("Good Omens", "Pratchett");
+//! authors.insert("Good Omens", "Gaiman");
+//! authors.insert("Mort", "Pratchett");
+//!
+//! assert_eq!(authors.get_all("Good Omens"), ["Pratchett", "Gaiman"]);
+//! assert_eq!(authors.len(), 3);
+//! assert_eq!(authors.keys_len(), 2);
+//! assert!(authors.remove_one("Mort", &"Pratchett"));
+//! assert!(!authors.contains_key("Mort"));
+//! ```
+use super::HashMap;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    iter::{FromIterator, FusedIterator},
+    slice,
+};
+
+/// A map from each key to one or more values. See the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct MultiMap<K, V, S = RandomState> {
+    /// Never holds an empty `Vec`
+    pub(in crate::hashmap) map: HashMap<K, Vec<V>, S>,
+    /// The number of values, over every key
+    pub(in crate::hashmap) len: usize,
+}
+
+impl<K, V> MultiMap<K, V, RandomState> {
+    /// Constructs a new, empty `MultiMap<K, V>`. Nothing is allocated until
+    /// the first insert.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> MultiMap<K, V, S> {
+    /// Constructs a new, empty multimap which hashes its keys with
+    /// `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        MultiMap {
+            map: HashMap::with_hasher(hash_builder),
+            len: 0,
+        }
+    }
+
+    /// The multimap's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// The number of values, over every key
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The number of keys with at least one value
+    pub fn keys_len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes every key and value.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.len = 0;
+    }
+
+    /// An iterator over every key and value, in arbitrary order of keys. A
+    /// key with several values is yielded once for each, with its values in
+    /// the order they were inserted.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            keys: self.map.iter(),
+            values: None,
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over each key along with all of its values, in arbitrary
+    /// order of keys.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::MultiMap;
+    ///
+    /// let m: MultiMap<_, _> = vec![(1, 'a'), (2, 'b'), (1, 'c')].into_iter().collect();
+    /// let mut all: Vec<_> = m.iter_all().collect();
+    /// all.sort();
+    /// assert_eq!(all, vec![(&1, &['a', 'c'][..]), (&2, &['b'][..])]);
+    /// ```
+    pub fn iter_all(&self) -> IterAll<'_, K, V> {
+        IterAll {
+            inner: self.map.iter(),
+        }
+    }
+
+    /// An iterator over the keys, each yielded once, in arbitrary order.
+    pub fn keys(&self) -> super::Keys<'_, K, Vec<V>> {
+        self.map.keys()
+    }
+
+    /// Keeps only the values for which `f` returns `true`, dropping any key
+    /// left without values.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut len = 0;
+        self.map.retain(|k, values| {
+            values.retain(|v| f(k, v));
+            len += values.len();
+            !values.is_empty()
+        });
+        self.len = len;
+    }
+}
+
+impl<K, V, S> MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Adds `value` to the values of `key`, after any it already has.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.map.entry(key).or_default().push(value);
+        self.len += 1;
+    }
+
+    /// The first value inserted for `key`, if it has any
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_all(key).first()
+    }
+
+    /// Every value of `key`, in the order they were inserted. Empty if the
+    /// key isn't in the map.
+    pub fn get_all<Q>(&self, key: &Q) -> &[V]
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns `true` if `key` has at least one value.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Removes the first value of `key` that equals `value`, keeping the
+    /// order of the rest. Returns `true` if there was one to remove.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::MultiMap;
+    ///
+    /// let mut m: MultiMap<_, _> = vec![(1, 'a'), (1, 'b'), (1, 'a')].into_iter().collect();
+    /// assert!(m.remove_one(&1, &'a'));
+    /// assert_eq!(m.get_all(&1), ['b', 'a']);
+    /// assert!(!m.remove_one(&1, &'z'));
+    /// ```
+    pub fn remove_one<Q>(&mut self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: PartialEq,
+    {
+        let values = match self.map.get_mut(key) {
+            Some(values) => values,
+            None => return false,
+        };
+        let i = match values.iter().position(|v| v == value) {
+            Some(i) => i,
+            None => return false,
+        };
+        values.remove(i);
+        if values.is_empty() {
+            self.map.remove(key);
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes `key` and returns all of its values, in the order they were
+    /// inserted. Empty if the key isn't in the map.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let values = self.map.remove(key).unwrap_or_default();
+        self.len -= values.len();
+        values
+    }
+}
+
+/// An iterator over every key and value of a [`MultiMap`]. Created by
+/// [`MultiMap::iter`].
+pub struct Iter<'a, K, V> {
+    keys: super::Iter<'a, K, Vec<V>>,
+    /// The key being yielded, and its values still to come
+    values: Option<(&'a K, slice::Iter<'a, V>)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, values)) = &mut self.values {
+                if let Some(v) = values.next() {
+                    self.remaining -= 1;
+                    return Some((*k, v));
+                }
+            }
+            let (k, values) = self.keys.next()?;
+            self.values = Some((k, values.iter()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// An iterator over each key of a [`MultiMap`] with all of its values.
+/// Created by [`MultiMap::iter_all`].
+pub struct IterAll<'a, K, V> {
+    inner: super::Iter<'a, K, Vec<V>>,
+}
+
+impl<'a, K, V> Iterator for IterAll<'a, K, V> {
+    type Item = (&'a K, &'a [V]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, values)| (k, values.as_slice()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterAll<'_, K, V> {}
+impl<K, V> FusedIterator for IterAll<'_, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a MultiMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = MultiMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> Default for MultiMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map from each key to a list of its values, e.g.
+/// `{1: ['a', 'c']}`
+impl<K, V, S> Debug for MultiMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter_all()).finish()
+    }
+}
+
+/// Two multimaps are equal if every key has the same values, in the same
+/// order.
+impl<K, V, S> PartialEq for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.map == other.map
+    }
+}
+
+impl<K, V, S> Eq for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::BTreeMap;
+
+    /// Checks the multimap against a model of it
+    fn check(m: &MultiMap<u8, u32>, model: &BTreeMap<u8, Vec<u32>>) {
+        assert_eq!(m.len(), model.values().map(Vec::len).sum::<usize>());
+        assert_eq!(m.keys_len(), model.len());
+        assert_eq!(m.iter().len(), m.len());
+        assert_eq!(m.iter().count(), m.len());
+        for (k, values) in model {
+            assert_eq!(m.get_all(k), values.as_slice());
+        }
+        for (k, values) in m.iter_all() {
+            assert!(!values.is_empty());
+            assert_eq!(model[k], values);
+        }
+    }
+
+    #[test]
+    fn matches_a_model() {
+        let mut rng = StdRng::seed_from_u64(0x3317);
+        let mut m = MultiMap::new();
+        let mut model = BTreeMap::<u8, Vec<u32>>::new();
+        for _ in 0..5000 {
+            let k = rng.gen_range(0..32u8);
+            let v = rng.gen_range(0..8u32);
+            match rng.gen_range(0..10) {
+                0..=5 => {
+                    m.insert(k, v);
+                    model.entry(k).or_default().push(v);
+                }
+                6..=8 => {
+                    let values = model.entry(k).or_default();
+                    let removed = values
+                        .iter()
+                        .position(|&x| x == v)
+                        .map(|i| values.remove(i));
+                    if values.is_empty() {
+                        model.remove(&k);
+                    }
+                    assert_eq!(m.remove_one(&k, &v), removed.is_some());
+                }
+                _ => {
+                    assert_eq!(m.remove_all(&k), model.remove(&k).unwrap_or_default());
+                }
+            }
+            check(&m, &model);
+        }
+    }
+
+    #[test]
+    fn missing_keys_have_no_values() {
+        let mut m = MultiMap::<String, i32>::new();
+        assert!(m.get_all("nope").is_empty());
+        assert_eq!(m.get("nope"), None);
+        assert!(m.remove_all("nope").is_empty());
+        assert!(!m.remove_one("nope", &1));
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn keeps_repeated_values() {
+        let mut m = MultiMap::new();
+        m.insert("k", 1);
+        m.insert("k", 1);
+        assert_eq!(m.get_all("k"), [1, 1]);
+        assert_eq!(m.len(), 2);
+        assert!(m.remove_one("k", &1));
+        assert_eq!(m.get_all("k"), [1]);
+    }
+
+    #[test]
+    fn retain_drops_emptied_keys() {
+        let mut m: MultiMap<_, _> = (0..100).map(|i| (i % 10, i)).collect();
+        m.retain(|&k, &v| k < 5 && v % 2 == 0);
+        assert_eq!(m.len(), 30);
+        assert_eq!(m.keys_len(), 3);
+        assert_eq!(m.get_all(&4), [4, 14, 24, 34, 44, 54, 64, 74, 84, 94]);
+        assert!(!m.contains_key(&1));
+    }
+
+    #[test]
+    fn equality_respects_value_order() {
+        let a: MultiMap<_, _> = vec![(1, 'a'), (1, 'b')].into_iter().collect();
+        let b: MultiMap<_, _> = vec![(1, 'b'), (1, 'a')].into_iter().collect();
+        assert_ne!(a, b);
+        assert_eq!(a, a.clone());
+        assert_eq!(format!("{:?}", a), "{1: ['a', 'b']}");
+    }
+
+    #[test]
+    fn clear() {
+        let mut m: MultiMap<_, _> = (0..10).map(|i| (i, i)).collect();
+        m.clear();
+        assert!(m.is_empty());
+        assert_eq!(m.keys_len(), 0);
+        assert_eq!(m.iter().next(), None);
+    }
+}
@@ -0,0 +1,706 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An open-addressing hash map with [Robin Hood][1] linear probing. Every pair
+//! lives directly in one flat table, so there is no allocation per bin and a
+//! lookup reads consecutive memory.
+//!
+//! A key that hashes to slot `i` goes in the first free slot at or after `i`.
+//! Its distance from `i` is its *probe distance*, and the Robin Hood rule is
+//! that an insert takes the slot of any pair that is closer to home than the
+//! new pair is, then carries on inserting the displaced pair instead. This
+//! keeps probe distances short and even, and it lets a lookup give up as soon
+//! as it passes a pair that is closer to home than the key would be.
+//!
+//! Removal uses [backward-shift deletion][2]: the rest of the cluster moves
+//! back one slot, so no tombstones are left behind to slow down later
+//! lookups.
+//!
+//! [1]: https://cs.uwaterloo.ca/research/tr/1986/CS-86-14.pdf
+//! [2]: https://codecapsule.com/2013/11/17/robin-hood-hashing-backward-shift-deletion/
+use super::Slot;
+use crate::hash::fmix64;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::Index,
+};
+
+mod iter;
+
+pub(in crate::hashmap) use iter::SlotTable;
+pub use iter::{Drain, ExtractIf, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+
+/// The number of slots allocated by the first insert into an empty map
+const INITIAL_SLOTS: usize = 8;
+
+/// Robin Hood keeps probe distances short enough to run much fuller than
+/// [`super::HashMap`] does
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.875;
+
+/// A hash map with the same API as [`super::HashMap`], storing its pairs in a
+/// single open-addressed table. See the [module docs](self).
+#[derive(Clone)]
+pub struct OpenMap<K, V, S = RandomState> {
+    /// Always empty or a power of two in length. The load factor is below one,
+    /// so there is always an empty slot to end a probe.
+    table: Vec<Option<Slot<K, V>>>,
+    len: usize,
+    max_load_factor: f64,
+    hash_builder: S,
+}
+
+/// How far slot `i` is from the ideal slot for `hash`, allowing for the probe
+/// having wrapped around the end of the table
+fn probe_distance(hash: u64, i: usize, mask: usize) -> usize {
+    i.wrapping_sub(hash as usize) & mask
+}
+
+/// Takes the pair out of slot `i`, then moves each following pair in the
+/// cluster back one slot, stopping at an empty slot or at a pair that is
+/// already in its ideal slot. The table is left exactly as if the pair had
+/// never been inserted.
+fn remove_at<K, V>(table: &mut [Option<Slot<K, V>>], i: usize) -> Slot<K, V> {
+    let mask = table.len() - 1;
+    let removed = table[i].take().expect("slot is occupied");
+    let mut hole = i;
+    loop {
+        let next = (hole + 1) & mask;
+        match &table[next] {
+            Some(slot) if probe_distance(slot.hash, next, mask) > 0 => {
+                table[hole] = table[next].take();
+                hole = next;
+            }
+            _ => return removed,
+        }
+    }
+}
+
+impl<K, V> OpenMap<K, V, RandomState> {
+    /// Constructs a new, empty `OpenMap<K,V>`. No slots are allocated until
+    /// the first insert.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Constructs a new, empty `OpenMap<K,V>` that can hold at least
+    /// `capacity` pairs before it needs to grow.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::OpenMap;
+    ///
+    /// let m = OpenMap::<u32, u32>::with_capacity(100);
+    /// assert!(m.capacity() >= 100);
+    /// assert_eq!(m.len(), 0);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> OpenMap<K, V, S> {
+    /// Constructs a new, empty map which hashes its keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        OpenMap {
+            table: Vec::new(),
+            len: 0,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            hash_builder,
+        }
+    }
+
+    /// Combines [`OpenMap::with_capacity`] and [`OpenMap::with_hasher`].
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes every pair, keeping the table for reuse.
+    pub fn clear(&mut self) {
+        for slot in &mut self.table {
+            *slot = None;
+        }
+        self.len = 0;
+    }
+
+    /// The number of pairs the map can hold before it has to grow
+    pub fn capacity(&self) -> usize {
+        (self.table.len() as f64 * self.max_load_factor) as usize
+    }
+
+    /// The fraction of slots in use above which the map grows
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor
+    }
+
+    /// Changes the maximum load factor, growing straight away if the map is now
+    /// over it. Probe sequences get longer as the table fills up, so this
+    /// trades lookup speed (especially for missing keys) for memory. Lowering
+    /// the load factor never shrinks the table.
+    ///
+    /// ## Panics
+    /// If `max_load_factor` is not strictly between zero and one. An open
+    /// table always needs an empty slot for a probe to stop at.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor < 1.0,
+            "the maximum load factor must be between zero and one"
+        );
+        self.max_load_factor = max_load_factor;
+        self.reserve(0);
+    }
+
+    /// Makes room for at least `additional` more pairs without growing.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            self.resize(self.slots_for(needed).max(self.table.len() * 2));
+        }
+    }
+
+    /// Shrinks the table to the smallest power-of-two number of slots that can
+    /// hold the current pairs. An empty map releases its whole table.
+    pub fn shrink_to_fit(&mut self) {
+        let slots = if self.len == 0 {
+            0
+        } else {
+            self.slots_for(self.len)
+        };
+        if slots < self.table.len() {
+            self.resize(slots);
+        }
+    }
+
+    /// The smallest power-of-two number of slots that keeps `len` pairs under
+    /// the maximum load factor
+    fn slots_for(&self, len: usize) -> usize {
+        let slots = (len as f64 / self.max_load_factor).ceil() as usize;
+        // `ceil` can land exactly on `len`, which would leave no empty slot
+        slots.max(len + 1).max(INITIAL_SLOTS).next_power_of_two()
+    }
+
+    /// Moves every pair into a fresh table of `slots` slots
+    fn resize(&mut self, slots: usize) {
+        let mut table = Vec::with_capacity(slots);
+        table.resize_with(slots, || None);
+        let old = mem::replace(&mut self.table, table);
+        for slot in old.into_iter().flatten() {
+            self.place(slot);
+        }
+    }
+
+    fn mask(&self) -> usize {
+        self.table.len() - 1
+    }
+
+    /// Inserts a pair whose key is known not to be in the map, returning the
+    /// index of the slot it ended up in. The table must have a free slot.
+    fn place(&mut self, slot: Slot<K, V>) -> usize {
+        let mask = self.mask();
+        let mut carry = slot;
+        let mut i = carry.hash as usize & mask;
+        let mut dist = 0;
+        // where the original pair went, once it has been placed or has
+        // displaced a resident and left us carrying that instead
+        let mut placed = None;
+        loop {
+            match &mut self.table[i] {
+                None => {
+                    self.table[i] = Some(carry);
+                    return placed.unwrap_or(i);
+                }
+                Some(resident) => {
+                    let resident_dist = probe_distance(resident.hash, i, mask);
+                    if resident_dist < dist {
+                        mem::swap(resident, &mut carry);
+                        placed.get_or_insert(i);
+                        dist = resident_dist;
+                    }
+                }
+            }
+            i = (i + 1) & mask;
+            dist += 1;
+        }
+    }
+
+    /// The pair in slot `i`, which must be occupied
+    fn slot(&self, i: usize) -> &Slot<K, V> {
+        self.table[i].as_ref().expect("slot is occupied")
+    }
+
+    fn slot_mut(&mut self, i: usize) -> &mut Slot<K, V> {
+        self.table[i].as_mut().expect("slot is occupied")
+    }
+
+    /// Removes the pair in slot `i`, which must be occupied
+    fn remove_index(&mut self, i: usize) -> Slot<K, V> {
+        self.len -= 1;
+        remove_at(&mut self.table, i)
+    }
+}
+
+impl<K, V, S> OpenMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// See [`super::HashMap`] on hashing borrowed forms of a key, and on why
+    /// the hash is mixed.
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        fmix64(self.hash_builder.hash_one(key))
+    }
+
+    /// The slot holding `key`, if it is in the map
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.table.is_empty() {
+            return None;
+        }
+        let mask = self.mask();
+        let mut i = hash as usize & mask;
+        let mut dist = 0;
+        while let Some(slot) = &self.table[i] {
+            // had the key been here, it would have displaced this richer pair
+            if probe_distance(slot.hash, i, mask) < dist {
+                return None;
+            }
+            if slot.hash == hash && slot.key.borrow() == key {
+                return Some(i);
+            }
+            i = (i + 1) & mask;
+            dist += 1;
+        }
+        None
+    }
+
+    /// Inserts `V` at key `K`. If the key was already present, its old value is
+    /// replaced and returned.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::OpenMap;
+    ///
+    /// let mut m = OpenMap::<&str, i32>::new();
+    /// assert_eq!(m.insert("foo", 1), None);
+    /// assert_eq!(m.insert("foo", 2), Some(1));
+    /// assert_eq!(m.get("foo"), Some(&2));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+        if let Some(i) = self.find(hash, &key) {
+            return Some(mem::replace(&mut self.slot_mut(i).value, value));
+        }
+        self.reserve(1);
+        self.place(Slot { hash, key, value });
+        self.len += 1;
+        None
+    }
+
+    /// Gets an optional reference to `V` using key `K`, or any borrowed form of
+    /// it.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(&self.slot(i).value)
+    }
+
+    /// Gets an optional mutable reference to `V` using key `K`, or any borrowed
+    /// form of it.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(&mut self.slot_mut(i).value)
+    }
+
+    /// Returns `true` if the map holds a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(self.hash(key), key).is_some()
+    }
+
+    /// Removes the pair for `key`, or any borrowed form of it, returning the
+    /// value if the key was in the map.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::OpenMap;
+    ///
+    /// let mut m = OpenMap::<&str, i32>::new();
+    /// m.insert("baz", 1);
+    /// assert_eq!(m.remove("baz"), Some(1));
+    /// assert_eq!(m.remove("baz"), None);
+    /// ```
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(self.remove_index(i).value)
+    }
+
+    /// Gets the [`Entry`] for `key`, for in-place insertion or modification.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::OpenMap;
+    ///
+    /// let mut counts = OpenMap::new();
+    /// for word in "a b a c a b".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(counts["a"], 3);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into a single pair of an [`OpenMap`], which may or may not be
+/// present. Created by [`OpenMap::entry`].
+pub enum Entry<'a, K, V, S = RandomState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// An entry for a key that is in the map
+pub struct OccupiedEntry<'a, K, V, S = RandomState> {
+    map: &'a mut OpenMap<K, V, S>,
+    /// The slot holding the pair
+    index: usize,
+}
+
+/// An entry for a key that is not in the map
+pub struct VacantEntry<'a, K, V, S = RandomState> {
+    map: &'a mut OpenMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S> {
+    /// Inserts `default` if the entry is vacant, and returns a mutable
+    /// reference to the value either way.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], but only computes the value when it is needed.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], using `V::default()`.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied, leaving a vacant entry
+    /// untouched.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// The key this entry was created for
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    /// The key already in the map
+    pub fn key(&self) -> &K {
+        &self.map.slot(self.index).key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.slot(self.index).value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.slot_mut(self.index).value
+    }
+
+    /// Converts the entry into a mutable reference that lives as long as the
+    /// borrow of the map
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slot_mut(self.index).value
+    }
+
+    /// Replaces the value, returning the old one. The key is left untouched.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the pair from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the pair from the map, returning both the key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        let slot = self.map.remove_index(self.index);
+        (slot.key, slot.value)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
+    /// The key that would be inserted
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back ownership of the key
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the pair, returning a mutable reference to the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let map = self.map;
+        // only grow once the pair is known to be new
+        map.reserve(1);
+        let i = map.place(Slot {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        map.len += 1;
+        &mut map.slot_mut(i).value
+    }
+}
+
+impl<K, V, S> Default for OpenMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, in iteration order
+impl<K, V, S> Debug for OpenMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal if they hold the same pairs, regardless of their
+/// capacities or the order in which the pairs were inserted
+impl<K, V, S> PartialEq for OpenMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for OpenMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+/// ## Panics
+/// If the key is not in the map. Use [`OpenMap::get`] when that is possible.
+impl<K, Q, V, S> Index<&Q> for OpenMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in OpenMap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    map_conformance!(OpenMap);
+
+    /// Every pair is reachable from its ideal slot without passing an empty
+    /// slot, and probe distances never jump by more than one between
+    /// neighbours (the Robin Hood invariant)
+    fn assert_invariants<K, V, S>(m: &OpenMap<K, V, S>) {
+        let mask = m.table.len().wrapping_sub(1);
+        let mut len = 0;
+        for (i, slot) in m.table.iter().enumerate() {
+            if let Some(slot) = slot {
+                len += 1;
+                let dist = probe_distance(slot.hash, i, mask);
+                for back in 1..=dist {
+                    assert!(
+                        m.table[i.wrapping_sub(back) & mask].is_some(),
+                        "gap in a probe"
+                    );
+                }
+                let next = (i + 1) & mask;
+                if let Some(after) = &m.table[next] {
+                    assert!(probe_distance(after.hash, next, mask) <= dist + 1);
+                }
+            }
+        }
+        assert_eq!(len, m.len());
+        assert!(m.len() < m.table.len() || m.table.is_empty());
+    }
+
+    #[test]
+    fn invariants_hold_through_inserts_and_removes() {
+        let mut m = OpenMap::new();
+        for i in 0..2000u32 {
+            m.insert(i, i);
+            if i % 3 == 0 {
+                m.remove(&(i / 2));
+            }
+        }
+        assert_invariants(&m);
+        m.retain(|k, _| k % 5 != 0);
+        assert_invariants(&m);
+        m.extract_if(|k, _| k % 7 == 0).take(10).for_each(drop);
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn backward_shift_leaves_no_tombstones() {
+        let mut m = OpenMap::with_capacity(100);
+        for i in 0..100u32 {
+            m.insert(i, ());
+        }
+        for i in 0..100u32 {
+            assert_eq!(m.remove(&i), Some(()));
+        }
+        assert!(m.table.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn runs_fuller_than_the_chained_map() {
+        let mut m = OpenMap::new();
+        for i in 0..1000u32 {
+            m.insert(i, ());
+            assert!(m.len() < m.table.len());
+        }
+        assert!(m.capacity() >= 1000);
+        assert_eq!(m.max_load_factor(), DEFAULT_MAX_LOAD_FACTOR);
+    }
+
+    #[test]
+    fn reserve_and_shrink_to_fit() {
+        let mut m = OpenMap::with_capacity(1000);
+        let slots = m.table.len();
+        for i in 0..1000 {
+            m.insert(i, ());
+        }
+        assert_eq!(m.table.len(), slots, "with_capacity should avoid resizes");
+        m.retain(|&k, _| k < 10);
+        m.shrink_to_fit();
+        assert!(m.table.len() < slots);
+        assert_invariants(&m);
+        for i in 0..10 {
+            assert!(m.contains_key(&i));
+        }
+        m.clear();
+        m.shrink_to_fit();
+        assert_eq!(m.capacity(), 0);
+    }
+
+    #[test]
+    fn a_high_load_factor_still_leaves_room() {
+        let mut m = OpenMap::new();
+        m.set_max_load_factor(0.99);
+        for i in 0..8u8 {
+            m.insert(i, ());
+        }
+        assert_invariants(&m);
+        m.set_max_load_factor(0.1);
+        assert!(m.capacity() >= 8);
+        assert_invariants(&m);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_full_load_factor() {
+        OpenMap::<u8, u8>::new().set_max_load_factor(1.0);
+    }
+}
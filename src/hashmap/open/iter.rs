@@ -0,0 +1,472 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Iteration over an [`OpenMap`]. Every iterator walks the table in slot
+//! order, skipping the empty slots. [`SwissMap`](crate::hashmap::SwissMap) and
+//! [`CuckooMap`](crate::hashmap::CuckooMap) lay their pairs out the same way,
+//! so they share all of these but [`ExtractIf`].
+use super::*;
+use std::{
+    iter::{FromIterator, FusedIterator},
+    slice, vec,
+};
+
+impl<K, V, S> OpenMap<K, V, S> {
+    /// An iterator over `(&K, &V)` pairs, in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.table.iter(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over `(&K, &mut V)` pairs, in arbitrary order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.table.iter_mut(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over the keys, in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator over the values, in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator over mutable references to the values, in arbitrary order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Removes every pair from the map, yielding them as an iterator. The table
+    /// is kept, so the map's capacity is unchanged. Pairs that are not
+    /// consumed before the iterator is dropped are still removed.
+    pub fn drain(&mut self) -> Drain<'_, K, V, Self> {
+        let remaining = self.len;
+        Drain::new(self, remaining)
+    }
+
+    /// Keeps only the pairs for which `f` returns `true`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::OpenMap;
+    ///
+    /// let mut m: OpenMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// m.retain(|k, _| k % 2 == 0);
+    /// assert_eq!(m.len(), 5);
+    /// assert!(!m.contains_key(&5));
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Removes and yields the pairs for which `pred` returns `true`, lazily.
+    /// Pairs that are not reached before the iterator is dropped stay in the
+    /// map.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        // A removal shifts the rest of its cluster back by one slot, which
+        // could carry a pair that was already visited past the end of the
+        // walk. Starting at an empty slot or at a pair in its ideal slot means
+        // that no cluster straddles the start, so that can't happen.
+        let mask = self.table.len().wrapping_sub(1);
+        let start = self
+            .table
+            .iter()
+            .enumerate()
+            .position(|(i, slot)| match slot {
+                Some(slot) => probe_distance(slot.hash, i, mask) == 0,
+                None => true,
+            })
+            .unwrap_or(0);
+        ExtractIf {
+            table: &mut self.table,
+            len: &mut self.len,
+            start,
+            visited: 0,
+            pred,
+        }
+    }
+}
+
+/// Created by [`OpenMap::iter`],
+/// [`SwissMap::iter`](crate::hashmap::SwissMap::iter) and
+/// [`CuckooMap::iter`](crate::hashmap::CuckooMap::iter)
+pub struct Iter<'a, K, V> {
+    pub(in crate::hashmap) slots: slice::Iter<'a, Option<Slot<K, V>>>,
+    pub(in crate::hashmap) remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((&slot.key, &slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// Implemented by hand, as deriving it would require `K: Clone` and `V: Clone`
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            slots: self.slots.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+/// Created by [`OpenMap::iter_mut`],
+/// [`SwissMap::iter_mut`](crate::hashmap::SwissMap::iter_mut) and
+/// [`CuckooMap::iter_mut`](crate::hashmap::CuckooMap::iter_mut)
+pub struct IterMut<'a, K, V> {
+    pub(in crate::hashmap) slots: slice::IterMut<'a, Option<Slot<K, V>>>,
+    pub(in crate::hashmap) remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((&slot.key, &mut slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// Created by [`OpenMap::keys`],
+/// [`SwissMap::keys`](crate::hashmap::SwissMap::keys) and
+/// [`CuckooMap::keys`](crate::hashmap::CuckooMap::keys)
+pub struct Keys<'a, K, V> {
+    pub(in crate::hashmap) inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// Created by [`OpenMap::values`],
+/// [`SwissMap::values`](crate::hashmap::SwissMap::values) and
+/// [`CuckooMap::values`](crate::hashmap::CuckooMap::values)
+pub struct Values<'a, K, V> {
+    pub(in crate::hashmap) inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// Created by [`OpenMap::values_mut`],
+/// [`SwissMap::values_mut`](crate::hashmap::SwissMap::values_mut) and
+/// [`CuckooMap::values_mut`](crate::hashmap::CuckooMap::values_mut)
+pub struct ValuesMut<'a, K, V> {
+    pub(in crate::hashmap) inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+/// Created by [`OpenMap::into_iter`](IntoIterator::into_iter),
+/// `SwissMap::into_iter` and `CuckooMap::into_iter`
+pub struct IntoIter<K, V> {
+    pub(in crate::hashmap) slots: vec::IntoIter<Option<Slot<K, V>>>,
+    pub(in crate::hashmap) remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((slot.key, slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// A map whose pairs live in a run of slots, which [`Drain`] takes out of
+/// the map while it drains them. The map is left empty and unallocated in the
+/// meantime, so it stays consistent even if the `Drain` is leaked.
+pub(in crate::hashmap) trait SlotTable<K, V> {
+    /// Takes every slot out of the map, leaving it empty and unallocated.
+    /// Returns the slots, along with whatever else the map needs to know to
+    /// take them back.
+    fn take_slots(&mut self) -> (Vec<Option<Slot<K, V>>>, usize);
+
+    /// Gives the map back the slots that [`SlotTable::take_slots`] took, all
+    /// of them empty now.
+    fn restore_slots(&mut self, slots: Vec<Option<Slot<K, V>>>, shape: usize);
+}
+
+impl<K, V, S> SlotTable<K, V> for OpenMap<K, V, S> {
+    fn take_slots(&mut self) -> (Vec<Option<Slot<K, V>>>, usize) {
+        self.len = 0;
+        (mem::take(&mut self.table), 0)
+    }
+
+    fn restore_slots(&mut self, slots: Vec<Option<Slot<K, V>>>, _: usize) {
+        self.table = slots;
+    }
+}
+
+/// How a [`Drain`] gives a map of type `M` back its slots
+type Restore<K, V, M> = fn(&mut M, Vec<Option<Slot<K, V>>>, usize);
+
+/// Created by [`OpenMap::drain`],
+/// [`SwissMap::drain`](crate::hashmap::SwissMap::drain) and
+/// [`CuckooMap::drain`](crate::hashmap::CuckooMap::drain), where `M` is the
+/// map being drained
+pub struct Drain<'a, K, V, M> {
+    /// The map's slots, out of it for as long as the `Drain` lives
+    slots: Vec<Option<Slot<K, V>>>,
+    /// The next slot to look at
+    index: usize,
+    map: &'a mut M,
+    /// `M`'s [`SlotTable::restore_slots`]. Holding the map as an `M` rather
+    /// than a trait object keeps the `Drain` `Send` and `Sync` whenever the
+    /// map is, without having to bound the public type by a private trait.
+    restore: Restore<K, V, M>,
+    /// Passed back to [`SlotTable::restore_slots`]
+    shape: usize,
+    remaining: usize,
+}
+
+impl<'a, K, V, M> Drain<'a, K, V, M> {
+    /// Drains the `len` pairs of `map`.
+    pub(in crate::hashmap) fn new(map: &'a mut M, len: usize) -> Self
+    where
+        M: SlotTable<K, V>,
+    {
+        let (slots, shape) = map.take_slots();
+        Drain {
+            slots,
+            index: 0,
+            map,
+            restore: M::restore_slots,
+            shape,
+            remaining: len,
+        }
+    }
+}
+
+impl<K, V, M> Iterator for Drain<'_, K, V, M> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(slot) = self.slots.get_mut(self.index) {
+            self.index += 1;
+            if let Some(slot) = slot.take() {
+                self.remaining -= 1;
+                return Some((slot.key, slot.value));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, M> ExactSizeIterator for Drain<'_, K, V, M> {}
+impl<K, V, M> FusedIterator for Drain<'_, K, V, M> {}
+
+/// Removes whatever wasn't yielded, and gives the emptied slots back to the
+/// map
+impl<K, V, M> Drop for Drain<'_, K, V, M> {
+    fn drop(&mut self) {
+        for slot in &mut self.slots[self.index..] {
+            *slot = None;
+        }
+        (self.restore)(self.map, mem::take(&mut self.slots), self.shape);
+    }
+}
+
+/// Created by [`OpenMap::extract_if`]
+pub struct ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    table: &'a mut Vec<Option<Slot<K, V>>>,
+    /// The map's length, kept up to date as pairs are removed
+    len: &'a mut usize,
+    /// The slot the walk started from
+    start: usize,
+    /// How many slots, counting from `start`, have been tested so far
+    visited: usize,
+    pred: F,
+}
+
+impl<K, V, F> Iterator for ExtractIf<'_, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.visited < self.table.len() {
+            let i = (self.start + self.visited) & (self.table.len() - 1);
+            if let Some(slot) = &mut self.table[i] {
+                if (self.pred)(&slot.key, &mut slot.value) {
+                    // the next pair in the cluster shifts back into `i`, so
+                    // don't advance
+                    let slot = remove_at(self.table, i);
+                    *self.len -= 1;
+                    return Some((slot.key, slot.value));
+                }
+            }
+            self.visited += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(*self.len))
+    }
+}
+
+impl<K, V, F> FusedIterator for ExtractIf<'_, K, V, F> where F: FnMut(&K, &mut V) -> bool {}
+
+impl<K, V, S> IntoIterator for OpenMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            slots: self.table.into_iter(),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a OpenMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut OpenMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for OpenMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = OpenMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for OpenMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S> Extend<(&'a K, &'a V)> for OpenMap<K, V, S>
+where
+    K: Eq + Hash + Copy,
+    V: Copy,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
@@ -0,0 +1,497 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [`serde`][1] support, behind the `serde` feature.
+//!
+//! Every map serializes as a map and [`HashSet`] as a sequence, just like
+//! `std`'s collections, so each can be read back as any of the others (or as
+//! a `std` collection). A [`MultiMap`] is a map from each key to a sequence
+//! of its values, and a [`BiMap`] a map from left to right values. Only
+//! [`IndexMap`] keeps its order through a round trip. The hasher is never
+//! serialized: deserializing builds a fresh `S::default()`, so a keyed hasher
+//! gets a new key.
+//!
+//! Deserializing pre-sizes the collection from the format's length hint,
+//! when it gives one. The hint comes from the input, so it is capped, lest a
+//! few bytes claiming a billion entries allocate gigabytes up front. If the
+//! input repeats a key, the last value wins.
+//!
+//! [1]: https://serde.rs
+use super::{
+    concurrent, hamt::SharedPointer, BiMap, ConcurrentHashMap, CuckooMap, Hamt, HashMap, HashSet,
+    IndexMap, MultiMap, OpenMap, SwissMap,
+};
+use serde::{
+    de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor},
+    ser::{Serialize, SerializeMap, Serializer},
+};
+use std::{
+    fmt,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    mem,
+};
+
+/// The most memory to allocate on the word of a length hint, as `serde` does
+/// for `std`'s collections
+const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+
+/// The capacity to pre-size for `hint` elements of type `T`
+fn cautious<T>(hint: Option<usize>) -> usize {
+    hint.unwrap_or(0)
+        .min(MAX_PREALLOC_BYTES / mem::size_of::<T>().max(1))
+}
+
+/// `Serialize` and `Deserialize` for maps that share `HashMap`'s API
+macro_rules! impl_serde_map {
+    ($($map:ident),*) => {$(
+        impl<K, V, S> Serialize for $map<K, V, S>
+        where
+            K: Serialize,
+            V: Serialize,
+        {
+            fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+                serializer.collect_map(self)
+            }
+        }
+
+        impl<'de, K, V, S> Deserialize<'de> for $map<K, V, S>
+        where
+            K: Deserialize<'de> + Eq + Hash,
+            V: Deserialize<'de>,
+            S: BuildHasher + Default,
+        {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct MapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+                impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+                where
+                    K: Deserialize<'de> + Eq + Hash,
+                    V: Deserialize<'de>,
+                    S: BuildHasher + Default,
+                {
+                    type Value = $map<K, V, S>;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str("a map")
+                    }
+
+                    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+                    where
+                        A: MapAccess<'de>,
+                    {
+                        let capacity = cautious::<(K, V)>(access.size_hint());
+                        let mut map = $map::with_capacity_and_hasher(capacity, S::default());
+                        while let Some((key, value)) = access.next_entry()? {
+                            map.insert(key, value);
+                        }
+                        Ok(map)
+                    }
+                }
+
+                deserializer.deserialize_map(MapVisitor(PhantomData))
+            }
+        }
+    )*};
+}
+
+impl_serde_map!(HashMap, OpenMap, SwissMap, CuckooMap, IndexMap);
+
+impl<T, S> Serialize for HashSet<T, S>
+where
+    T: Serialize,
+{
+    fn serialize<R: Serializer>(&self, serializer: R) -> Result<R::Ok, R::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+impl<'de, T, S> Deserialize<'de> for HashSet<T, S>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SetVisitor<T, S>(PhantomData<(T, S)>);
+
+        impl<'de, T, S> Visitor<'de> for SetVisitor<T, S>
+        where
+            T: Deserialize<'de> + Eq + Hash,
+            S: BuildHasher + Default,
+        {
+            type Value = HashSet<T, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let capacity = cautious::<T>(access.size_hint());
+                let mut set = HashSet::with_capacity_and_hasher(capacity, S::default());
+                while let Some(value) = access.next_element()? {
+                    set.insert(value);
+                }
+                Ok(set)
+            }
+        }
+
+        deserializer.deserialize_seq(SetVisitor(PhantomData))
+    }
+}
+
+impl<K, V, P, S> Serialize for Hamt<K, V, P, S>
+where
+    K: Serialize,
+    V: Serialize,
+    P: SharedPointer,
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_map(self)
+    }
+}
+
+/// A `Hamt` has nothing to pre-size: it is built up one path copy at a time.
+impl<'de, K, V, P, S> Deserialize<'de> for Hamt<K, V, P, S>
+where
+    K: Deserialize<'de> + Eq + Hash + Clone,
+    V: Deserialize<'de> + Clone,
+    P: SharedPointer,
+    S: BuildHasher + Default + Clone,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HamtVisitor<K, V, P, S>(PhantomData<(K, V, P, S)>);
+
+        impl<'de, K, V, P, S> Visitor<'de> for HamtVisitor<K, V, P, S>
+        where
+            K: Deserialize<'de> + Eq + Hash + Clone,
+            V: Deserialize<'de> + Clone,
+            P: SharedPointer,
+            S: BuildHasher + Default + Clone,
+        {
+            type Value = Hamt<K, V, P, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let mut map = Hamt::with_hasher(S::default());
+                while let Some((key, value)) = access.next_entry()? {
+                    map = map.insert(key, value);
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(HamtVisitor(PhantomData))
+    }
+}
+
+/// Holds every shard's read lock while serializing, so the result is a
+/// consistent snapshot: writers wait until it is done.
+impl<K, V, S> Serialize for ConcurrentHashMap<K, V, S>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        let shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| concurrent::read(shard))
+            .collect();
+        let len = shards.iter().map(|shard| shard.len()).sum();
+        let mut map = serializer.serialize_map(Some(len))?;
+        for (key, value) in shards.iter().flat_map(|shard| shard.iter()) {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Uses the default number of shards. The shards only grow as pairs are
+/// inserted, since a length hint says nothing about how the keys will spread
+/// over them.
+impl<'de, K, V, S> Deserialize<'de> for ConcurrentHashMap<K, V, S>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default + Clone,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+        impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+        where
+            K: Deserialize<'de> + Eq + Hash,
+            V: Deserialize<'de>,
+            S: BuildHasher + Default + Clone,
+        {
+            type Value = ConcurrentHashMap<K, V, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let map = ConcurrentHashMap::with_hasher(S::default());
+                while let Some((key, value)) = access.next_entry()? {
+                    map.insert(key, value);
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+/// Each key maps to a sequence of its values, as in a `HashMap<K, Vec<V>>`.
+impl<K, V, S> Serialize for MultiMap<K, V, S>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_map(self.iter_all())
+    }
+}
+
+/// A key that turns up twice keeps the values of both, and a key with no
+/// values is left out.
+impl<'de, K, V, S> Deserialize<'de> for MultiMap<K, V, S>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MultiMapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+        impl<'de, K, V, S> Visitor<'de> for MultiMapVisitor<K, V, S>
+        where
+            K: Deserialize<'de> + Eq + Hash,
+            V: Deserialize<'de>,
+            S: BuildHasher + Default,
+        {
+            type Value = MultiMap<K, V, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of sequences")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let capacity = cautious::<(K, Vec<V>)>(access.size_hint());
+                let mut map = MultiMap {
+                    map: HashMap::with_capacity_and_hasher(capacity, S::default()),
+                    len: 0,
+                };
+                while let Some((key, values)) = access.next_entry::<K, Vec<V>>()? {
+                    if !values.is_empty() {
+                        map.len += values.len();
+                        map.map.entry(key).or_default().extend(values);
+                    }
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(MultiMapVisitor(PhantomData))
+    }
+}
+
+impl<L, R, S> Serialize for BiMap<L, R, S>
+where
+    L: Serialize,
+    R: Serialize,
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_map(self)
+    }
+}
+
+/// Pairs are inserted in order with [`BiMap::insert`], so if two share a
+/// value, the later one wins.
+impl<'de, L, R, S> Deserialize<'de> for BiMap<L, R, S>
+where
+    L: Deserialize<'de> + Eq + Hash + Clone,
+    R: Deserialize<'de> + Eq + Hash + Clone,
+    S: BuildHasher + Default + Clone,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BiMapVisitor<L, R, S>(PhantomData<(L, R, S)>);
+
+        impl<'de, L, R, S> Visitor<'de> for BiMapVisitor<L, R, S>
+        where
+            L: Deserialize<'de> + Eq + Hash + Clone,
+            R: Deserialize<'de> + Eq + Hash + Clone,
+            S: BuildHasher + Default + Clone,
+        {
+            type Value = BiMap<L, R, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let mut map = BiMap::with_hasher(S::default());
+                while let Some((left, right)) = access.next_entry()? {
+                    map.insert(left, right);
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(BiMapVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::FxBuildHasher;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// Serializes to JSON and back
+    fn round_trip<T>(value: &T) -> T
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        let json = serde_json::to_string(value).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    /// The pairs in key order, to compare maps whose order is arbitrary
+    fn sorted<'a, I>(pairs: I) -> Vec<(u32, String)>
+    where
+        I: IntoIterator<Item = (&'a u32, &'a String)>,
+    {
+        let sorted: BTreeMap<_, _> = pairs.into_iter().map(|(k, v)| (*k, v.clone())).collect();
+        sorted.into_iter().collect()
+    }
+
+    fn pairs() -> impl Iterator<Item = (u32, String)> {
+        (0..500).map(|i| (i, format!("value {}", i)))
+    }
+
+    #[test]
+    fn maps_round_trip() {
+        let m: HashMap<u32, String> = pairs().collect();
+        assert_eq!(round_trip(&m), m);
+        let m: OpenMap<u32, String> = pairs().collect();
+        assert_eq!(round_trip(&m), m);
+        let m: SwissMap<u32, String> = pairs().collect();
+        assert_eq!(round_trip(&m), m);
+        let m: CuckooMap<u32, String> = pairs().collect();
+        assert_eq!(round_trip(&m), m);
+        let m: Hamt<u32, String> = pairs().collect();
+        assert_eq!(round_trip(&m), m);
+        let m: HashMap<u32, String, FxBuildHasher> = pairs().collect();
+        assert_eq!(round_trip(&m), m);
+    }
+
+    #[test]
+    fn index_map_keeps_its_order() {
+        let mut m: IndexMap<u32, String> = pairs().collect();
+        m.sort_by(&crate::sort::Quick, |k1, _, k2, _| k2.cmp(k1));
+        let back = round_trip(&m);
+        assert!(back.iter().eq(m.iter()));
+    }
+
+    #[test]
+    fn concurrent_map_round_trips() {
+        let m: ConcurrentHashMap<u32, String> = ConcurrentHashMap::with_shards(4);
+        for (k, v) in pairs() {
+            m.insert(k, v);
+        }
+        let back: ConcurrentHashMap<u32, String> = round_trip(&m);
+        assert_eq!(back.len(), 500);
+        for (k, v) in pairs() {
+            assert_eq!(back.get_cloned(&k), Some(v));
+        }
+    }
+
+    #[test]
+    fn multimaps_round_trip() {
+        let m: MultiMap<u32, String> = pairs().map(|(k, v)| (k % 7, v)).collect();
+        assert_eq!(round_trip(&m), m);
+        let json = r#"{"a":[1,2],"b":[],"a":[3]}"#;
+        let m: MultiMap<String, i32> = serde_json::from_str(json).unwrap();
+        assert_eq!(m.get_all("a"), [1, 2, 3]);
+        assert!(!m.contains_key("b"));
+        assert_eq!(m.len(), 3);
+    }
+
+    #[test]
+    fn bimaps_round_trip() {
+        let m: BiMap<u32, String> = pairs().collect();
+        let back = round_trip(&m);
+        assert_eq!(back, m);
+        assert_eq!(back.get_by_right("value 7"), Some(&7));
+        let m: BiMap<String, i32> = serde_json::from_str(r#"{"a":1,"b":1}"#).unwrap();
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get_by_right(&1).map(String::as_str), Some("b"));
+    }
+
+    #[test]
+    fn sets_round_trip() {
+        let s: HashSet<String> = (0..100).map(|i| i.to_string()).collect();
+        assert_eq!(round_trip(&s), s);
+    }
+
+    #[test]
+    fn maps_read_each_others_output() {
+        let m: SwissMap<u32, String> = pairs().collect();
+        let json = serde_json::to_string(&m).unwrap();
+        let std: std::collections::HashMap<u32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(sorted(&std), sorted(&m));
+        let index: IndexMap<u32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(sorted(&index), sorted(&m));
+
+        let s: HashSet<u8> = (0..10).collect();
+        let json = serde_json::to_string(&s).unwrap();
+        let std: BTreeSet<u8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(std, (0..10).collect());
+    }
+
+    #[test]
+    fn json_has_the_expected_shape() {
+        let mut m = IndexMap::<String, i32>::new();
+        m.insert("b".to_string(), 2);
+        m.insert("a".to_string(), 1);
+        assert_eq!(serde_json::to_string(&m).unwrap(), r#"{"b":2,"a":1}"#);
+        let empty: HashMap<String, i32> = serde_json::from_str("{}").unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn the_last_duplicate_key_wins() {
+        let m: HashMap<String, i32> = serde_json::from_str(r#"{"a":1,"b":2,"a":3}"#).unwrap();
+        assert_eq!(m.len(), 2);
+        assert_eq!(m["a"], 3);
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        assert!(serde_json::from_str::<HashMap<String, i32>>(r#"{"a":"one"}"#).is_err());
+        assert!(serde_json::from_str::<HashMap<String, i32>>("[1, 2]").is_err());
+        assert!(serde_json::from_str::<HashSet<i32>>(r#"{"a":1}"#).is_err());
+    }
+
+    #[test]
+    fn length_hints_are_capped() {
+        assert_eq!(cautious::<u64>(Some(10)), 10);
+        assert_eq!(cautious::<u64>(None), 0);
+        assert_eq!(cautious::<u64>(Some(usize::MAX)), 128 * 1024);
+        assert_eq!(cautious::<()>(Some(usize::MAX)), MAX_PREALLOC_BYTES);
+    }
+}
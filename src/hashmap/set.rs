@@ -0,0 +1,666 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A hash set, which is just a [`HashMap`] whose values are all `()`.
+//!
+//! The set operations come in two flavours. [`HashSet::union`] and friends
+//! borrow both sets and return lazy iterators, so nothing is allocated or
+//! cloned until the result is consumed. The operators (`|`, `&`, `-` and `^`)
+//! take two `&HashSet`s and collect the result into a new set of clones:
+//!
+//! ```
+//! use tinystd::hashmap::HashSet;
+//!
+//! let a: HashSet<_> = vec![1, 2, 3].into_iter().collect();
+//! let b: HashSet<_> = vec![2, 3, 4].into_iter().collect();
+//!
+//! let mut both: Vec<_> = a.intersection(&b).collect();
+//! both.sort();
+//! assert_eq!(both, vec![&2, &3]);
+//!
+//! let either = &a ^ &b;
+//! assert!(either.contains(&1) && either.contains(&4));
+//! assert_eq!(either.len(), 2);
+//! ```
+use super::HashMap;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    iter::{Chain, FromIterator, FusedIterator},
+    ops::{BitAnd, BitOr, BitXor, Sub},
+};
+
+/// A set of unique values, stored as the keys of a [`HashMap`]. See the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct HashSet<T, S = RandomState> {
+    map: HashMap<T, (), S>,
+}
+
+impl<T> HashSet<T, RandomState> {
+    /// Constructs a new, empty `HashSet<T>`. Nothing is allocated until the
+    /// first insert.
+    pub fn new() -> Self {
+        HashSet {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Constructs a new, empty `HashSet<T>` that can hold at least `capacity`
+    /// values before it needs to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashSet {
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+}
+
+impl<T, S> HashSet<T, S> {
+    /// Constructs a new, empty set which hashes its values with
+    /// `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashSet {
+            map: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    /// Combines [`HashSet::with_capacity`] and [`HashSet::with_hasher`].
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        HashSet {
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+        }
+    }
+
+    /// The set's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// The number of values in the set
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes every value, keeping the allocation for reuse.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// The number of values the set can hold before it has to grow
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Makes room for at least `additional` more values without growing.
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
+
+    /// Shrinks the set's storage to fit its current values.
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// An iterator over the values, in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.map.keys(),
+        }
+    }
+
+    /// Removes every value from the set, yielding them as an iterator.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain {
+            inner: self.map.drain(),
+        }
+    }
+
+    /// Keeps only the values for which `f` returns `true`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.map.retain(|t, _| f(t));
+    }
+}
+
+impl<T, S> HashSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Adds a value to the set, returning `true` if it was not already there.
+    /// If it was, the set is left unchanged (the value already in the set is
+    /// kept, and `value` is dropped).
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashSet;
+    ///
+    /// let mut s = HashSet::new();
+    /// assert!(s.insert("a"));
+    /// assert!(!s.insert("a"));
+    /// assert_eq!(s.len(), 1);
+    /// ```
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.insert(value, ()).is_none()
+    }
+
+    /// Returns `true` if the set holds `value`, or any borrowed form of it.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Removes `value`, returning `true` if it was in the set.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// The values in `self` or `other`, each yielded once, lazily.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::HashSet;
+    ///
+    /// let a: HashSet<_> = (0..5).collect();
+    /// let b: HashSet<_> = (3..8).collect();
+    /// let mut all: Vec<_> = a.union(&b).copied().collect();
+    /// all.sort();
+    /// assert_eq!(all, (0..8).collect::<Vec<_>>());
+    /// ```
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, S> {
+        // walk the bigger set in full, and the smaller one checking for repeats
+        let (big, small) = if self.len() >= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Union {
+            inner: big.iter().chain(small.difference(big)),
+        }
+    }
+
+    /// The values in both `self` and `other`, lazily.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, S> {
+        // walk the smaller set, testing each value against the bigger one
+        let (small, big) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Intersection {
+            iter: small.iter(),
+            other: big,
+        }
+    }
+
+    /// The values in `self` but not in `other`, lazily.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, S> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// The values in exactly one of `self` and `other`, lazily.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, S> {
+        SymmetricDifference {
+            inner: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    /// Returns `true` if every value in `self` is also in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|t| other.contains(t))
+    }
+
+    /// Returns `true` if every value in `other` is also in `self`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns `true` if `self` and `other` have no values in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).next().is_none()
+    }
+}
+
+/// Created by [`HashSet::iter`]
+pub struct Iter<'a, T> {
+    inner: super::Keys<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// Created by [`HashSet::into_iter`](IntoIterator::into_iter)
+pub struct IntoIter<T> {
+    inner: super::IntoIter<T, ()>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(t, ())| t)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+impl<T> FusedIterator for IntoIter<T> {}
+
+/// Created by [`HashSet::drain`]
+pub struct Drain<'a, T> {
+    inner: super::Drain<'a, T, ()>,
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(t, ())| t)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Drain<'_, T> {}
+impl<T> FusedIterator for Drain<'_, T> {}
+
+/// Created by [`HashSet::union`]
+pub struct Union<'a, T, S> {
+    inner: Chain<Iter<'a, T>, Difference<'a, T, S>>,
+}
+
+impl<'a, T, S> Iterator for Union<'a, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T, S> FusedIterator for Union<'_, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+}
+
+/// Created by [`HashSet::intersection`]
+pub struct Intersection<'a, T, S> {
+    iter: Iter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+impl<'a, T, S> Iterator for Intersection<'a, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|t| other.contains(*t))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T, S> FusedIterator for Intersection<'_, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+}
+
+/// Created by [`HashSet::difference`]
+pub struct Difference<'a, T, S> {
+    iter: Iter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+impl<'a, T, S> Iterator for Difference<'a, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|t| !other.contains(*t))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T, S> FusedIterator for Difference<'_, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+}
+
+/// Created by [`HashSet::symmetric_difference`]
+pub struct SymmetricDifference<'a, T, S> {
+    inner: Chain<Difference<'a, T, S>, Difference<'a, T, S>>,
+}
+
+impl<'a, T, S> Iterator for SymmetricDifference<'a, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T, S> FusedIterator for SymmetricDifference<'_, T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+}
+
+impl<T, S> IntoIterator for HashSet<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.map.into_iter(),
+        }
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a HashSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, S> FromIterator<T> for HashSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        HashSet {
+            map: iter.into_iter().map(|t| (t, ())).collect(),
+        }
+    }
+}
+
+impl<T, S> Extend<T> for HashSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|t| (t, ())));
+    }
+}
+
+impl<'a, T, S> Extend<&'a T> for HashSet<T, S>
+where
+    T: Eq + Hash + Copy,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T, S> Default for HashSet<T, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        HashSet {
+            map: HashMap::default(),
+        }
+    }
+}
+
+/// Prints as a set, e.g. `{1, 2}`, in iteration order
+impl<T, S> Debug for HashSet<T, S>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S> PartialEq for HashSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T, S> Eq for HashSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+}
+
+/// Generates an operator that collects one of the lazy set operations into a
+/// new set
+macro_rules! set_operator {
+    ($trait:ident, $method:ident, $op:ident, $doc:expr) => {
+        #[doc = $doc]
+        impl<T, S> $trait<&HashSet<T, S>> for &HashSet<T, S>
+        where
+            T: Eq + Hash + Clone,
+            S: BuildHasher + Default,
+        {
+            type Output = HashSet<T, S>;
+
+            fn $method(self, rhs: &HashSet<T, S>) -> HashSet<T, S> {
+                self.$op(rhs).cloned().collect()
+            }
+        }
+    };
+}
+
+set_operator!(
+    BitOr,
+    bitor,
+    union,
+    "`&a | &b` is a new set of the values in `a` or `b`"
+);
+set_operator!(
+    BitAnd,
+    bitand,
+    intersection,
+    "`&a & &b` is a new set of the values in both `a` and `b`"
+);
+set_operator!(
+    Sub,
+    sub,
+    difference,
+    "`&a - &b` is a new set of the values in `a` but not in `b`"
+);
+set_operator!(
+    BitXor,
+    bitxor,
+    symmetric_difference,
+    "`&a ^ &b` is a new set of the values in exactly one of `a` and `b`"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(values: &[u32]) -> HashSet<u32> {
+        values.iter().copied().collect()
+    }
+
+    fn sorted<'a>(iter: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut v: Vec<_> = iter.copied().collect();
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn insert_contains_remove() {
+        let mut s = HashSet::new();
+        assert!(s.insert("a".to_string()));
+        assert!(!s.insert("a".to_string()));
+        assert!(s.contains("a"));
+        assert!(!s.contains("b"));
+        assert_eq!(s.len(), 1);
+        assert!(s.remove("a"));
+        assert!(!s.remove("a"));
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn lazy_set_operations() {
+        let a = set(&[1, 2, 3, 4]);
+        let b = set(&[3, 4, 5]);
+        assert_eq!(sorted(a.union(&b)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(sorted(b.union(&a)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(sorted(a.intersection(&b)), vec![3, 4]);
+        assert_eq!(sorted(b.intersection(&a)), vec![3, 4]);
+        assert_eq!(sorted(a.difference(&b)), vec![1, 2]);
+        assert_eq!(sorted(b.difference(&a)), vec![5]);
+        assert_eq!(sorted(a.symmetric_difference(&b)), vec![1, 2, 5]);
+
+        let empty = set(&[]);
+        assert_eq!(sorted(a.union(&empty)), vec![1, 2, 3, 4]);
+        assert_eq!(a.intersection(&empty).count(), 0);
+        assert_eq!(empty.difference(&a).count(), 0);
+    }
+
+    #[test]
+    fn operators_build_new_sets() {
+        let a = set(&[1, 2, 3]);
+        let b = set(&[2, 3, 4]);
+        assert_eq!(&a | &b, set(&[1, 2, 3, 4]));
+        assert_eq!(&a & &b, set(&[2, 3]));
+        assert_eq!(&a - &b, set(&[1]));
+        assert_eq!(&a ^ &b, set(&[1, 4]));
+        // the operands are only borrowed
+        assert_eq!(a.len(), 3);
+        assert_eq!(b.len(), 3);
+    }
+
+    #[test]
+    fn subset_superset_disjoint() {
+        let small = set(&[1, 2]);
+        let big = set(&[1, 2, 3]);
+        let other = set(&[7, 8]);
+        assert!(small.is_subset(&big));
+        assert!(!big.is_subset(&small));
+        assert!(big.is_superset(&small));
+        assert!(small.is_subset(&small));
+        assert!(set(&[]).is_subset(&small));
+        assert!(small.is_disjoint(&other));
+        assert!(!small.is_disjoint(&big));
+    }
+
+    #[test]
+    fn iteration_drain_and_retain() {
+        let mut s: HashSet<u32> = (0..20).collect();
+        assert_eq!(s.iter().len(), 20);
+        s.retain(|&t| t % 2 == 0);
+        assert_eq!(sorted(s.iter()), (0..20).step_by(2).collect::<Vec<_>>());
+        s.extend(&[100, 102]);
+        assert_eq!(s.len(), 12);
+        let mut drained: Vec<_> = s.drain().collect();
+        drained.sort_unstable();
+        assert_eq!(drained.len(), 12);
+        assert!(s.is_empty());
+        s.extend(drained);
+        let mut owned: Vec<_> = s.into_iter().collect();
+        owned.sort_unstable();
+        assert_eq!(owned[11], 102);
+    }
+
+    #[test]
+    fn debug_prints_as_a_set() {
+        assert_eq!(format!("{:?}", set(&[])), "{}");
+        assert_eq!(format!("{:?}", set(&[7])), "{7}");
+    }
+
+    #[test]
+    fn custom_hashers() {
+        use crate::hash::FxBuildHasher;
+        let a: HashSet<u64, FxBuildHasher> = (0..100).collect();
+        let b: HashSet<u64, FxBuildHasher> = (50..150).collect();
+        let both = &a & &b;
+        assert_eq!(both.len(), 50);
+        assert!(both.is_subset(&a) && both.is_subset(&b));
+    }
+}
@@ -0,0 +1,662 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A binary snapshot format for a [`HashMap`] of fixed-size keys and values,
+//! written by [`HashMap::save_to`] and read back by [`HashMap::load_from`].
+//!
+//! Every number is little-endian:
+//!
+//! | bytes       | contents                                               |
+//! |-------------|--------------------------------------------------------|
+//! | 8           | the magic bytes `tinyhmap`                             |
+//! | 4           | the format version, currently 1                        |
+//! | 4, 4        | the size of a key and of a value, in bytes             |
+//! | 8           | the map's maximum load factor, as the bits of an `f64` |
+//! | 8, 8        | the hasher's seed                                      |
+//! | 8           | the number of pairs, `n`                               |
+//! | 8           | the header checksum, of all the bytes above            |
+//! | n × (k + v) | each pair, its key and then its value                  |
+//! | 8           | the checksum of the pairs                              |
+//!
+//! The checksums are 64-bit [FNV-1a][1]. Every step of FNV-1a is a bijection
+//! on its state, so a change to any single byte always changes the checksum;
+//! wider damage goes unnoticed with probability 2⁻⁶⁴. The header has a
+//! checksum of its own so that a damaged pair count is caught before it is
+//! used to size the map. Neither is a defense against deliberate tampering:
+//! anyone can compute an FNV-1a hash. So the header is never trusted to
+//! allocate much up front either: the map is pre-sized for at most
+//! [`MAX_PREALLOC_BINS`] bins, and grows as pairs are actually read.
+//!
+//! Loading rehashes every key, so it is the cost of `n` inserts into a map
+//! that rarely has to grow. With the hasher's seed restored, the loaded map
+//! hashes exactly like the saved one did. That takes a [`SeededHasher`]:
+//! [`SipState`] and the [`BuildHasherDefault`]s, like
+//! [`crate::hash::FxBuildHasher`]. The default `std` `RandomState` can be
+//! snapshotted too, but its keys can't be read, so a map that uses it comes
+//! back with fresh random keys: the same pairs, in a different order.
+//!
+//! [1]: http://www.isthe.com/chongo/tech/comp/fnv/
+use super::{HashMap, LOAD_FACTORS};
+use crate::hash::{Fnv1aHasher, SipState};
+use std::{
+    collections::hash_map::RandomState,
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
+    io::{self, Read, Write},
+};
+
+/// The first bytes of every snapshot
+const MAGIC: [u8; 8] = *b"tinyhmap";
+
+/// The version of the format that this module reads and writes
+pub const VERSION: u32 = 1;
+
+/// The most bins to allocate on the word of a snapshot's header
+pub const MAX_PREALLOC_BINS: usize = 1 << 20;
+
+/// A key or value type with a fixed-size binary encoding.
+///
+/// Implemented for the primitive integers and for byte arrays.
+pub trait Record: Sized {
+    /// The size of the encoding, in bytes
+    const SIZE: usize;
+
+    /// Writes the encoding of `self` into `buf`, which is `SIZE` bytes long.
+    fn encode(&self, buf: &mut [u8]);
+
+    /// Reads a value back from the `SIZE` bytes that [`Record::encode`] wrote.
+    fn decode(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_record {
+    ($($int:ty),*) => {$(
+        impl Record for $int {
+            const SIZE: usize = std::mem::size_of::<$int>();
+
+            fn encode(&self, buf: &mut [u8]) {
+                buf.copy_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(buf: &[u8]) -> Self {
+                let mut bytes = [0; std::mem::size_of::<$int>()];
+                bytes.copy_from_slice(buf);
+                <$int>::from_le_bytes(bytes)
+            }
+        }
+    )*};
+}
+
+impl_record!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl<const N: usize> Record for [u8; N] {
+    const SIZE: usize = N;
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(buf);
+        bytes
+    }
+}
+
+/// A [`BuildHasher`] that can be saved as a seed and rebuilt from it.
+pub trait SeededHasher: BuildHasher + Sized {
+    /// The state that determines how keys are hashed
+    fn seed(&self) -> [u64; 2];
+
+    /// Rebuilds the hasher that [`SeededHasher::seed`] came from.
+    fn from_seed(seed: [u64; 2]) -> Self;
+}
+
+/// The seed is the SipHash key.
+impl SeededHasher for SipState {
+    fn seed(&self) -> [u64; 2] {
+        let (k0, k1) = self.keys();
+        [k0, k1]
+    }
+
+    fn from_seed([k0, k1]: [u64; 2]) -> Self {
+        SipState::with_keys(k0, k1)
+    }
+}
+
+/// Unseeded hashers, like [`crate::hash::FxBuildHasher`], save a zero seed.
+impl<H: Hasher + Default> SeededHasher for BuildHasherDefault<H> {
+    fn seed(&self) -> [u64; 2] {
+        [0, 0]
+    }
+
+    fn from_seed(_: [u64; 2]) -> Self {
+        BuildHasherDefault::default()
+    }
+}
+
+/// `std`'s keys are private, so this saves a zero seed and loads with a fresh
+/// [`RandomState::new`]. Every key is rehashed on load anyway, so the loaded map
+/// holds the same pairs, only not in the same order.
+impl SeededHasher for RandomState {
+    fn seed(&self) -> [u64; 2] {
+        [0, 0]
+    }
+
+    fn from_seed(_: [u64; 2]) -> Self {
+        RandomState::new()
+    }
+}
+
+/// Why a snapshot could not be loaded (or saved)
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The reader or writer failed.
+    Io(io::Error),
+    /// The input ended before the snapshot did.
+    Truncated,
+    /// The input doesn't start with the magic bytes, so isn't a snapshot.
+    NotASnapshot,
+    /// The snapshot was written by a different version of the format.
+    UnsupportedVersion(u32),
+    /// The snapshot's keys or values are a different size than the map's, so
+    /// it must be of a map of different types.
+    RecordSize {
+        /// The sizes of the map's keys and values
+        expected: (usize, usize),
+        /// The sizes of the snapshot's keys and values
+        found: (usize, usize),
+    },
+    /// The header doesn't match its checksum, or makes no sense.
+    CorruptHeader,
+    /// The pairs don't match their checksum, or hold the same key twice.
+    CorruptEntries,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O failed: {}", e),
+            SnapshotError::Truncated => f.write_str("the snapshot is truncated"),
+            SnapshotError::NotASnapshot => f.write_str("not a hash map snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "unsupported snapshot version {}", v)
+            }
+            SnapshotError::RecordSize { expected, found } => write!(
+                f,
+                "expected {}-byte keys and {}-byte values, found {} and {}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            SnapshotError::CorruptHeader => f.write_str("the snapshot's header is corrupt"),
+            SnapshotError::CorruptEntries => f.write_str("the snapshot's entries are corrupt"),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Running out of input is reported as [`SnapshotError::Truncated`] rather
+/// than as I/O failure.
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            SnapshotError::Truncated
+        } else {
+            SnapshotError::Io(e)
+        }
+    }
+}
+
+/// Passes bytes through to or from `inner`, keeping a checksum of them
+struct Checksummed<T> {
+    inner: T,
+    checksum: Fnv1aHasher,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            checksum: Fnv1aHasher::default(),
+        }
+    }
+
+    /// The checksum of the bytes so far, restarting it for the bytes to come
+    fn take_checksum(&mut self) -> u64 {
+        std::mem::take(&mut self.checksum).finish()
+    }
+}
+
+impl<W: Write> Checksummed<W> {
+    /// Writes the checksum of the bytes so far, itself unchecksummed.
+    fn write_checksum(&mut self) -> io::Result<()> {
+        let checksum = self.take_checksum();
+        self.inner.write_all(&checksum.to_le_bytes())
+    }
+}
+
+impl<R: Read> Checksummed<R> {
+    /// Reads a checksum, itself unchecksummed, and compares it to that of the
+    /// bytes so far.
+    fn checksum_matches(&mut self) -> io::Result<bool> {
+        let expected = self.take_checksum();
+        let mut bytes = [0; 8];
+        self.inner.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes) == expected)
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.checksum.write(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.checksum.write(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// The size of a key and a value, as written in the header
+fn record_sizes<K: Record, V: Record>() -> (u32, u32) {
+    let size = |s: usize| u32::try_from(s).expect("records must be under 4 GiB");
+    (size(K::SIZE), size(V::SIZE))
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Record,
+    V: Record,
+    S: SeededHasher,
+{
+    /// Writes a snapshot of the map to `writer`, in the format described in
+    /// the [module docs](self). The writes are small, so a file should be
+    /// wrapped in a [`std::io::BufWriter`]. The hasher has to be a
+    /// [`SeededHasher`]; see the [module docs](self) on what each one restores.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::{hash::SipState, hashmap::HashMap};
+    ///
+    /// let mut m = HashMap::with_hasher(SipState::new());
+    /// for i in 0..1000u64 {
+    ///     m.insert(i, i * i);
+    /// }
+    /// let mut bytes = Vec::new();
+    /// m.save_to(&mut bytes).unwrap();
+    ///
+    /// let loaded = HashMap::<u64, u64, SipState>::load_from(&bytes[..]).unwrap();
+    /// assert_eq!(loaded, m);
+    /// assert_eq!(loaded.hasher(), m.hasher());
+    ///
+    /// // a map with the default hasher round trips too, but with new keys
+    /// let m: HashMap<u64, u64> = (0..10).map(|i| (i, i)).collect();
+    /// let mut bytes = Vec::new();
+    /// m.save_to(&mut bytes).unwrap();
+    /// assert_eq!(HashMap::<u64, u64>::load_from(&bytes[..]).unwrap(), m);
+    /// ```
+    pub fn save_to<W: Write>(&self, writer: W) -> Result<(), SnapshotError> {
+        let mut out = Checksummed::new(writer);
+        let (key_size, value_size) = record_sizes::<K, V>();
+        let [k0, k1] = self.hash_builder.seed();
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&key_size.to_le_bytes())?;
+        out.write_all(&value_size.to_le_bytes())?;
+        out.write_all(&self.max_load_factor.to_bits().to_le_bytes())?;
+        out.write_all(&k0.to_le_bytes())?;
+        out.write_all(&k1.to_le_bytes())?;
+        out.write_all(&(self.len as u64).to_le_bytes())?;
+        out.write_checksum()?;
+
+        let mut buf = vec![0; K::SIZE + V::SIZE];
+        for (key, value) in self {
+            let (k, v) = buf.split_at_mut(K::SIZE);
+            key.encode(k);
+            value.encode(v);
+            out.write_all(&buf)?;
+        }
+        out.write_checksum()?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads back a map that [`HashMap::save_to`] wrote, with the same
+    /// hasher and maximum load factor. Damage to the snapshot is reported as
+    /// an error, never a panic: see [`SnapshotError`]. So is a maximum load
+    /// factor outside `1/64..=64`, which no sensible map has.
+    ///
+    /// The reads are small, so a file should be wrapped in a
+    /// [`std::io::BufReader`]. Nothing past the end of the snapshot is read.
+    pub fn load_from<R: Read>(reader: R) -> Result<Self, SnapshotError>
+    where
+        K: Eq + Hash,
+    {
+        let mut input = Checksummed::new(reader);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        // a different version may lay out the rest differently, so stop here
+        let version = input.read_u32()?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let key_size = input.read_u32()?;
+        let value_size = input.read_u32()?;
+        let max_load_factor = f64::from_bits(input.read_u64()?);
+        let seed = [input.read_u64()?, input.read_u64()?];
+        let len = input.read_u64()?;
+        if !input.checksum_matches()? {
+            return Err(SnapshotError::CorruptHeader);
+        }
+        let expected = record_sizes::<K, V>();
+        if (key_size, value_size) != expected {
+            return Err(SnapshotError::RecordSize {
+                expected: (K::SIZE, V::SIZE),
+                found: (key_size as usize, value_size as usize),
+            });
+        }
+        let len = usize::try_from(len).map_err(|_| SnapshotError::CorruptHeader)?;
+        if !LOAD_FACTORS.contains(&max_load_factor) {
+            return Err(SnapshotError::CorruptHeader);
+        }
+
+        let mut map = HashMap::with_hasher(S::from_seed(seed));
+        map.max_load_factor = max_load_factor;
+        map.reserve(len.min((MAX_PREALLOC_BINS as f64 * max_load_factor) as usize));
+        let mut buf = vec![0; K::SIZE + V::SIZE];
+        for _ in 0..len {
+            input.read_exact(&mut buf)?;
+            let (k, v) = buf.split_at(K::SIZE);
+            map.insert(K::decode(k), V::decode(v));
+        }
+        if !input.checksum_matches()? || map.len() != len {
+            return Err(SnapshotError::CorruptEntries);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{test_hasher, FxBuildHasher};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_map(n: usize) -> HashMap<u64, u64, SipState> {
+        let mut rng = StdRng::seed_from_u64(0x5a5a);
+        let mut m = HashMap::with_hasher(test_hasher());
+        for _ in 0..n {
+            m.insert(rng.gen(), rng.gen());
+        }
+        m
+    }
+
+    fn snapshot<K: Record, V: Record, S: SeededHasher>(m: &HashMap<K, V, S>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        m.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn load(bytes: &[u8]) -> Result<HashMap<u64, u64, SipState>, SnapshotError> {
+        HashMap::load_from(bytes)
+    }
+
+    /// The size of the header, including its checksum
+    const HEADER: usize = 8 + 4 * 3 + 8 * 4 + 8;
+
+    #[test]
+    fn round_trips() {
+        let m = random_map(10_000);
+        let bytes = snapshot(&m);
+        assert_eq!(bytes.len(), HEADER + 10_000 * 16 + 8);
+        let loaded = load(&bytes).unwrap();
+        assert_eq!(loaded, m);
+        assert_eq!(loaded.hasher(), &test_hasher());
+        assert_eq!(loaded.max_load_factor(), m.max_load_factor());
+        // the same seed means the same hashes, so the same iteration order
+        assert!(loaded.iter().eq(m.iter()));
+    }
+
+    #[test]
+    fn round_trips_with_the_default_hasher() {
+        let m: HashMap<u64, u64> = random_map(1000).into_iter().collect();
+        let mut bytes = Vec::new();
+        m.save_to(&mut bytes).unwrap();
+        assert_eq!(bytes[28..44], [0; 16], "no seed is saved");
+        let loaded = HashMap::<u64, u64>::load_from(&bytes[..]).unwrap();
+        assert_eq!(loaded, m);
+        assert_ne!(loaded.hasher().hash_one(1u64), m.hasher().hash_one(1u64));
+    }
+
+    #[test]
+    fn round_trips_empty_maps() {
+        let m = HashMap::<u64, u64, _>::with_hasher(test_hasher());
+        let bytes = snapshot(&m);
+        assert_eq!(bytes.len(), HEADER + 8);
+        assert!(load(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_max_load_factor() {
+        let mut m = HashMap::with_hasher(FxBuildHasher::default());
+        m.set_max_load_factor(2.5);
+        m.insert([1u8; 20], -7i32);
+        m.insert([2u8; 20], 12i32);
+        let loaded = HashMap::<[u8; 20], i32, FxBuildHasher>::load_from(&snapshot(&m)[..]).unwrap();
+        assert_eq!(loaded, m);
+        assert_eq!(loaded.max_load_factor(), 2.5);
+    }
+
+    #[test]
+    fn reads_no_further_than_the_snapshot() {
+        let m = random_map(100);
+        let mut bytes = snapshot(&m);
+        bytes.extend_from_slice(b"trailing");
+        let mut reader = &bytes[..];
+        assert_eq!(
+            HashMap::<u64, u64, SipState>::load_from(&mut reader).unwrap(),
+            m
+        );
+        assert_eq!(reader, b"trailing");
+    }
+
+    #[test]
+    fn every_flipped_bit_is_detected() {
+        let bytes = snapshot(&random_map(20));
+        for i in 0..bytes.len() {
+            for bit in 0..8 {
+                let mut damaged = bytes.clone();
+                damaged[i] ^= 1 << bit;
+                assert!(load(&damaged).is_err(), "bit {} of byte {}", bit, i);
+            }
+        }
+    }
+
+    #[test]
+    fn damage_is_reported_by_kind() {
+        let bytes = snapshot(&random_map(20));
+        let damage = |i: usize| {
+            let mut damaged = bytes.clone();
+            damaged[i] ^= 0xff;
+            load(&damaged).unwrap_err()
+        };
+        assert!(matches!(damage(0), SnapshotError::NotASnapshot));
+        assert!(matches!(damage(8), SnapshotError::UnsupportedVersion(_)));
+        assert!(matches!(damage(12), SnapshotError::CorruptHeader));
+        assert!(matches!(damage(HEADER - 1), SnapshotError::CorruptHeader));
+        assert!(matches!(damage(HEADER), SnapshotError::CorruptEntries));
+        assert!(matches!(
+            damage(bytes.len() - 1),
+            SnapshotError::CorruptEntries
+        ));
+    }
+
+    #[test]
+    fn truncation_is_detected() {
+        let bytes = snapshot(&random_map(20));
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(load(&bytes[..len]), Err(SnapshotError::Truncated)),
+                "truncated to {} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_types() {
+        let bytes = snapshot(&random_map(20));
+        let err = HashMap::<u32, u64, SipState>::load_from(&bytes[..]).unwrap_err();
+        assert!(matches!(
+            err,
+            SnapshotError::RecordSize {
+                expected: (4, 8),
+                found: (8, 8)
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "expected 4-byte keys and 8-byte values, found 8 and 8"
+        );
+    }
+
+    /// Rewrites the header of `bytes` with `f`, fixing up its checksum so that
+    /// only the change itself is wrong
+    fn rewrite_header(bytes: &mut [u8], f: impl FnOnce(&mut [u8])) {
+        f(&mut bytes[..HEADER - 8]);
+        let mut checksum = Fnv1aHasher::default();
+        checksum.write(&bytes[..HEADER - 8]);
+        bytes[HEADER - 8..HEADER].copy_from_slice(&checksum.finish().to_le_bytes());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = snapshot(&random_map(20));
+        rewrite_header(&mut bytes, |h| {
+            h[8..12].copy_from_slice(&2u32.to_le_bytes())
+        });
+        assert!(matches!(
+            load(&bytes),
+            Err(SnapshotError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn rejects_nonsense_load_factors() {
+        let mut bytes = snapshot(&random_map(20));
+        rewrite_header(&mut bytes, |h| {
+            h[20..28].copy_from_slice(&f64::NAN.to_bits().to_le_bytes())
+        });
+        assert!(matches!(load(&bytes), Err(SnapshotError::CorruptHeader)));
+        for &factor in &[0.0, -1.0, 1e-300, f64::INFINITY, 1e300] {
+            rewrite_header(&mut bytes, |h| {
+                h[20..28].copy_from_slice(&f64::to_bits(factor).to_le_bytes())
+            });
+            assert!(
+                matches!(load(&bytes), Err(SnapshotError::CorruptHeader)),
+                "load factor {}",
+                factor
+            );
+        }
+    }
+
+    /// A header can claim any number of pairs, and still have a valid
+    /// checksum: loading it must neither allocate for them all up front nor
+    /// overflow, but run out of input.
+    #[test]
+    fn huge_pair_counts_are_not_trusted() {
+        let bytes = snapshot(&random_map(20));
+        for &len in &[1u64 << 40, u64::MAX] {
+            let mut damaged = bytes.clone();
+            rewrite_header(&mut damaged, |h| {
+                h[44..52].copy_from_slice(&len.to_le_bytes())
+            });
+            assert!(
+                matches!(load(&damaged), Err(SnapshotError::Truncated)),
+                "{} pairs",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn tiny_load_factors_are_rejected_not_overflowed() {
+        let mut m = HashMap::with_hasher(test_hasher());
+        m.insert(1u64, 1u64);
+        let mut bytes = snapshot(&m);
+        rewrite_header(&mut bytes, |h| {
+            h[20..28].copy_from_slice(&1e-300f64.to_bits().to_le_bytes())
+        });
+        assert!(load(&bytes).is_err());
+    }
+
+    #[test]
+    fn a_checksummed_duplicate_key_is_corrupt() {
+        let mut m = HashMap::with_hasher(test_hasher());
+        m.insert(1u64, 10u64);
+        m.insert(2, 20);
+        let mut bytes = snapshot(&m);
+        // make both keys 1, and the pairs' checksum agree
+        bytes[HEADER..HEADER + 8].copy_from_slice(&1u64.to_le_bytes());
+        bytes[HEADER + 16..HEADER + 24].copy_from_slice(&1u64.to_le_bytes());
+        let end = bytes.len() - 8;
+        let mut checksum = Fnv1aHasher::default();
+        checksum.write(&bytes[HEADER..end]);
+        bytes[end..].copy_from_slice(&checksum.finish().to_le_bytes());
+        assert!(matches!(load(&bytes), Err(SnapshotError::CorruptEntries)));
+    }
+
+    #[test]
+    fn io_errors_are_passed_on() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+        let err = HashMap::<u64, u64, SipState>::load_from(Broken).unwrap_err();
+        assert!(matches!(err, SnapshotError::Io(_)));
+        assert!(err.source().is_some());
+    }
+}
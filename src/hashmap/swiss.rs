@@ -0,0 +1,888 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A [SwissTable][1]-style hash map, after Abseil's `flat_hash_map` and
+//! [`hashbrown`][2] (which backs `std`'s own `HashMap`).
+//!
+//! Alongside the table of pairs sits a table of one-byte *control bytes*, one
+//! per slot. A control byte says whether its slot is empty, deleted, or full,
+//! and a full slot's byte also holds 7 bits of its key's hash (the *tag*, or
+//! H2). The rest of the hash (H1) picks where probing starts.
+//!
+//! Probing moves a *group* of 16 control bytes at a time. The group is
+//! loaded into a single `u128` and compared against the tag with a few
+//! ordinary integer operations ("SIMD within a register"), so one step of a
+//! lookup tests 16 slots and only touches a pair whose tag matched. Real SIMD
+//! instructions would do the same thing in fewer cycles; this is the portable
+//! fallback that `hashbrown` uses on targets without them.
+//!
+//! [1]: https://abseil.io/about/design/swisstables
+//! [2]: https://github.com/rust-lang/hashbrown
+use super::Slot;
+use crate::hash::fmix64;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    convert::TryInto,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::Index,
+};
+
+mod iter;
+
+use super::open::SlotTable;
+pub use super::open::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+pub use iter::ExtractIf;
+
+/// The number of control bytes probed at once. Also the smallest table size.
+const GROUP_WIDTH: usize = 16;
+
+/// Control byte of a slot that has never held a pair, or whose pair was
+/// removed where no probe could have passed over it. Ends a probe.
+const EMPTY: u8 = 0b1111_1111;
+
+/// Control byte (a "tombstone") of a slot whose pair was removed while some
+/// probe might still need to pass over it
+const DELETED: u8 = 0b1000_0000;
+
+/// The same default as `hashbrown`
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.875;
+
+/// `0x01` in every byte
+const LO: u128 = u128::from_ne_bytes([0x01; GROUP_WIDTH]);
+
+/// `0x80` in every byte
+const HI: u128 = u128::from_ne_bytes([0x80; GROUP_WIDTH]);
+
+/// The top 7 bits of the hash, stored in the control byte of a full slot. A
+/// full slot's control byte therefore always has its high bit clear, unlike
+/// [`EMPTY`] and [`DELETED`].
+fn tag(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// 16 consecutive control bytes, byte `i` of the group in byte `i` of the
+/// integer (counting from the least significant end)
+#[derive(Debug, Clone, Copy)]
+struct Group(u128);
+
+impl Group {
+    /// The group starting at control byte `i`. Control bytes are mirrored
+    /// past the end of the table, so this never has to wrap around.
+    fn load(ctrl: &[u8], i: usize) -> Self {
+        Group(u128::from_le_bytes(
+            ctrl[i..i + GROUP_WIDTH].try_into().unwrap(),
+        ))
+    }
+
+    /// The bytes equal to `tag`. XOR turns matching bytes into zeros, and
+    /// `(x - 0x01..) & !x & 0x80..` sets the high bit of every zero byte.
+    ///
+    /// The subtraction can borrow out of a zero byte into the byte above it,
+    /// so a byte of `tag ^ 1` just above a real match may be reported too.
+    /// There are never false negatives, and a false positive is only a
+    /// wasted key comparison.
+    fn match_tag(self, tag: u8) -> BitMask {
+        let x = self.0 ^ (LO * tag as u128);
+        BitMask(x.wrapping_sub(LO) & !x & HI)
+    }
+
+    /// The [`EMPTY`] bytes: the only control bytes with both of their top two
+    /// bits set
+    fn match_empty(self) -> BitMask {
+        BitMask(self.0 & (self.0 << 1) & HI)
+    }
+
+    /// The [`EMPTY`] or [`DELETED`] bytes: those with their high bit set
+    fn match_empty_or_deleted(self) -> BitMask {
+        BitMask(self.0 & HI)
+    }
+}
+
+/// One bit (the high bit of a byte) per matching byte of a [`Group`].
+/// Iterating yields the positions of the matches within the group.
+#[derive(Debug, Clone, Copy)]
+struct BitMask(u128);
+
+impl BitMask {
+    fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    fn lowest(mut self) -> Option<usize> {
+        self.next()
+    }
+
+    /// How many of the group's first bytes are not matches
+    fn leading_misses(self) -> usize {
+        self.0.trailing_zeros() as usize / 8
+    }
+
+    /// How many of the group's last bytes are not matches
+    fn trailing_misses(self) -> usize {
+        self.0.leading_zeros() as usize / 8
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let i = self.0.trailing_zeros() as usize / 8;
+        // clear the lowest set bit
+        self.0 &= self.0 - 1;
+        Some(i)
+    }
+}
+
+/// Sets the control byte of slot `i`, and its mirror if it is one of the first
+/// [`GROUP_WIDTH`] slots
+fn set_ctrl(ctrl: &mut [u8], i: usize, byte: u8) {
+    let mask = ctrl.len() - GROUP_WIDTH - 1;
+    ctrl[i] = byte;
+    // `i` itself for every slot but the first few
+    ctrl[(i.wrapping_sub(GROUP_WIDTH) & mask) + GROUP_WIDTH] = byte;
+}
+
+/// Takes the pair out of slot `i`. If every group that contains the slot also
+/// contains an empty slot, no probe can have passed over it, so it can go
+/// straight back to [`EMPTY`]; otherwise it has to become a tombstone, counted
+/// in `deleted`.
+fn erase<K, V>(
+    ctrl: &mut [u8],
+    slots: &mut [Option<Slot<K, V>>],
+    deleted: &mut usize,
+    i: usize,
+) -> Slot<K, V> {
+    let mask = slots.len() - 1;
+    let before = Group::load(ctrl, i.wrapping_sub(GROUP_WIDTH) & mask).match_empty();
+    let after = Group::load(ctrl, i).match_empty();
+    // the longest run of non-empty slots through `i` that a probe could see
+    let run = before.trailing_misses() + after.leading_misses();
+    let byte = if run >= GROUP_WIDTH {
+        *deleted += 1;
+        DELETED
+    } else {
+        EMPTY
+    };
+    set_ctrl(ctrl, i, byte);
+    slots[i].take().expect("slot is occupied")
+}
+
+/// A hash map with the same API as [`super::HashMap`], using SwissTable
+/// control bytes to probe 16 slots at a time. See the [module docs](self).
+#[derive(Clone)]
+pub struct SwissMap<K, V, S = RandomState> {
+    /// One control byte per slot, followed by copies of the first
+    /// [`GROUP_WIDTH`] so that a group can be loaded from any slot. Empty
+    /// for an unallocated map.
+    ctrl: Vec<u8>,
+    /// Always empty or a power of two, at least [`GROUP_WIDTH`], in length
+    slots: Vec<Option<Slot<K, V>>>,
+    len: usize,
+    /// The number of [`DELETED`] control bytes. They take up room in probe
+    /// sequences just like pairs do, so they count against the capacity.
+    deleted: usize,
+    max_load_factor: f64,
+    hash_builder: S,
+}
+
+impl<K, V> SwissMap<K, V, RandomState> {
+    /// Constructs a new, empty `SwissMap<K,V>`. No slots are allocated until
+    /// the first insert.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Constructs a new, empty `SwissMap<K,V>` that can hold at least
+    /// `capacity` pairs before it needs to grow.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::SwissMap;
+    ///
+    /// let m = SwissMap::<u32, u32>::with_capacity(100);
+    /// assert!(m.capacity() >= 100);
+    /// assert_eq!(m.len(), 0);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> SwissMap<K, V, S> {
+    /// Constructs a new, empty map which hashes its keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        SwissMap {
+            ctrl: Vec::new(),
+            slots: Vec::new(),
+            len: 0,
+            deleted: 0,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            hash_builder,
+        }
+    }
+
+    /// Combines [`SwissMap::with_capacity`] and [`SwissMap::with_hasher`].
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    /// The map's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of pairs in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes every pair, keeping the table for reuse.
+    pub fn clear(&mut self) {
+        for byte in &mut self.ctrl {
+            *byte = EMPTY;
+        }
+        for slot in &mut self.slots {
+            *slot = None;
+        }
+        self.len = 0;
+        self.deleted = 0;
+    }
+
+    /// The number of pairs the map can hold before it has to grow
+    pub fn capacity(&self) -> usize {
+        (self.slots.len() as f64 * self.max_load_factor) as usize
+    }
+
+    /// The fraction of slots in use above which the map grows
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor
+    }
+
+    /// Changes the maximum load factor, growing straight away if the map is now
+    /// over it. Lowering the load factor never shrinks the table.
+    ///
+    /// ## Panics
+    /// If `max_load_factor` is not strictly between zero and one. A probe only
+    /// stops at an empty slot, so the table can never be full.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor < 1.0,
+            "the maximum load factor must be between zero and one"
+        );
+        self.max_load_factor = max_load_factor;
+        self.reserve(0);
+    }
+
+    /// Makes room for at least `additional` more pairs without growing.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            self.resize(self.slots_for(needed).max(self.slots.len() * 2));
+        } else if needed + self.deleted > self.capacity() {
+            // the pairs fit, only the tombstones are in the way: clear them out
+            // without growing
+            self.resize(self.slots.len());
+        }
+    }
+
+    /// Shrinks the table to the smallest power-of-two number of slots that can
+    /// hold the current pairs. An empty map releases its whole table.
+    pub fn shrink_to_fit(&mut self) {
+        let slots = if self.len == 0 {
+            0
+        } else {
+            self.slots_for(self.len)
+        };
+        if slots < self.slots.len() {
+            self.resize(slots);
+        }
+    }
+
+    /// The smallest power-of-two number of slots that keeps `len` pairs under
+    /// the maximum load factor
+    fn slots_for(&self, len: usize) -> usize {
+        let slots = (len as f64 / self.max_load_factor).ceil() as usize;
+        slots.max(len + 1).max(GROUP_WIDTH).next_power_of_two()
+    }
+
+    /// Moves every pair into a fresh table of `slots` slots, dropping every
+    /// tombstone along the way
+    fn resize(&mut self, slots: usize) {
+        let mut table = Vec::with_capacity(slots);
+        table.resize_with(slots, || None);
+        let old = mem::replace(&mut self.slots, table);
+        self.ctrl = if slots == 0 {
+            Vec::new()
+        } else {
+            vec![EMPTY; slots + GROUP_WIDTH]
+        };
+        self.deleted = 0;
+        for slot in old.into_iter().flatten() {
+            self.place(slot);
+        }
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    /// The slots to probe for `hash`, a group at a time: the start of each
+    /// group. The stride grows by a group each step ("triangular" probing),
+    /// which visits every group of a power-of-two table exactly once.
+    fn probe_seq(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let mask = self.mask();
+        let groups = self.slots.len() / GROUP_WIDTH;
+        (0..groups).scan(hash as usize & mask, move |pos, stride| {
+            let here = *pos;
+            *pos = (*pos + (stride + 1) * GROUP_WIDTH) & mask;
+            Some(here)
+        })
+    }
+
+    /// Inserts a pair whose key is known not to be in the map, in the first
+    /// empty or deleted slot of its probe sequence. Returns the slot's index.
+    /// The table must have room.
+    fn place(&mut self, slot: Slot<K, V>) -> usize {
+        let mask = self.mask();
+        let i = self
+            .probe_seq(slot.hash)
+            .find_map(|pos| {
+                let free = Group::load(&self.ctrl, pos).match_empty_or_deleted();
+                free.lowest().map(|bit| (pos + bit) & mask)
+            })
+            .expect("the table is never full");
+        if self.ctrl[i] == DELETED {
+            self.deleted -= 1;
+        }
+        set_ctrl(&mut self.ctrl, i, tag(slot.hash));
+        self.slots[i] = Some(slot);
+        i
+    }
+
+    /// The pair in slot `i`, which must be occupied
+    fn slot(&self, i: usize) -> &Slot<K, V> {
+        self.slots[i].as_ref().expect("slot is occupied")
+    }
+
+    fn slot_mut(&mut self, i: usize) -> &mut Slot<K, V> {
+        self.slots[i].as_mut().expect("slot is occupied")
+    }
+
+    /// Removes the pair in slot `i`, which must be occupied
+    fn remove_index(&mut self, i: usize) -> Slot<K, V> {
+        self.len -= 1;
+        erase(&mut self.ctrl, &mut self.slots, &mut self.deleted, i)
+    }
+}
+
+impl<K, V, S> SwissMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// See [`super::HashMap`] on hashing borrowed forms of a key, and on why
+    /// the hash is mixed.
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        fmix64(self.hash_builder.hash_one(key))
+    }
+
+    /// The slot holding `key`, if it is in the map
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mask = self.mask();
+        let tag = tag(hash);
+        for pos in self.probe_seq(hash) {
+            let group = Group::load(&self.ctrl, pos);
+            for bit in group.match_tag(tag) {
+                let i = (pos + bit) & mask;
+                if let Some(slot) = &self.slots[i] {
+                    if slot.hash == hash && slot.key.borrow() == key {
+                        return Some(i);
+                    }
+                }
+            }
+            // an insert would have stopped at this empty slot
+            if group.match_empty().any() {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Inserts `V` at key `K`. If the key was already present, its old value is
+    /// replaced and returned.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::SwissMap;
+    ///
+    /// let mut m = SwissMap::<&str, i32>::new();
+    /// assert_eq!(m.insert("foo", 1), None);
+    /// assert_eq!(m.insert("foo", 2), Some(1));
+    /// assert_eq!(m.get("foo"), Some(&2));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+        if let Some(i) = self.find(hash, &key) {
+            return Some(mem::replace(&mut self.slot_mut(i).value, value));
+        }
+        self.reserve(1);
+        self.place(Slot { hash, key, value });
+        self.len += 1;
+        None
+    }
+
+    /// Gets an optional reference to `V` using key `K`, or any borrowed form of
+    /// it.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(&self.slot(i).value)
+    }
+
+    /// Gets an optional mutable reference to `V` using key `K`, or any borrowed
+    /// form of it.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(&mut self.slot_mut(i).value)
+    }
+
+    /// Returns `true` if the map holds a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(self.hash(key), key).is_some()
+    }
+
+    /// Removes the pair for `key`, or any borrowed form of it, returning the
+    /// value if the key was in the map.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::SwissMap;
+    ///
+    /// let mut m = SwissMap::<&str, i32>::new();
+    /// m.insert("baz", 1);
+    /// assert_eq!(m.remove("baz"), Some(1));
+    /// assert_eq!(m.remove("baz"), None);
+    /// ```
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let i = self.find(self.hash(key), key)?;
+        Some(self.remove_index(i).value)
+    }
+
+    /// Gets the [`Entry`] for `key`, for in-place insertion or modification.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::SwissMap;
+    ///
+    /// let mut counts = SwissMap::new();
+    /// for word in "a b a c a b".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(counts["a"], 3);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into a single pair of a [`SwissMap`], which may or may not be
+/// present. Created by [`SwissMap::entry`].
+pub enum Entry<'a, K, V, S = RandomState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// An entry for a key that is in the map
+pub struct OccupiedEntry<'a, K, V, S = RandomState> {
+    map: &'a mut SwissMap<K, V, S>,
+    /// The slot holding the pair
+    index: usize,
+}
+
+/// An entry for a key that is not in the map
+pub struct VacantEntry<'a, K, V, S = RandomState> {
+    map: &'a mut SwissMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S> {
+    /// Inserts `default` if the entry is vacant, and returns a mutable
+    /// reference to the value either way.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], but only computes the value when it is needed.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert`], using `V::default()`.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied, leaving a vacant entry
+    /// untouched.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// The key this entry was created for
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    /// The key already in the map
+    pub fn key(&self) -> &K {
+        &self.map.slot(self.index).key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.slot(self.index).value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.slot_mut(self.index).value
+    }
+
+    /// Converts the entry into a mutable reference that lives as long as the
+    /// borrow of the map
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slot_mut(self.index).value
+    }
+
+    /// Replaces the value, returning the old one. The key is left untouched.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the pair from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the pair from the map, returning both the key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        let slot = self.map.remove_index(self.index);
+        (slot.key, slot.value)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
+    /// The key that would be inserted
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back ownership of the key
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the pair, returning a mutable reference to the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let map = self.map;
+        // only grow once the pair is known to be new
+        map.reserve(1);
+        let i = map.place(Slot {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        map.len += 1;
+        &mut map.slot_mut(i).value
+    }
+}
+
+impl<K, V, S> Default for SwissMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// Prints as a map, e.g. `{"a": 1, "b": 2}`, in iteration order
+impl<K, V, S> Debug for SwissMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal if they hold the same pairs, regardless of their
+/// capacities or the order in which the pairs were inserted
+impl<K, V, S> PartialEq for SwissMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for SwissMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+/// ## Panics
+/// If the key is not in the map. Use [`SwissMap::get`] when that is possible.
+impl<K, Q, V, S> Index<&Q> for SwissMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in SwissMap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashmap::conformance::Zero;
+    use std::hash::BuildHasherDefault;
+
+    map_conformance!(SwissMap);
+
+    fn group(bytes: [u8; GROUP_WIDTH]) -> Group {
+        Group::load(&bytes, 0)
+    }
+
+    /// The control bytes agree with the slots, the mirror agrees with the
+    /// start of the table, and the counts add up
+    fn assert_invariants<K, V, S>(m: &SwissMap<K, V, S>) {
+        if m.slots.is_empty() {
+            assert!(m.ctrl.is_empty());
+            return;
+        }
+        let n = m.slots.len();
+        assert_eq!(&m.ctrl[..GROUP_WIDTH], &m.ctrl[n..]);
+        for (byte, slot) in m.ctrl.iter().zip(&m.slots) {
+            match slot {
+                Some(slot) => assert_eq!(*byte, tag(slot.hash)),
+                None => assert!(*byte == EMPTY || *byte == DELETED),
+            }
+        }
+        assert_eq!(m.slots.iter().flatten().count(), m.len());
+        let deleted = m.ctrl[..n].iter().filter(|&&b| b == DELETED).count();
+        assert_eq!(deleted, m.deleted);
+        assert!(m.len() + m.deleted < n);
+    }
+
+    #[test]
+    fn match_tag_finds_every_match() {
+        let mut bytes = [EMPTY; GROUP_WIDTH];
+        bytes[0] = 0x12;
+        bytes[5] = 0x12;
+        bytes[6] = DELETED;
+        bytes[15] = 0x12;
+        let matches: Vec<_> = group(bytes).match_tag(0x12).collect();
+        assert_eq!(matches, vec![0, 5, 15]);
+        assert!(!group(bytes).match_tag(0x34).any());
+    }
+
+    #[test]
+    fn match_tag_false_positives_are_only_next_to_real_matches() {
+        // 0x13 == 0x12 ^ 1, directly after a real match
+        let mut bytes = [0x00; GROUP_WIDTH];
+        bytes[3] = 0x12;
+        bytes[4] = 0x13;
+        let matches: Vec<_> = group(bytes).match_tag(0x12).collect();
+        assert!(matches.contains(&3));
+        assert!(matches.iter().all(|&i| i == 3 || i == 4));
+    }
+
+    #[test]
+    fn match_empty_and_deleted() {
+        let mut bytes = [0x7f; GROUP_WIDTH];
+        bytes[2] = EMPTY;
+        bytes[9] = DELETED;
+        bytes[14] = EMPTY;
+        let g = group(bytes);
+        assert_eq!(g.match_empty().collect::<Vec<_>>(), vec![2, 14]);
+        assert_eq!(
+            g.match_empty_or_deleted().collect::<Vec<_>>(),
+            vec![2, 9, 14]
+        );
+        assert_eq!(g.match_empty().leading_misses(), 2);
+        assert_eq!(g.match_empty().trailing_misses(), 1);
+        assert_eq!(group([0; GROUP_WIDTH]).match_empty().trailing_misses(), 16);
+    }
+
+    #[test]
+    fn invariants_hold_through_inserts_and_removes() {
+        let mut m = SwissMap::new();
+        for i in 0..5000u32 {
+            m.insert(i, i);
+            if i % 3 == 0 {
+                m.remove(&(i / 2));
+            }
+        }
+        assert_invariants(&m);
+        m.retain(|k, _| k % 5 != 0);
+        assert_invariants(&m);
+        let _ = m.drain().take(3).count();
+        assert_invariants(&m);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn draining_resets_the_control_bytes() {
+        let mut m: SwissMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let (slots, ctrl) = (m.slots.len(), m.ctrl.len());
+        std::mem::forget(m.drain());
+        assert!(m.slots.is_empty() && m.ctrl.is_empty());
+        assert_invariants(&m);
+
+        m.extend((0..100).map(|i| (i, i)));
+        m.remove(&7);
+        let _ = m.drain().take(3).count();
+        assert_eq!((m.slots.len(), m.ctrl.len()), (slots, ctrl));
+        assert!(m.ctrl.iter().all(|&b| b == EMPTY));
+        assert_eq!(m.deleted, 0);
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn removing_from_a_sparse_table_leaves_no_tombstones() {
+        let mut m = SwissMap::with_capacity(1000);
+        for i in 0..10u32 {
+            m.insert(i, ());
+        }
+        for i in 0..10u32 {
+            m.remove(&i);
+        }
+        assert_eq!(m.deleted, 0);
+        assert!(m.ctrl.iter().all(|&b| b == EMPTY));
+    }
+
+    #[test]
+    fn churn_does_not_grow_the_table() {
+        let mut m = SwissMap::new();
+        for i in 0..10_000u32 {
+            m.insert(i, ());
+            if i >= 10 {
+                m.remove(&(i - 10));
+            }
+        }
+        assert_eq!(m.len(), 10);
+        assert!(m.slots.len() <= 32);
+        assert_invariants(&m);
+    }
+
+    #[test]
+    fn colliding_keys_reuse_tombstones() {
+        // every key has the same hash (and tag), so the table fills in order
+        // and every removal from the middle leaves a tombstone
+        let mut m = SwissMap::with_hasher(BuildHasherDefault::<Zero>::default());
+        for i in 0..20u32 {
+            m.insert(i, ());
+        }
+        let slots = m.slots.len();
+        for round in 0..100 {
+            m.remove(&(round % 20));
+            assert_eq!(m.deleted, 1);
+            m.insert(round % 20, ());
+            assert_eq!(m.deleted, 0);
+            assert_invariants(&m);
+        }
+        assert_eq!(m.slots.len(), slots);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_full_load_factor() {
+        SwissMap::<u8, u8>::new().set_max_load_factor(1.0);
+    }
+}
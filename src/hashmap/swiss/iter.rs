@@ -0,0 +1,227 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Iteration over a [`SwissMap`]. The pairs sit in a table laid out just like
+//! an [`OpenMap`](super::super::OpenMap)'s, so the iterators are shared with
+//! it; only [`ExtractIf`], which has to keep the control bytes up to date, is
+//! specific to this map.
+use super::*;
+use std::iter::{FromIterator, FusedIterator};
+
+impl<K, V, S> SwissMap<K, V, S> {
+    /// An iterator over `(&K, &V)` pairs, in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.slots.iter(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over `(&K, &mut V)` pairs, in arbitrary order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.slots.iter_mut(),
+            remaining: self.len,
+        }
+    }
+
+    /// An iterator over the keys, in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator over the values, in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator over mutable references to the values, in arbitrary order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Removes every pair from the map, yielding them as an iterator. The table
+    /// is kept, so the map's capacity is unchanged. Pairs that are not
+    /// consumed before the iterator is dropped are still removed.
+    pub fn drain(&mut self) -> Drain<'_, K, V, Self> {
+        let remaining = self.len;
+        Drain::new(self, remaining)
+    }
+
+    /// Keeps only the pairs for which `f` returns `true`.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::hashmap::SwissMap;
+    ///
+    /// let mut m: SwissMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// m.retain(|k, _| k % 2 == 0);
+    /// assert_eq!(m.len(), 5);
+    /// assert!(!m.contains_key(&5));
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Removes and yields the pairs for which `pred` returns `true`, lazily.
+    /// Pairs that are not reached before the iterator is dropped stay in the
+    /// map.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        ExtractIf {
+            ctrl: &mut self.ctrl,
+            slots: &mut self.slots,
+            len: &mut self.len,
+            deleted: &mut self.deleted,
+            index: 0,
+            pred,
+        }
+    }
+}
+
+/// Every slot is about to be emptied, so the control bytes are cleared up
+/// front, and set back to empty when the slots return. The `Vec` is kept, so
+/// neither allocation is lost.
+impl<K, V, S> SlotTable<K, V> for SwissMap<K, V, S> {
+    fn take_slots(&mut self) -> (Vec<Option<Slot<K, V>>>, usize) {
+        self.len = 0;
+        self.deleted = 0;
+        self.ctrl.clear();
+        (mem::take(&mut self.slots), 0)
+    }
+
+    fn restore_slots(&mut self, slots: Vec<Option<Slot<K, V>>>, _: usize) {
+        if !slots.is_empty() {
+            self.ctrl.resize(slots.len() + GROUP_WIDTH, EMPTY);
+        }
+        self.slots = slots;
+    }
+}
+
+/// Created by [`SwissMap::extract_if`]
+pub struct ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    ctrl: &'a mut Vec<u8>,
+    slots: &'a mut Vec<Option<Slot<K, V>>>,
+    /// The map's length and tombstone count, kept up to date as pairs are
+    /// removed
+    len: &'a mut usize,
+    deleted: &'a mut usize,
+    /// The next slot to test. Removing a pair never moves another one, so
+    /// this only goes forwards.
+    index: usize,
+    pred: F,
+}
+
+impl<K, V, F> Iterator for ExtractIf<'_, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.slots.len() {
+            let i = self.index;
+            self.index += 1;
+            if let Some(slot) = &mut self.slots[i] {
+                if (self.pred)(&slot.key, &mut slot.value) {
+                    let slot = erase(self.ctrl, self.slots, self.deleted, i);
+                    *self.len -= 1;
+                    return Some((slot.key, slot.value));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(*self.len))
+    }
+}
+
+impl<K, V, F> FusedIterator for ExtractIf<'_, K, V, F> where F: FnMut(&K, &mut V) -> bool {}
+
+impl<K, V, S> IntoIterator for SwissMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            slots: self.slots.into_iter(),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a SwissMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut SwissMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for SwissMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = SwissMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for SwissMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S> Extend<(&'a K, &'a V)> for SwissMap<K, V, S>
+where
+    K: Eq + Hash + Copy,
+    V: Copy,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
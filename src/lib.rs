@@ -15,8 +15,13 @@
 //! nominal version of the thing myself.
 
 #![doc(html_favicon_url = "https://f001.backblazeb2.com/file/dks-public/favicon.ico")]
+pub mod arc;
+pub mod cache;
 pub mod channel;
+pub mod hash;
 pub mod hashmap;
+pub mod probabilistic;
 pub mod rc;
 pub mod refcell;
+pub mod search;
 pub mod sort;
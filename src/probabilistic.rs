@@ -0,0 +1,77 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Probabilistic data structures, which answer questions about a set or a
+//! stream approximately, in a small fraction of the space an exact answer
+//! would take. Each keeps only hashes of its items, never the items
+//! themselves:
+//!
+//! - [`BloomFilter`] answers "have I seen this?" with no false negatives and
+//!   a configurable rate of false positives. [`CountingBloomFilter`] also
+//!   supports removal.
+//! - [`CountMinSketch`] answers "how often have I seen this?", never
+//!   underestimating.
+//! - [`HyperLogLog`] answers "how many distinct things have I seen?"
+//!
+//! Two structures built with the same parameters and the same hasher can be
+//! merged, e.g. to combine the results of counting on several machines.
+//!
+//! ```
+//! use tinystd::probabilistic::BloomFilter;
+//!
+//! let mut seen = BloomFilter::new(1000, 0.01);
+//! seen.insert("apple");
+//! assert!(seen.contains("apple"));
+//! // 99% of the time:
+//! // assert!(!seen.contains("banana"));
+//! ```
+pub mod bloom;
+pub mod count_min;
+pub mod hyperloglog;
+
+pub use bloom::{BloomFilter, CountingBloomFilter};
+pub use count_min::CountMinSketch;
+pub use hyperloglog::HyperLogLog;
+
+use crate::hash::fmix64;
+use std::hash::{BuildHasher, Hash};
+
+/// Hashes `item`, then mixes the bits of the hash, so that even a weak hasher
+/// like [`crate::hash::FxHasher`] gives bits that look independent of each
+/// other. The structures here slice a hash up and rely on every slice being
+/// uniformly distributed.
+fn hash<T, S>(hash_builder: &S, item: &T) -> u64
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    fmix64(hash_builder.hash_one(item))
+}
+
+/// `k` indexes into `0..m` for an item with the given hash, by
+/// [double hashing][1]: the `i`th is `h1 + i * h2 (mod m)`. Two hash functions
+/// do as well as `k` independent ones, asymptotically.
+///
+/// [1]: https://www.eecs.harvard.edu/~michaelm/postscripts/rsa2008.pdf
+fn indexes(hash: u64, k: usize, m: usize) -> impl Iterator<Item = usize> {
+    let h1 = hash;
+    let h2 = fmix64(hash);
+    (0..k as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m as u64) as usize)
+}
+
+/// Whether two hashers hash alike. There's no way to compare arbitrary
+/// [`BuildHasher`]s, so compare what they do to a probe value instead: two
+/// differently keyed hashers agree on it with probability 2⁻⁶⁴.
+fn same_hasher<S: BuildHasher>(a: &S, b: &S) -> bool {
+    const PROBE: u64 = 0x7465_7374_7072_6f62;
+    a.hash_one(PROBE) == b.hash_one(PROBE)
+}
@@ -0,0 +1,637 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Bloom filters][1]: approximate set membership.
+//!
+//! A Bloom filter is an array of `m` bits, all initially clear, and `k` hash
+//! functions onto the array. Inserting an item sets the `k` bits it hashes to;
+//! looking one up checks whether all `k` are set. An item that was inserted
+//! is always found, but an item that wasn't may find all of its bits set by
+//! others: a false positive.
+//!
+//! After `n` inserts into `m` bits, the false positive rate is about
+//! `(1 - e^(-kn/m))^k`. For a target rate `p`, that is smallest with
+//! `m = -n ln p / (ln 2)²` bits and `k = (m / n) ln 2` hashes, which is how
+//! [`BloomFilter::new`] sizes a filter: about 9.6 bits per item for a 1%
+//! rate, and 4.8 more for each tenfold improvement.
+//!
+//! A plain Bloom filter can't remove an item, since the item's bits may be
+//! shared with others. A [`CountingBloomFilter`] keeps a small counter in
+//! place of each bit, so it can.
+//!
+//! [1]: https://en.wikipedia.org/wiki/Bloom_filter
+use super::{hash, indexes, same_hasher};
+use std::{
+    collections::hash_map::RandomState,
+    f64::consts::LN_2,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The number of bits and hashes that give `false_positive_rate` for
+/// `expected_items` items
+fn optimal_size(expected_items: usize, false_positive_rate: f64) -> (usize, usize) {
+    assert!(
+        false_positive_rate > 0.0 && false_positive_rate < 1.0,
+        "the false positive rate must be between zero and one"
+    );
+    let n = expected_items.max(1) as f64;
+    let m = (-n * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+    let k = (m / n * LN_2).round().max(1.0);
+    (m as usize, k as usize)
+}
+
+/// An approximate set of `T`s, with no false negatives and a tunable rate of
+/// false positives. See the [module docs](self).
+#[derive(Clone)]
+pub struct BloomFilter<T: ?Sized, S = RandomState> {
+    /// `num_bits` bits, packed 64 to a word
+    bits: Vec<u64>,
+    num_bits: usize,
+    hashes: usize,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> BloomFilter<T, RandomState> {
+    /// Constructs an empty filter sized so that after `expected_items` inserts,
+    /// lookups of other items are false positives at about
+    /// `false_positive_rate`.
+    ///
+    /// ## Panics
+    /// If `false_positive_rate` is not strictly between zero and one.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::probabilistic::BloomFilter;
+    ///
+    /// let filter = BloomFilter::<u64>::new(1000, 0.01);
+    /// assert_eq!(filter.hashes(), 7);
+    /// assert!(filter.bits() > 9 * 1000);
+    /// ```
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        Self::with_hasher(expected_items, false_positive_rate, RandomState::new())
+    }
+}
+
+impl<T: ?Sized, S> BloomFilter<T, S> {
+    /// Like [`BloomFilter::new`], hashing items with `hash_builder`. Filters
+    /// that are to be combined must share a hasher.
+    pub fn with_hasher(expected_items: usize, false_positive_rate: f64, hash_builder: S) -> Self {
+        let (num_bits, hashes) = optimal_size(expected_items, false_positive_rate);
+        BloomFilter {
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_bits,
+            hashes,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The filter's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of bits in the filter, `m`
+    pub fn bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// The number of bits each item sets, `k`
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns `true` if nothing has been inserted since the filter was created
+    /// or cleared.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Removes every item.
+    pub fn clear(&mut self) {
+        for word in &mut self.bits {
+            *word = 0;
+        }
+    }
+
+    /// The number of bits set
+    fn ones(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// The chance that looking up an item that was never inserted returns
+    /// `true`, given the bits set so far
+    pub fn false_positive_rate(&self) -> f64 {
+        (self.ones() as f64 / self.num_bits as f64).powi(self.hashes as i32)
+    }
+
+    /// An estimate of the number of distinct items inserted, from the
+    /// fraction of bits set: `-(m / k) ln(1 - ones / m)`.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.num_bits as f64;
+        let k = self.hashes as f64;
+        -m / k * (1.0 - self.ones() as f64 / m).ln()
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// Panics unless `other` can be combined with this filter
+    fn assert_compatible(&self, other: &Self)
+    where
+        S: BuildHasher,
+    {
+        assert!(
+            self.num_bits == other.num_bits && self.hashes == other.hashes,
+            "filters must have the same number of bits and hashes"
+        );
+        assert!(
+            same_hasher(&self.hash_builder, &other.hash_builder),
+            "filters must share a hasher"
+        );
+    }
+}
+
+impl<T, S> BloomFilter<T, S>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Adds an item. Returns `true` if the item was definitely not in the
+    /// filter before, `false` if it may have been.
+    pub fn insert(&mut self, item: &T) -> bool {
+        let mut new = false;
+        for i in indexes(hash(&self.hash_builder, item), self.hashes, self.num_bits) {
+            let bit = 1 << (i % 64);
+            new |= self.bits[i / 64] & bit == 0;
+            self.bits[i / 64] |= bit;
+        }
+        new
+    }
+
+    /// Returns `true` if the item may have been inserted, and `false` if it
+    /// definitely wasn't.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::probabilistic::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new(100, 0.001);
+    /// for word in "the quick brown fox".split(' ') {
+    ///     filter.insert(word);
+    /// }
+    /// assert!(filter.contains("fox"));
+    /// // `false`, 999 times in 1000
+    /// let _ = filter.contains("dog");
+    /// ```
+    pub fn contains(&self, item: &T) -> bool {
+        indexes(hash(&self.hash_builder, item), self.hashes, self.num_bits).all(|i| self.get(i))
+    }
+
+    /// Adds every item of `other` to this filter. The result is exactly the
+    /// filter that inserting both filters' items would have built.
+    ///
+    /// ## Panics
+    /// If the filters differ in size, number of hashes or hasher.
+    ///
+    /// ## Example:
+    /// ```
+    /// use std::collections::hash_map::RandomState;
+    /// use tinystd::probabilistic::BloomFilter;
+    ///
+    /// let hasher = RandomState::new();
+    /// let mut a = BloomFilter::with_hasher(100, 0.01, hasher.clone());
+    /// let mut b = BloomFilter::with_hasher(100, 0.01, hasher);
+    /// a.insert("a");
+    /// b.insert("b");
+    /// a.union(&b);
+    /// assert!(a.contains("a") && a.contains("b"));
+    /// ```
+    pub fn union(&mut self, other: &Self) {
+        self.assert_compatible(other);
+        for (word, other) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= other;
+        }
+    }
+
+    /// Keeps only the bits set in both filters. Every item inserted into both
+    /// is still found, but the false positive rate can be higher than a filter
+    /// built from just those items would have, since an item in neither may
+    /// have its bits set half by one filter and half by the other.
+    ///
+    /// ## Panics
+    /// If the filters differ in size, number of hashes or hasher.
+    pub fn intersection(&mut self, other: &Self) {
+        self.assert_compatible(other);
+        for (word, other) in self.bits.iter_mut().zip(&other.bits) {
+            *word &= other;
+        }
+    }
+}
+
+impl<T, S> Extend<T> for BloomFilter<T, S>
+where
+    T: Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(&item);
+        }
+    }
+}
+
+/// Prints the filter's shape rather than its bits
+impl<T: ?Sized, S> Debug for BloomFilter<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BloomFilter")
+            .field("bits", &self.num_bits)
+            .field("hashes", &self.hashes)
+            .field("ones", &self.ones())
+            .finish()
+    }
+}
+
+/// A Bloom filter with a counter in place of each bit, so that items can be
+/// removed again. See the [module docs](self).
+///
+/// The counters are a byte each, so this takes eight times the space of a
+/// [`BloomFilter`] with the same false positive rate. A counter that reaches
+/// 255 sticks there: it can no longer tell how many items share it, so
+/// decrementing it might cause false negatives later.
+#[derive(Clone)]
+pub struct CountingBloomFilter<T: ?Sized, S = RandomState> {
+    counters: Vec<u8>,
+    hashes: usize,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> CountingBloomFilter<T, RandomState> {
+    /// Constructs an empty filter sized like [`BloomFilter::new`].
+    ///
+    /// ## Panics
+    /// If `false_positive_rate` is not strictly between zero and one.
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        Self::with_hasher(expected_items, false_positive_rate, RandomState::new())
+    }
+}
+
+impl<T: ?Sized, S> CountingBloomFilter<T, S> {
+    /// Like [`CountingBloomFilter::new`], hashing items with `hash_builder`.
+    pub fn with_hasher(expected_items: usize, false_positive_rate: f64, hash_builder: S) -> Self {
+        let (counters, hashes) = optimal_size(expected_items, false_positive_rate);
+        CountingBloomFilter {
+            counters: vec![0; counters],
+            hashes,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The filter's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of counters in the filter, `m`
+    pub fn counters(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// The number of counters each item increments, `k`
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns `true` if every item inserted has been removed again.
+    pub fn is_empty(&self) -> bool {
+        self.counters.iter().all(|&c| c == 0)
+    }
+
+    /// Removes every item.
+    pub fn clear(&mut self) {
+        for c in &mut self.counters {
+            *c = 0;
+        }
+    }
+
+    /// The chance that looking up an item that was never inserted returns
+    /// `true`, given the counters set so far
+    pub fn false_positive_rate(&self) -> f64 {
+        let nonzero = self.counters.iter().filter(|&&c| c != 0).count();
+        (nonzero as f64 / self.counters.len() as f64).powi(self.hashes as i32)
+    }
+}
+
+impl<T, S> CountingBloomFilter<T, S>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    fn indexes(&self, item: &T) -> impl Iterator<Item = usize> {
+        indexes(
+            hash(&self.hash_builder, item),
+            self.hashes,
+            self.counters.len(),
+        )
+    }
+
+    /// Adds an item. Adding an item twice means it has to be removed twice.
+    pub fn insert(&mut self, item: &T) {
+        for i in self.indexes(item) {
+            self.counters[i] = self.counters[i].saturating_add(1);
+        }
+    }
+
+    /// Returns `true` if the item may be in the filter, and `false` if it
+    /// definitely isn't.
+    pub fn contains(&self, item: &T) -> bool {
+        self.indexes(item).all(|i| self.counters[i] != 0)
+    }
+
+    /// An upper bound on the number of times the item is in the filter,
+    /// unless it is more than 254.
+    pub fn count(&self, item: &T) -> u8 {
+        self.indexes(item)
+            .map(|i| self.counters[i])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Removes one copy of an item. Returns `false`, changing nothing, if the
+    /// item definitely wasn't in the filter.
+    ///
+    /// Only remove items that were inserted: removing a false positive takes
+    /// away counts that other items rely on, so they may no longer be found.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::probabilistic::CountingBloomFilter;
+    ///
+    /// let mut filter = CountingBloomFilter::new(100, 0.01);
+    /// filter.insert("a");
+    /// assert!(filter.remove("a"));
+    /// assert!(!filter.contains("a"));
+    /// assert!(!filter.remove("a"));
+    /// ```
+    pub fn remove(&mut self, item: &T) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+        for i in self.indexes(item) {
+            // a saturated counter has lost track of how many items share it
+            if self.counters[i] != u8::MAX {
+                self.counters[i] -= 1;
+            }
+        }
+        true
+    }
+}
+
+impl<T, S> Extend<T> for CountingBloomFilter<T, S>
+where
+    T: Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(&item);
+        }
+    }
+}
+
+/// Prints the filter's shape rather than its counters
+impl<T: ?Sized, S> Debug for CountingBloomFilter<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountingBloomFilter")
+            .field("counters", &self.counters.len())
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{test_hasher, FxBuildHasher};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// The fraction of `probes` (none of which were inserted) that `contains`
+    /// claims are present
+    fn observed_rate(contains: impl Fn(&u64) -> bool, probes: &[u64]) -> f64 {
+        probes.iter().filter(|p| contains(p)).count() as f64 / probes.len() as f64
+    }
+
+    /// `n` distinct random keys, and `probes` more that are distinct from those
+    fn keys(rng: &mut StdRng, n: usize, probes: usize) -> (Vec<u64>, Vec<u64>) {
+        // even keys are inserted and odd keys probed
+        let inserted = (0..n).map(|_| rng.gen::<u64>() & !1).collect();
+        let probes = (0..probes).map(|_| rng.gen::<u64>() | 1).collect();
+        (inserted, probes)
+    }
+
+    #[test]
+    fn sizing_matches_the_formulas() {
+        let f = BloomFilter::<u64>::new(1_000_000, 0.01);
+        assert_eq!(f.hashes(), 7);
+        // 9.585 bits per item
+        assert!((f.bits() as f64 / 1e6 - 9.585).abs() < 0.01);
+        let f = BloomFilter::<u64>::new(1_000_000, 0.001);
+        assert_eq!(f.hashes(), 10);
+        assert!((f.bits() as f64 / 1e6 - 14.378).abs() < 0.01);
+        // a filter for no items still works
+        let mut f = BloomFilter::new(0, 0.5);
+        f.insert(&1);
+        assert!(f.contains(&1));
+    }
+
+    #[test]
+    fn false_positive_rate_meets_the_target() {
+        let mut rng = StdRng::seed_from_u64(0xb100);
+        for &target in &[0.1, 0.01, 0.001] {
+            let n = 20_000;
+            let (inserted, probes) = keys(&mut rng, n, 200_000);
+            let mut f = BloomFilter::with_hasher(n, target, test_hasher());
+            f.extend(inserted.iter().copied());
+            assert!(inserted.iter().all(|k| f.contains(k)), "no false negatives");
+            let observed = observed_rate(|k| f.contains(k), &probes);
+            assert!(
+                observed < target * 1.25,
+                "target {}, observed {}",
+                target,
+                observed
+            );
+            // the filter's own estimate agrees with what was observed
+            let predicted = f.false_positive_rate();
+            assert!((observed - predicted).abs() < target * 0.25);
+        }
+    }
+
+    #[test]
+    fn a_weak_hasher_is_mixed_well_enough() {
+        // FxHash of sequential integers differs only in a few bits
+        let n = 10_000;
+        let mut f = BloomFilter::with_hasher(n, 0.01, FxBuildHasher::default());
+        f.extend(0..n as u64);
+        let probes: Vec<u64> = (n as u64..11 * n as u64).collect();
+        assert!(observed_rate(|k| f.contains(k), &probes) < 0.0125);
+    }
+
+    #[test]
+    fn overfilling_raises_the_rate() {
+        let mut rng = StdRng::seed_from_u64(0xb101);
+        let (inserted, probes) = keys(&mut rng, 10_000, 50_000);
+        let mut f = BloomFilter::with_hasher(1000, 0.01, test_hasher());
+        f.extend(inserted.iter().copied());
+        assert!(observed_rate(|k| f.contains(k), &probes) > 0.5);
+    }
+
+    #[test]
+    fn estimated_len_is_close() {
+        let mut rng = StdRng::seed_from_u64(0xb102);
+        let mut f = BloomFilter::with_hasher(100_000, 0.01, test_hasher());
+        for &n in &[100, 10_000, 50_000] {
+            f.clear();
+            assert!(f.is_empty());
+            for _ in 0..n {
+                f.insert(&rng.gen::<u64>());
+            }
+            let error = (f.estimated_len() - n as f64).abs() / n as f64;
+            assert!(error < 0.02, "{} items, relative error {}", n, error);
+        }
+    }
+
+    #[test]
+    fn insert_reports_new_items() {
+        let mut f = BloomFilter::with_hasher(100, 0.001, test_hasher());
+        assert!(f.insert("a"));
+        assert!(!f.insert("a"));
+        assert!(f.insert("b"));
+    }
+
+    #[test]
+    fn union_is_the_filter_of_both_sets() {
+        let mut a = BloomFilter::with_hasher(1000, 0.01, test_hasher());
+        let mut b = BloomFilter::with_hasher(1000, 0.01, test_hasher());
+        let mut both = BloomFilter::with_hasher(1000, 0.01, test_hasher());
+        a.extend(0..500u32);
+        b.extend(300..800u32);
+        both.extend(0..800u32);
+        a.union(&b);
+        assert_eq!(a.bits, both.bits);
+    }
+
+    #[test]
+    fn intersection_keeps_the_common_items() {
+        let mut rng = StdRng::seed_from_u64(0xb103);
+        let mut a = BloomFilter::with_hasher(2000, 0.01, test_hasher());
+        let mut b = BloomFilter::with_hasher(2000, 0.01, test_hasher());
+        a.extend(0..1000u64);
+        b.extend(500..1500u64);
+        a.intersection(&b);
+        assert!((500..1000).all(|k| a.contains(&k)));
+        // keys only in one filter are mostly gone
+        let gone = (0..500)
+            .chain(1000..1500)
+            .filter(|k| !a.contains(k))
+            .count();
+        assert!(gone > 950);
+        let probes: Vec<u64> = (0..10_000).map(|_| rng.gen_range(2000..u64::MAX)).collect();
+        assert!(observed_rate(|k| a.contains(k), &probes) < 0.01);
+    }
+
+    #[test]
+    #[should_panic(expected = "share a hasher")]
+    fn combining_differently_hashed_filters_panics() {
+        let mut a = BloomFilter::<u8>::new(100, 0.01);
+        let b = BloomFilter::<u8>::new(100, 0.01);
+        a.union(&b);
+    }
+
+    #[test]
+    #[should_panic(expected = "same number of bits")]
+    fn combining_differently_sized_filters_panics() {
+        let mut a = BloomFilter::<u8, _>::with_hasher(100, 0.01, test_hasher());
+        let b = BloomFilter::with_hasher(200, 0.01, test_hasher());
+        a.intersection(&b);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_certain_false_positive_rate() {
+        BloomFilter::<u8>::new(100, 1.0);
+    }
+
+    #[test]
+    fn counting_filter_removes_items() {
+        let mut rng = StdRng::seed_from_u64(0xb104);
+        let n = 10_000;
+        let (inserted, probes) = keys(&mut rng, n, 100_000);
+        let mut f = CountingBloomFilter::with_hasher(n, 0.01, test_hasher());
+        f.extend(inserted.iter().copied());
+        assert!(observed_rate(|k| f.contains(k), &probes) < 0.0125);
+
+        // remove half: the rest are all still there, and the removed half
+        // look like any other absent key
+        let (removed, kept) = inserted.split_at(n / 2);
+        for k in removed {
+            assert!(f.remove(k));
+        }
+        assert!(kept.iter().all(|k| f.contains(k)));
+        let rate = observed_rate(|k| f.contains(k), removed);
+        assert!(rate < 0.002, "{} of the removed keys remain", rate);
+
+        for k in kept {
+            f.remove(k);
+        }
+        assert!(f.is_empty());
+    }
+
+    #[test]
+    fn counting_filter_counts_duplicates() {
+        let mut f = CountingBloomFilter::with_hasher(100, 0.01, test_hasher());
+        f.insert("a");
+        f.insert("a");
+        assert_eq!(f.count("a"), 2);
+        assert!(f.remove("a"));
+        assert!(f.contains("a"));
+        assert!(f.remove("a"));
+        assert!(!f.contains("a"));
+        assert!(!f.remove("a"));
+        assert_eq!(f.count("a"), 0);
+    }
+
+    #[test]
+    fn saturated_counters_never_cause_false_negatives() {
+        let mut f = CountingBloomFilter::with_hasher(100, 0.01, test_hasher());
+        for _ in 0..300 {
+            f.insert("hot");
+        }
+        f.insert("cold");
+        assert_eq!(f.count("hot"), u8::MAX);
+        for _ in 0..300 {
+            f.remove("hot");
+        }
+        // the counters "hot" saturated no longer know how many items share
+        // them, so it stays, and so does anything sharing them
+        assert!(f.contains("hot"));
+        assert!(f.contains("cold"));
+    }
+}
@@ -0,0 +1,337 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The [Count-Min sketch][1] of Cormode and Muthukrishnan: approximate
+//! frequencies over a stream.
+//!
+//! The sketch is a grid of counters, `depth` rows of `width`, with a hash
+//! function per row. Adding an item increments one counter in every row, the
+//! one its hash picks. Every counter an item touches has been incremented at
+//! least once per occurrence of the item, and perhaps by other items too, so
+//! the smallest of them is an estimate that is never too low.
+//!
+//! With `width = ⌈e / ε⌉` and `depth = ⌈ln(1 / δ)⌉`, the estimate is also at
+//! most `ε N` too high, where `N` is the total of all counts, except with
+//! probability `δ`. The space depends only on ε and δ, not on how many
+//! distinct items there are.
+//!
+//! [1]: http://dimacs.rutgers.edu/~graham/pubs/papers/cm-full.pdf
+use super::{hash, indexes, same_hasher};
+use std::{
+    collections::hash_map::RandomState,
+    f64::consts::E,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Approximate counts of `T`s, never underestimated. See the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct CountMinSketch<T: ?Sized, S = RandomState> {
+    /// `depth` rows of `width` counters
+    counters: Vec<u64>,
+    width: usize,
+    depth: usize,
+    /// The sum of every count added, `N`
+    total: u64,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> CountMinSketch<T, RandomState> {
+    /// Constructs an empty sketch whose estimates are at most `epsilon` times
+    /// the total count too high, with probability at least `1 - delta`.
+    ///
+    /// ## Panics
+    /// If `epsilon` or `delta` is not strictly between zero and one.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::probabilistic::CountMinSketch;
+    ///
+    /// let sketch = CountMinSketch::<str>::new(0.001, 0.01);
+    /// assert_eq!(sketch.width(), 2719);
+    /// assert_eq!(sketch.depth(), 5);
+    /// ```
+    pub fn new(epsilon: f64, delta: f64) -> Self {
+        Self::with_hasher(epsilon, delta, RandomState::new())
+    }
+}
+
+impl<T: ?Sized, S> CountMinSketch<T, S> {
+    /// Like [`CountMinSketch::new`], hashing items with `hash_builder`.
+    /// Sketches that are to be merged must share a hasher.
+    pub fn with_hasher(epsilon: f64, delta: f64, hash_builder: S) -> Self {
+        assert!(
+            epsilon > 0.0 && epsilon < 1.0,
+            "epsilon must be between zero and one"
+        );
+        assert!(
+            delta > 0.0 && delta < 1.0,
+            "delta must be between zero and one"
+        );
+        let width = (E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as usize;
+        CountMinSketch {
+            counters: vec![0; width * depth],
+            width,
+            depth,
+            total: 0,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The sketch's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of counters in each row
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of rows, each with its own hash function
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The sum of every count added so far
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The most that any estimate is too high by, except with probability
+    /// `delta`: `ε N`
+    pub fn error_bound(&self) -> f64 {
+        E / self.width as f64 * self.total as f64
+    }
+
+    /// Forgets every count.
+    pub fn clear(&mut self) {
+        for c in &mut self.counters {
+            *c = 0;
+        }
+        self.total = 0;
+    }
+
+    /// Adds the counts of `other` to this sketch, as if every item added to
+    /// it had been added here too.
+    ///
+    /// ## Panics
+    /// If the sketches differ in width, depth or hasher.
+    pub fn merge(&mut self, other: &Self)
+    where
+        S: BuildHasher,
+    {
+        assert!(
+            self.width == other.width && self.depth == other.depth,
+            "sketches must have the same width and depth"
+        );
+        assert!(
+            same_hasher(&self.hash_builder, &other.hash_builder),
+            "sketches must share a hasher"
+        );
+        for (c, other) in self.counters.iter_mut().zip(&other.counters) {
+            *c = c.saturating_add(*other);
+        }
+        self.total = self.total.saturating_add(other.total);
+    }
+}
+
+impl<T, S> CountMinSketch<T, S>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// The counter that `item` hashes to in each row, as an index into
+    /// `counters`
+    fn cells(&self, item: &T) -> impl Iterator<Item = usize> {
+        let width = self.width;
+        indexes(hash(&self.hash_builder, item), self.depth, width)
+            .enumerate()
+            .map(move |(row, i)| row * width + i)
+    }
+
+    /// Counts `count` more occurrences of `item`.
+    pub fn add(&mut self, item: &T, count: u64) {
+        for i in self.cells(item) {
+            self.counters[i] = self.counters[i].saturating_add(count);
+        }
+        self.total = self.total.saturating_add(count);
+    }
+
+    /// Counts one more occurrence of `item`.
+    pub fn increment(&mut self, item: &T) {
+        self.add(item, 1);
+    }
+
+    /// An estimate of how often `item` has occurred. Never less than the true
+    /// count.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::probabilistic::CountMinSketch;
+    ///
+    /// let mut sketch = CountMinSketch::new(0.01, 0.01);
+    /// for word in "a rose is a rose is a rose".split(' ') {
+    ///     sketch.increment(word);
+    /// }
+    /// assert!(sketch.estimate("rose") >= 3);
+    /// assert!(sketch.estimate("a") as f64 <= 3.0 + sketch.error_bound());
+    /// ```
+    pub fn estimate(&self, item: &T) -> u64 {
+        self.cells(item)
+            .map(|i| self.counters[i])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+impl<T, S> Extend<T> for CountMinSketch<T, S>
+where
+    T: Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.increment(&item);
+        }
+    }
+}
+
+/// Prints the sketch's shape rather than its counters
+impl<T: ?Sized, S> Debug for CountMinSketch<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountMinSketch")
+            .field("width", &self.width)
+            .field("depth", &self.depth)
+            .field("total", &self.total)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::test_hasher;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    /// A skewed stream, like real traffic: item `i` of `n` is drawn with
+    /// probability proportional to `1 / (i + 1)`
+    fn zipf_stream(rng: &mut StdRng, n: u64, len: usize) -> Vec<u64> {
+        let weights: Vec<f64> = (0..n).map(|i| 1.0 / (i + 1) as f64).collect();
+        let total: f64 = weights.iter().sum();
+        let mut cumulative = Vec::with_capacity(weights.len());
+        let mut sum = 0.0;
+        for w in weights {
+            sum += w / total;
+            cumulative.push(sum);
+        }
+        (0..len)
+            .map(|_| {
+                let x: f64 = rng.gen();
+                cumulative.partition_point(|&c| c < x).min(n as usize - 1) as u64
+            })
+            .collect()
+    }
+
+    fn true_counts(stream: &[u64]) -> HashMap<u64, u64> {
+        let mut counts = HashMap::new();
+        for &item in stream {
+            *counts.entry(item).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn estimates_are_within_the_bounds() {
+        let mut rng = StdRng::seed_from_u64(0xc0de);
+        let (epsilon, delta) = (0.001, 0.01);
+        let stream = zipf_stream(&mut rng, 100_000, 200_000);
+        let counts = true_counts(&stream);
+        let mut sketch = CountMinSketch::with_hasher(epsilon, delta, test_hasher());
+        sketch.extend(stream.iter().copied());
+        assert_eq!(sketch.total(), stream.len() as u64);
+
+        let bound = epsilon * stream.len() as f64;
+        let mut over = 0;
+        for (item, &count) in &counts {
+            let estimate = sketch.estimate(item);
+            assert!(estimate >= count, "never underestimates");
+            if (estimate - count) as f64 > bound {
+                over += 1;
+            }
+        }
+        let rate = over as f64 / counts.len() as f64;
+        assert!(rate <= delta, "{} of estimates broke the bound", rate);
+        // the errors are small next to the heavy hitters' counts, so they come
+        // out in the right order
+        let mut heaviest: Vec<u64> = (0..10).collect();
+        heaviest.sort_by_key(|item| std::cmp::Reverse(sketch.estimate(item)));
+        assert_eq!(heaviest, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn items_never_added_estimate_low() {
+        let mut rng = StdRng::seed_from_u64(0xc0df);
+        let mut sketch = CountMinSketch::with_hasher(0.001, 0.001, test_hasher());
+        for _ in 0..100_000 {
+            sketch.increment(&rng.gen_range(0..1_000_000u64));
+        }
+        let bound = sketch.error_bound();
+        let over = (2_000_000..2_010_000u64)
+            .filter(|k| sketch.estimate(k) as f64 > bound)
+            .count();
+        assert!(over <= 10);
+    }
+
+    #[test]
+    fn add_counts_many_at_once() {
+        let mut sketch = CountMinSketch::with_hasher(0.01, 0.01, test_hasher());
+        sketch.add("a", 1_000);
+        sketch.add("b", 5);
+        sketch.increment("b");
+        assert_eq!(sketch.estimate("a"), 1_000);
+        assert_eq!(sketch.estimate("b"), 6);
+        assert_eq!(sketch.total(), 1_006);
+        sketch.clear();
+        assert_eq!(sketch.estimate("a"), 0);
+        assert_eq!(sketch.total(), 0);
+    }
+
+    #[test]
+    fn merging_equals_counting_everything_in_one() {
+        let mut rng = StdRng::seed_from_u64(0xc0e0);
+        let stream = zipf_stream(&mut rng, 1000, 20_000);
+        let (left, right) = stream.split_at(stream.len() / 3);
+        let mut a = CountMinSketch::with_hasher(0.01, 0.01, test_hasher());
+        let mut b = CountMinSketch::with_hasher(0.01, 0.01, test_hasher());
+        let mut both = CountMinSketch::with_hasher(0.01, 0.01, test_hasher());
+        a.extend(left.iter().copied());
+        b.extend(right.iter().copied());
+        both.extend(stream.iter().copied());
+        a.merge(&b);
+        assert_eq!(a.counters, both.counters);
+        assert_eq!(a.total(), both.total());
+    }
+
+    #[test]
+    #[should_panic(expected = "share a hasher")]
+    fn merging_differently_hashed_sketches_panics() {
+        let mut a = CountMinSketch::<u8>::new(0.01, 0.01);
+        a.merge(&CountMinSketch::new(0.01, 0.01));
+    }
+}
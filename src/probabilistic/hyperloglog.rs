@@ -0,0 +1,356 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [HyperLogLog][1]: approximate counting of distinct items.
+//!
+//! In a stream of uniformly random hashes, a hash that starts with `r` zero
+//! bits turns up about once in every `2^r` distinct items, so the longest run
+//! of leading zeros seen says roughly how many distinct items there were. One
+//! such observation is very noisy, so HyperLogLog splits the stream in `m`
+//! using the first `p` bits of each hash, keeps the longest run for each part
+//! in a *register*, and combines the registers with a harmonic mean.
+//!
+//! The standard error of the estimate is about `1.04 / √m`: 1.6% for the
+//! default `p = 12`, in 4 KiB of registers, whether there were a thousand
+//! distinct items or a billion. While many registers are still zero, the
+//! estimate switches to *linear counting* from the number of empty registers,
+//! which is more accurate there.
+//!
+//! [1]: http://algo.inria.fr/flajolet/Publications/FlFuGaMe07.pdf
+use super::{hash, same_hasher};
+use std::{
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The precision used by [`HyperLogLog::new`]
+const DEFAULT_PRECISION: u8 = 12;
+
+/// An estimate of the number of distinct `T`s seen. See the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct HyperLogLog<T: ?Sized, S = RandomState> {
+    /// `2^precision` registers, each the most leading zeros seen (plus one)
+    /// among the hashes sent to it
+    registers: Vec<u8>,
+    precision: u8,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> HyperLogLog<T, RandomState> {
+    /// Constructs an empty counter with 4096 registers, for a standard error of
+    /// about 1.6%.
+    ///
+    /// ## Example:
+    /// ```
+    /// use tinystd::probabilistic::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new();
+    /// for i in 0..100_000u32 {
+    ///     hll.insert(&(i % 25_000));
+    /// }
+    /// let error = (hll.estimate() - 25_000.0).abs() / 25_000.0;
+    /// assert!(error < 5.0 * hll.standard_error());
+    /// ```
+    pub fn new() -> Self {
+        Self::with_precision(DEFAULT_PRECISION)
+    }
+
+    /// Constructs an empty counter with `2^precision` registers.
+    ///
+    /// ## Panics
+    /// If `precision` is not between 4 and 18.
+    pub fn with_precision(precision: u8) -> Self {
+        Self::with_precision_and_hasher(precision, RandomState::new())
+    }
+}
+
+impl<T: ?Sized, S> HyperLogLog<T, S> {
+    /// Like [`HyperLogLog::new`], hashing items with `hash_builder`. Counters
+    /// that are to be merged must share a hasher.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_precision_and_hasher(DEFAULT_PRECISION, hash_builder)
+    }
+
+    /// Combines [`HyperLogLog::with_precision`] and
+    /// [`HyperLogLog::with_hasher`].
+    pub fn with_precision_and_hasher(precision: u8, hash_builder: S) -> Self {
+        assert!(
+            (4..=18).contains(&precision),
+            "the precision must be between 4 and 18"
+        );
+        HyperLogLog {
+            registers: vec![0; 1 << precision],
+            precision,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The counter's [`BuildHasher`]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The number of bits of each hash that pick a register, `p`
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The relative standard error of [`HyperLogLog::estimate`], `1.04 / √m`
+    pub fn standard_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+
+    /// Returns `true` if nothing has been inserted since the counter was
+    /// created or cleared.
+    pub fn is_empty(&self) -> bool {
+        self.registers.iter().all(|&r| r == 0)
+    }
+
+    /// Forgets every item.
+    pub fn clear(&mut self) {
+        for r in &mut self.registers {
+            *r = 0;
+        }
+    }
+
+    /// An estimate of the number of distinct items inserted
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            // with 64-bit hashes, the large-range correction of the original
+            // paper (for when 32-bit hashes start to collide) isn't needed
+            raw
+        }
+    }
+
+    /// Adds the items of `other` to this counter, as if every item inserted
+    /// into it had been inserted here too.
+    ///
+    /// ## Panics
+    /// If the counters differ in precision or hasher.
+    ///
+    /// ## Example:
+    /// ```
+    /// use std::collections::hash_map::RandomState;
+    /// use tinystd::probabilistic::HyperLogLog;
+    ///
+    /// let hasher = RandomState::new();
+    /// let mut monday = HyperLogLog::with_hasher(hasher.clone());
+    /// let mut tuesday = HyperLogLog::with_hasher(hasher);
+    /// monday.extend(0..600);
+    /// tuesday.extend(400..1000);
+    /// monday.merge(&tuesday);
+    /// let error = (monday.estimate() - 1000.0).abs() / 1000.0;
+    /// assert!(error < 5.0 * monday.standard_error());
+    /// ```
+    pub fn merge(&mut self, other: &Self)
+    where
+        S: BuildHasher,
+    {
+        assert_eq!(
+            self.precision, other.precision,
+            "counters must have the same precision"
+        );
+        assert!(
+            same_hasher(&self.hash_builder, &other.hash_builder),
+            "counters must share a hasher"
+        );
+        for (r, &other) in self.registers.iter_mut().zip(&other.registers) {
+            *r = (*r).max(other);
+        }
+    }
+}
+
+impl<T, S> HyperLogLog<T, S>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Counts an item, if it hasn't been seen before.
+    pub fn insert(&mut self, item: &T) {
+        let hash = hash(&self.hash_builder, item);
+        let p = self.precision as u32;
+        let register = (hash >> (64 - p)) as usize;
+        // the bits that didn't pick the register, with a one after them so
+        // that a run of zeros can't go on past them
+        let rest = (hash << p) | (1 << (p - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        let r = &mut self.registers[register];
+        *r = (*r).max(rank);
+    }
+}
+
+impl<T, S> Default for HyperLogLog<T, S>
+where
+    T: ?Sized,
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<T, S> Extend<T> for HyperLogLog<T, S>
+where
+    T: Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(&item);
+        }
+    }
+}
+
+/// Prints the counter's precision and estimate rather than its registers
+impl<T: ?Sized, S> Debug for HyperLogLog<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HyperLogLog")
+            .field("precision", &self.precision)
+            .field("estimate", &self.estimate())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{test_hasher, FxBuildHasher};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn relative_error(estimate: f64, n: usize) -> f64 {
+        (estimate - n as f64).abs() / n as f64
+    }
+
+    #[test]
+    fn estimates_are_within_three_standard_errors() {
+        let mut rng = StdRng::seed_from_u64(0x4411);
+        for &precision in &[8, 12, 14] {
+            for &n in &[10, 1000, 50_000, 500_000] {
+                let mut hll = HyperLogLog::with_precision_and_hasher(precision, test_hasher());
+                for _ in 0..n {
+                    hll.insert(&rng.gen::<u64>());
+                }
+                let error = relative_error(hll.estimate(), n);
+                assert!(
+                    error < 3.0 * hll.standard_error(),
+                    "precision {}, {} items: relative error {}",
+                    precision,
+                    n,
+                    error
+                );
+            }
+        }
+    }
+
+    /// Averaged over many independent counters, the error should match the
+    /// advertised standard error
+    #[test]
+    fn the_standard_error_is_as_advertised() {
+        let mut rng = StdRng::seed_from_u64(0x4412);
+        let (runs, n) = (100, 10_000);
+        let mut squares = 0.0;
+        for _ in 0..runs {
+            let mut hll = HyperLogLog::with_precision_and_hasher(8, test_hasher());
+            for _ in 0..n {
+                hll.insert(&rng.gen::<u64>());
+            }
+            squares += relative_error(hll.estimate(), n).powi(2);
+        }
+        let rmse = (squares / runs as f64).sqrt();
+        let expected = 1.04 / 16.0;
+        assert!(
+            (rmse - expected).abs() < expected * 0.25,
+            "rmse {}, expected {}",
+            rmse,
+            expected
+        );
+    }
+
+    #[test]
+    fn duplicates_are_not_counted() {
+        let mut hll = HyperLogLog::with_hasher(test_hasher());
+        for _ in 0..10 {
+            hll.extend(0..10_000u32);
+        }
+        assert!(relative_error(hll.estimate(), 10_000) < 0.05);
+        hll.clear();
+        assert!(hll.is_empty());
+        assert_eq!(hll.estimate(), 0.0);
+    }
+
+    /// Linear counting is only off when two items share a register, which is
+    /// rare while there are far fewer items than registers
+    #[test]
+    fn small_counts_are_nearly_exact() {
+        let mut hll = HyperLogLog::with_hasher(test_hasher());
+        for n in 1..=100u32 {
+            hll.insert(&n);
+            let error = (hll.estimate() - n as f64).abs();
+            assert!(
+                error < 1.0 + 0.05 * n as f64,
+                "{} items, off by {}",
+                n,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn a_weak_hasher_is_mixed_well_enough() {
+        let mut hll = HyperLogLog::with_hasher(FxBuildHasher::default());
+        hll.extend(0..100_000u64);
+        assert!(relative_error(hll.estimate(), 100_000) < 3.0 * hll.standard_error());
+    }
+
+    #[test]
+    fn merging_equals_counting_everything_in_one() {
+        let mut a = HyperLogLog::with_hasher(test_hasher());
+        let mut b = HyperLogLog::with_hasher(test_hasher());
+        let mut both = HyperLogLog::with_hasher(test_hasher());
+        a.extend(0..60_000u64);
+        b.extend(40_000..100_000u64);
+        both.extend(0..100_000u64);
+        a.merge(&b);
+        assert_eq!(a.registers, both.registers);
+        assert!(relative_error(a.estimate(), 100_000) < 3.0 * a.standard_error());
+    }
+
+    #[test]
+    #[should_panic(expected = "same precision")]
+    fn merging_different_precisions_panics() {
+        let mut a = HyperLogLog::<u8, _>::with_precision_and_hasher(10, test_hasher());
+        a.merge(&HyperLogLog::with_precision_and_hasher(11, test_hasher()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_tiny_precision() {
+        HyperLogLog::<u8>::with_precision(2);
+    }
+}
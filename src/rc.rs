@@ -92,7 +92,6 @@ impl<T> Drop for Rc<T> {
         let current = inner.refcount.get();
         if current == 1 {
             // this is the last ref
-            drop(inner);
             let _ = unsafe { Box::from_raw(self.inner.as_ptr()) };
         } else {
             // there are other shared refs already given out
@@ -0,0 +1,388 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A family of searches over sorted slices. [`slice::binary_search`] covers the
+//! common case; the functions here are the variations that come up once the
+//! data has been sorted with something from [`crate::sort`]:
+//!
+//! - [`lower_bound`], [`upper_bound`] and [`equal_range`], the C++-style
+//!   bounds which are well-defined even in the presence of duplicates
+//! - [`exponential_search`], which is faster than a plain binary search when
+//!   the key is near the front of a very large (or unbounded) slice
+//! - [`interpolation_search`], which guesses the position of numeric keys and
+//!   takes `O(log log n)` probes on uniformly distributed data
+//! - [`branchless_lower_bound`] and [`Eytzinger`], which trade the textbook
+//!   layout for fewer branch mispredictions and cache misses on large,
+//!   read-mostly arrays. See [Khuong and Morin][1] for the details.
+//!
+//! The searches that return a `Result` follow the convention of
+//! [`slice::binary_search`]: `Ok` holds the index of a matching element, and
+//! `Err` holds the index at which the key could be inserted to keep the slice
+//! sorted.
+//!
+//! [1]: https://arxiv.org/abs/1509.05053
+use std::{cmp::Ordering, ops::Range};
+
+/// The index of the first element that is _not less than_ `key`, or
+/// `slice.len()` if every element is less than `key`.
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::lower_bound;
+///
+/// let v = [1, 2, 2, 2, 3];
+/// assert_eq!(lower_bound(&v, &2), 1);
+/// assert_eq!(lower_bound(&v, &4), 5);
+/// ```
+pub fn lower_bound<T: Ord>(slice: &[T], key: &T) -> usize {
+    partition_point(slice, |x| x < key)
+}
+
+/// The index of the first element that is _greater than_ `key`, or
+/// `slice.len()` if no element is greater than `key`.
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::upper_bound;
+///
+/// let v = [1, 2, 2, 2, 3];
+/// assert_eq!(upper_bound(&v, &2), 4);
+/// assert_eq!(upper_bound(&v, &0), 0);
+/// ```
+pub fn upper_bound<T: Ord>(slice: &[T], key: &T) -> usize {
+    partition_point(slice, |x| x <= key)
+}
+
+/// The range of indices whose elements are equal to `key`. The range is empty
+/// (but still positioned at the insertion point) when `key` is absent.
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::equal_range;
+///
+/// let v = [1, 2, 2, 2, 3];
+/// assert_eq!(equal_range(&v, &2), 1..4);
+/// assert_eq!(equal_range(&v, &0), 0..0);
+/// ```
+pub fn equal_range<T: Ord>(slice: &[T], key: &T) -> Range<usize> {
+    let start = lower_bound(slice, key);
+    let end = start + upper_bound(&slice[start..], key);
+    start..end
+}
+
+/// The first index for which `pred` is false, assuming `pred` is true for a
+/// prefix of the slice and false for the rest.
+fn partition_point<T, P>(slice: &[T], mut pred: P) -> usize
+where
+    P: FnMut(&T) -> bool,
+{
+    let (mut lo, mut hi) = (0, slice.len());
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(&slice[mid]) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+/// Exponential (a.k.a. galloping) search: probe indices `1, 2, 4, 8, ...` until
+/// the key is bracketed, then binary search inside the bracket. This takes
+/// `O(log i)` comparisons where `i` is the position of the key, rather than
+/// `O(log n)`.
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::exponential_search;
+///
+/// let v: Vec<_> = (0..1000).map(|i| i * 2).collect();
+/// assert_eq!(exponential_search(&v, &6), Ok(3));
+/// assert_eq!(exponential_search(&v, &7), Err(4));
+/// ```
+pub fn exponential_search<T: Ord>(slice: &[T], key: &T) -> Result<usize, usize> {
+    if slice.is_empty() {
+        return Err(0);
+    }
+    let mut bound = 1;
+    while bound < slice.len() && &slice[bound] < key {
+        bound *= 2;
+    }
+    // the key is somewhere in `[bound / 2, bound]`
+    let lo = bound / 2;
+    let hi = (bound + 1).min(slice.len());
+    let i = lo + lower_bound(&slice[lo..hi], key);
+    match slice.get(i) {
+        Some(x) if x == key => Ok(i),
+        _ => Err(i),
+    }
+}
+
+/// Types whose values can be placed on a number line, which is what
+/// [`interpolation_search`] needs in order to guess where a key should be.
+pub trait Numeric: Ord {
+    fn as_i128(&self) -> i128;
+}
+
+macro_rules! numeric {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn as_i128(&self) -> i128 {
+                    *self as i128
+                }
+            }
+        )*
+    };
+}
+
+numeric!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// Interpolation search: rather than always probing the middle, probe where the
+/// key _would_ be if the values were spread evenly between the two ends of the
+/// remaining range. On uniformly distributed data this needs `O(log log n)`
+/// probes; on adversarial data it degrades to `O(n)`.
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::interpolation_search;
+///
+/// let v: Vec<u64> = (0..1000).map(|i| i * 10).collect();
+/// assert_eq!(interpolation_search(&v, &420), Ok(42));
+/// assert_eq!(interpolation_search(&v, &425), Err(43));
+/// ```
+pub fn interpolation_search<T: Numeric>(slice: &[T], key: &T) -> Result<usize, usize> {
+    if slice.is_empty() {
+        return Err(0);
+    }
+    let (mut lo, mut hi) = (0, slice.len() - 1);
+    loop {
+        // keep the invariant `slice[lo] <= key <= slice[hi]`
+        if key < &slice[lo] {
+            return Err(lo);
+        }
+        if key > &slice[hi] {
+            return Err(hi + 1);
+        }
+        let (a, b) = (slice[lo].as_i128(), slice[hi].as_i128());
+        let mid = if a == b {
+            lo
+        } else {
+            // the ratio is in `[0, 1]`, computed in floating point so that the
+            // product can't overflow for 64-bit keys
+            let ratio = (key.as_i128() - a) as f64 / (b - a) as f64;
+            lo + ((ratio * (hi - lo) as f64) as usize).min(hi - lo)
+        };
+        match slice[mid].cmp(key) {
+            Ordering::Equal => return Ok(mid),
+            Ordering::Less => lo = mid + 1,
+            Ordering::Greater => hi = mid - 1,
+        }
+    }
+}
+
+/// A binary search whose loop body has no data-dependent branch: the midpoint
+/// is selected with a conditional move, and the loop always runs exactly
+/// `⌈log₂ n⌉` times. Returns the same index as [`lower_bound`].
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::branchless_lower_bound;
+///
+/// let v = [1, 3, 5, 7, 9];
+/// assert_eq!(branchless_lower_bound(&v, &5), 2);
+/// assert_eq!(branchless_lower_bound(&v, &6), 3);
+/// ```
+pub fn branchless_lower_bound<T: Ord>(slice: &[T], key: &T) -> usize {
+    if slice.is_empty() {
+        return 0;
+    }
+    let mut base = 0;
+    let mut size = slice.len();
+    while size > 1 {
+        let half = size / 2;
+        let mid = base + half;
+        base = if &slice[mid] < key { mid } else { base };
+        size -= half;
+    }
+    base + (&slice[base] < key) as usize
+}
+
+/// A sorted array stored in [Eytzinger][1] (breadth-first, heap-like) order.
+///
+/// A binary search over a sorted array touches one element per cache line for
+/// its first several probes; in Eytzinger order the first few levels of the
+/// implicit search tree sit next to each other in memory, so the top of the
+/// search is cache-resident and the rest is easy for the hardware to prefetch.
+/// The cost is an `O(n)` rebuild whenever the data changes, which is why it
+/// suits read-mostly arrays.
+///
+/// Results are reported as indices into the _sorted_ order, so they can be used
+/// interchangeably with the other searches in this module.
+///
+/// ## Example:
+/// ```
+/// use tinystd::search::Eytzinger;
+///
+/// let sorted: Vec<_> = (0..100).map(|i| i * 3).collect();
+/// let tree = Eytzinger::from_sorted(&sorted);
+/// assert_eq!(tree.binary_search(&42), Ok(14));
+/// assert_eq!(tree.binary_search(&43), Err(15));
+/// assert_eq!(tree.lower_bound(&1_000), 100);
+/// ```
+///
+/// [1]: https://en.wikipedia.org/wiki/Binary_heap#Heap_implementation
+#[derive(Debug, Clone)]
+pub struct Eytzinger<T> {
+    /// Node `i` has its children at `2i + 1` and `2i + 2`
+    tree: Vec<T>,
+    /// The index in the sorted order of each node in `tree`
+    rank: Vec<usize>,
+}
+
+impl<T: Ord + Clone> Eytzinger<T> {
+    /// Builds the layout from an already sorted slice.
+    pub fn from_sorted(sorted: &[T]) -> Self {
+        let n = sorted.len();
+        let mut order = vec![0; n];
+        let mut next = 0;
+        fill(&mut order, &mut next, 0);
+        Eytzinger {
+            tree: order.iter().map(|&i| sorted[i].clone()).collect(),
+            rank: order,
+        }
+    }
+}
+
+/// An in-order walk of the implicit tree hands out the sorted indices
+fn fill(order: &mut [usize], next: &mut usize, node: usize) {
+    if node < order.len() {
+        fill(order, next, 2 * node + 1);
+        order[node] = *next;
+        *next += 1;
+        fill(order, next, 2 * node + 2);
+    }
+}
+
+impl<T: Ord> Eytzinger<T> {
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Same as [`lower_bound`], on the original sorted order.
+    pub fn lower_bound(&self, key: &T) -> usize {
+        match self.find(key) {
+            Some(node) => self.rank[node],
+            None => self.len(),
+        }
+    }
+
+    /// Same as [`slice::binary_search`], on the original sorted order.
+    pub fn binary_search(&self, key: &T) -> Result<usize, usize> {
+        match self.find(key) {
+            Some(node) if &self.tree[node] == key => Ok(self.rank[node]),
+            Some(node) => Err(self.rank[node]),
+            None => Err(self.len()),
+        }
+    }
+
+    /// Descends to a leaf, going right whenever the node is less than `key`.
+    /// The answer is the last node at which the descent went _left_, which is
+    /// recovered by stripping the trailing right turns off the path.
+    fn find(&self, key: &T) -> Option<usize> {
+        let mut i = 0;
+        while i < self.tree.len() {
+            i = 2 * i + 1 + (&self.tree[i] < key) as usize;
+        }
+        // in 1-based numbering each right turn appends a 1 bit to the path
+        let j = (i + 1) >> ((i + 1).trailing_ones() + 1);
+        j.checked_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every search in the module should agree with a linear scan
+    #[test]
+    fn agrees_with_linear_scan() {
+        let v: Vec<u32> = vec![1, 1, 2, 3, 5, 8, 13, 21, 21, 21, 34];
+        let tree = Eytzinger::from_sorted(&v);
+        for key in 0..40 {
+            let lo = v.iter().take_while(|&&x| x < key).count();
+            let hi = v.iter().take_while(|&&x| x <= key).count();
+            assert_eq!(lower_bound(&v, &key), lo);
+            assert_eq!(upper_bound(&v, &key), hi);
+            assert_eq!(equal_range(&v, &key), lo..hi);
+            assert_eq!(branchless_lower_bound(&v, &key), lo);
+            assert_eq!(tree.lower_bound(&key), lo);
+
+            for result in [
+                exponential_search(&v, &key),
+                interpolation_search(&v, &key),
+                tree.binary_search(&key),
+            ] {
+                match result {
+                    Ok(i) => assert_eq!(v[i], key),
+                    Err(i) => {
+                        assert_eq!(lo, hi, "{} is present but was not found", key);
+                        assert_eq!(i, lo);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_slices() {
+        let v: Vec<i64> = vec![];
+        assert_eq!(lower_bound(&v, &1), 0);
+        assert_eq!(upper_bound(&v, &1), 0);
+        assert_eq!(equal_range(&v, &1), 0..0);
+        assert_eq!(exponential_search(&v, &1), Err(0));
+        assert_eq!(interpolation_search(&v, &1), Err(0));
+        assert_eq!(branchless_lower_bound(&v, &1), 0);
+        let tree = Eytzinger::from_sorted(&v);
+        assert!(tree.is_empty());
+        assert_eq!(tree.binary_search(&1), Err(0));
+    }
+
+    #[test]
+    fn interpolation_handles_extreme_keys() {
+        let v = [i64::MIN, -1, 0, 1, i64::MAX];
+        for (i, key) in v.iter().enumerate() {
+            assert_eq!(interpolation_search(&v, key), Ok(i));
+        }
+        assert_eq!(interpolation_search(&v, &2), Err(4));
+        let v = [u64::MAX; 4];
+        assert!(interpolation_search(&v, &u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn eytzinger_of_every_size() {
+        for n in 0..64 {
+            let v: Vec<_> = (0..n).map(|i| i * 2).collect();
+            let tree = Eytzinger::from_sorted(&v);
+            assert_eq!(tree.len(), n);
+            for i in 0..n {
+                assert_eq!(tree.binary_search(&(i * 2)), Ok(i));
+                assert_eq!(tree.binary_search(&(i * 2 + 1)), Err(i + 1));
+            }
+        }
+    }
+}
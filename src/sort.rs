@@ -14,21 +14,66 @@
 //!
 //! [1]: https://youtu.be/h4RkCyJyXmM
 mod bubble;
+pub mod compare;
+mod cycle;
+pub mod float;
 mod insertion;
+pub mod merge;
 mod quick;
 mod selection;
 
 pub use bubble::Bubble;
+pub use cycle::Cycle;
 pub use insertion::Insertion;
 pub use quick::Quick;
 pub use selection::Selection;
 
+use std::cmp::Ordering;
+
 /// A shared sorting trait
 pub trait Sorter {
+    /// Any mutable `ref<slice<T>>` is sortable, given a comparator that
+    /// totally orders `T`. See [`compare`] for ways of building comparators.
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering;
+
     /// Any mutable `ref<slice<T>>` that is `Ord` is sortable.
     fn sort<T>(&self, slice: &mut [T])
     where
-        T: Ord;
+        T: Ord,
+    {
+        self.sort_by(slice, T::cmp);
+    }
+
+    /// Sorts by the `Ord` of a key extracted from each element. The key is
+    /// recomputed on every comparison.
+    fn sort_by_key<T, K, F>(&self, slice: &mut [T], mut key: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(slice, |a, b| key(a).cmp(&key(b)));
+    }
+}
+
+/// Sorters that can report how many times they wrote an element into the
+/// slice, for when writes are the expensive part of sorting (e.g. flash or
+/// memory-mapped storage). A swap counts as two writes.
+pub trait CountWrites: Sorter {
+    /// Sorts the slice with a comparator, returning the number of element
+    /// writes performed.
+    fn sort_by_counting_writes<T, F>(&self, slice: &mut [T], compare: F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering;
+
+    /// Sorts the slice, returning the number of element writes performed.
+    fn sort_counting_writes<T>(&self, slice: &mut [T]) -> usize
+    where
+        T: Ord,
+    {
+        self.sort_by_counting_writes(slice, T::cmp)
+    }
 }
 
 #[cfg(test)]
@@ -37,11 +82,11 @@ mod tests {
     /// A simple smoke check for the Sorter trait using [`slice::sort`]
     struct StdSorter;
     impl Sorter for StdSorter {
-        fn sort<T>(&self, slice: &mut [T])
+        fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
         where
-            T: Ord,
+            F: FnMut(&T, &T) -> Ordering,
         {
-            slice.sort();
+            slice.sort_by(compare);
         }
     }
 
@@ -51,4 +96,30 @@ mod tests {
         StdSorter.sort(&mut items);
         assert_eq!(items, &[1, 2, 3, 4]);
     }
+
+    #[test]
+    fn sort_by_and_sort_by_key() {
+        let input = vec![(1, 'd'), (3, 'a'), (2, 'c'), (5, 'b'), (4, 'e')];
+        let descending = vec![(5, 'b'), (4, 'e'), (3, 'a'), (2, 'c'), (1, 'd')];
+        let by_letter = vec![(3, 'a'), (5, 'b'), (2, 'c'), (1, 'd'), (4, 'e')];
+
+        macro_rules! check {
+            ($($s:expr),*) => {$(
+                let mut v = input.clone();
+                $s.sort_by(&mut v, |a, b| b.cmp(a));
+                assert_eq!(v, descending);
+                let mut v = input.clone();
+                $s.sort_by_key(&mut v, |&(_, c)| c);
+                assert_eq!(v, by_letter);
+            )*};
+        }
+        check!(
+            Bubble,
+            Insertion { smart: true },
+            Insertion { smart: false },
+            Selection,
+            Quick,
+            Cycle
+        );
+    }
 }
@@ -15,15 +15,15 @@ use super::*;
 
 pub struct Bubble;
 impl Sorter for Bubble {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
         let mut swapped = true;
         while swapped {
             swapped = false;
             for i in 1..slice.len() {
-                if slice[i - 1] > slice[i] {
+                if compare(&slice[i - 1], &slice[i]) == Ordering::Greater {
                     slice.swap(i, i - 1);
                     swapped = true;
                 }
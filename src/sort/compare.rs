@@ -0,0 +1,253 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Building comparators for [`Sorter::sort_by`](super::Sorter::sort_by).
+//!
+//! A comparator is any `Fn(&T, &T) -> Ordering`. The [`Comparator`] trait is
+//! implemented for all of them and adds combinators, so a multi-column sort
+//! reads the way you would describe it:
+//!
+//! ```
+//! use tinystd::sort::{compare::*, Quick, Sorter};
+//!
+//! struct Row {
+//!     team: &'static str,
+//!     score: u32,
+//!     seed: Option<u32>,
+//! }
+//!
+//! let mut rows = vec![
+//!     Row { team: "b", score: 10, seed: None },
+//!     Row { team: "a", score: 7, seed: Some(2) },
+//!     Row { team: "b", score: 12, seed: Some(1) },
+//!     Row { team: "a", score: 7, seed: None },
+//! ];
+//!
+//! // team ascending, then score descending, then unseeded rows first
+//! let order = by_key(|r: &Row| r.team)
+//!     .then_by(by_key(|r: &Row| r.score).reversed())
+//!     .then_by(by_field(|r: &Row| &r.seed, u32::cmp.nulls_first()));
+//! Quick.sort_by(&mut rows, order);
+//!
+//! let summary: Vec<_> = rows.iter().map(|r| (r.team, r.score, r.seed)).collect();
+//! assert_eq!(
+//!     summary,
+//!     vec![("a", 7, None), ("a", 7, Some(2)), ("b", 12, Some(1)), ("b", 10, None)]
+//! );
+//! ```
+//!
+//! The string orderings at the bottom of the module are locale-independent:
+//! they depend only on Unicode scalar values and Unicode's own (not the
+//! locale's) lowercase mappings, so they sort the same way on every machine.
+use std::cmp::Ordering;
+
+/// Combinators for comparators. Implemented for every `Fn(&T, &T) -> Ordering`.
+pub trait Comparator<T: ?Sized>: Fn(&T, &T) -> Ordering + Sized {
+    /// Breaks ties in `self` using `next`.
+    fn then_by<C>(self, next: C) -> impl Fn(&T, &T) -> Ordering
+    where
+        C: Fn(&T, &T) -> Ordering,
+    {
+        move |a, b| self(a, b).then_with(|| next(a, b))
+    }
+
+    /// The opposite order.
+    fn reversed(self) -> impl Fn(&T, &T) -> Ordering {
+        move |a, b| self(b, a)
+    }
+
+    /// Lifts the comparator to `Option<T>`, with `None` before any `Some`.
+    fn nulls_first(self) -> impl Fn(&Option<T>, &Option<T>) -> Ordering
+    where
+        T: Sized,
+    {
+        move |a, b| match (a, b) {
+            (Some(a), Some(b)) => self(a, b),
+            (a, b) => a.is_some().cmp(&b.is_some()),
+        }
+    }
+
+    /// Lifts the comparator to `Option<T>`, with `None` after any `Some`.
+    fn nulls_last(self) -> impl Fn(&Option<T>, &Option<T>) -> Ordering
+    where
+        T: Sized,
+    {
+        move |a, b| match (a, b) {
+            (Some(a), Some(b)) => self(a, b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    }
+}
+
+impl<T: ?Sized, F> Comparator<T> for F where F: Fn(&T, &T) -> Ordering {}
+
+/// Compares by the `Ord` of a key computed from each element.
+pub fn by_key<T, K, F>(key: F) -> impl Fn(&T, &T) -> Ordering
+where
+    T: ?Sized,
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    move |a, b| key(a).cmp(&key(b))
+}
+
+/// Compares a borrowed field of each element with another comparator. Unlike
+/// [`by_key`] this never clones the field, and the field need not be `Ord`.
+pub fn by_field<T, U, P, C>(field: P, compare: C) -> impl Fn(&T, &T) -> Ordering
+where
+    T: ?Sized,
+    U: ?Sized,
+    P: Fn(&T) -> &U,
+    C: Fn(&U, &U) -> Ordering,
+{
+    move |a, b| compare(field(a), field(b))
+}
+
+/// Natural ("human") ordering: runs of ASCII digits compare by their numeric
+/// value, so `"file9" < "file10"`. Everything else compares by `char`.
+///
+/// Numbers of any length are supported, since digit runs are compared as
+/// strings rather than parsed. Strings that differ only in leading zeros are
+/// ordered by their raw text so that the result is still a total order.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::{compare::natural, Insertion, Sorter};
+///
+/// let mut files = vec!["file10.txt", "file9.txt", "file1.txt"];
+/// Insertion { smart: false }.sort_by(&mut files, |a, b| natural(a, b));
+/// assert_eq!(files, vec!["file1.txt", "file9.txt", "file10.txt"]);
+/// ```
+pub fn natural(a: &str, b: &str) -> Ordering {
+    natural_by(a, b, char::cmp).then_with(|| a.cmp(b))
+}
+
+/// Case-insensitive ordering, using Unicode's locale-independent lowercase
+/// mapping. Strings that differ only in case are ordered by their raw text, so
+/// `"Apple" < "apple" < "Banana"`.
+pub fn case_insensitive(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+        .then_with(|| a.cmp(b))
+}
+
+/// [`natural`] and [`case_insensitive`] at once: `"File9" < "file10"`.
+pub fn natural_case_insensitive(a: &str, b: &str) -> Ordering {
+    natural_by(a, b, |x, y| x.to_lowercase().cmp(y.to_lowercase())).then_with(|| a.cmp(b))
+}
+
+fn natural_by<F>(mut a: &str, mut b: &str, compare_chars: F) -> Ordering
+where
+    F: Fn(&char, &char) -> Ordering,
+{
+    loop {
+        let (x, y) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y),
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (x, rest_a) = split_digits(a);
+            let (y, rest_b) = split_digits(b);
+            let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+            // with no leading zeros, a longer number is a bigger number
+            let ord = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            a = rest_a;
+            b = rest_b;
+        } else {
+            let ord = compare_chars(&x, &y);
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+        }
+    }
+}
+
+/// Splits off the leading run of ASCII digits
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort::{Quick, Sorter};
+
+    #[test]
+    fn natural_orders_digit_runs_numerically() {
+        let mut v = vec![
+            "img12.png",
+            "img10.png",
+            "IMG2.png",
+            "img2.png",
+            "img1.png",
+            "img02.png",
+            "img",
+            "img100000000000000000000000.png",
+        ];
+        Quick.sort_by(&mut v, |a, b| natural(a, b));
+        assert_eq!(
+            v,
+            vec![
+                "IMG2.png",
+                "img",
+                "img1.png",
+                "img02.png",
+                "img2.png",
+                "img10.png",
+                "img12.png",
+                "img100000000000000000000000.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn natural_case_insensitive_interleaves_cases() {
+        let mut v = vec!["file10", "File9", "file9", "FILE1"];
+        Quick.sort_by(&mut v, |a, b| natural_case_insensitive(a, b));
+        assert_eq!(v, vec!["FILE1", "File9", "file9", "file10"]);
+    }
+
+    #[test]
+    fn case_insensitive_is_total() {
+        let mut v = vec!["banana", "Apple", "apple", "Äpfel", "äpfel"];
+        Quick.sort_by(&mut v, |a, b| case_insensitive(a, b));
+        assert_eq!(v, vec!["Apple", "apple", "banana", "Äpfel", "äpfel"]);
+        assert_eq!(case_insensitive("ABC", "abc"), Ordering::Less);
+        assert_eq!(case_insensitive("abc", "abc"), Ordering::Equal);
+    }
+
+    #[test]
+    fn nulls_first_and_last() {
+        let mut v = vec![Some(2), None, Some(1), None];
+        Quick.sort_by(&mut v, i32::cmp.nulls_first());
+        assert_eq!(v, vec![None, None, Some(1), Some(2)]);
+        Quick.sort_by(&mut v, i32::cmp.reversed().nulls_last());
+        assert_eq!(v, vec![Some(2), Some(1), None, None]);
+    }
+
+    #[test]
+    fn then_by_only_breaks_ties() {
+        let mut v = vec![(1, 'b'), (0, 'z'), (1, 'a')];
+        let order = by_key(|p: &(i32, char)| p.0).then_by(by_key(|p: &(i32, char)| p.1));
+        Quick.sort_by(&mut v, order);
+        assert_eq!(v, vec![(0, 'z'), (1, 'a'), (1, 'b')]);
+    }
+}
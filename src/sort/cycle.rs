@@ -0,0 +1,146 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Cycle sort][1]. Slow (always `O(n²)` comparisons), but every element is
+//! written at most once, directly into its final position, which is provably
+//! the minimum number of writes for an in-place sort. Useful when writes are
+//! far more expensive than reads, e.g. flash or memory-mapped storage.
+//!
+//! Each misplaced element is picked up and dropped into its final slot; the
+//! element that was displaced is picked up next, and so on until the cycle
+//! comes back around to the starting slot.
+//! [1]: https://en.wikipedia.org/wiki/Cycle_sort
+use super::*;
+use std::{mem::ManuallyDrop, ptr};
+
+pub struct Cycle;
+
+/// The element currently "in hand". While it is held, its original slot in the
+/// slice is a hole (a stale bitwise copy) which must be filled before anyone
+/// else can observe the slice. If a comparison panics, dropping the `Hole`
+/// writes the held element back so nothing is leaked or dropped twice.
+struct Hole<'a, T> {
+    item: ManuallyDrop<T>,
+    slice: &'a mut [T],
+    start: usize,
+}
+
+impl<T> Drop for Hole<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::write(
+                &mut self.slice[self.start],
+                ManuallyDrop::take(&mut self.item),
+            )
+        };
+    }
+}
+
+/// The final position of `item` if the cycle starts at `start`: `start` plus
+/// the number of elements after it that are smaller
+fn position<T, F>(slice: &[T], start: usize, item: &T, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let smaller = slice[start + 1..]
+        .iter()
+        .filter(|&x| compare(x, item) == Ordering::Less)
+        .count();
+    start + smaller
+}
+
+impl CountWrites for Cycle {
+    fn sort_by_counting_writes<T, F>(&self, slice: &mut [T], mut compare: F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut writes = 0;
+        for start in 0..slice.len().saturating_sub(1) {
+            let mut pos = position(slice, start, &slice[start], &mut compare);
+            if pos == start {
+                continue;
+            }
+
+            // SAFETY: `slice[start]` is only read (never dropped or handed out
+            // mutably) until the hole is filled, either at the end of the cycle
+            // or by `Hole::drop` on unwind.
+            let item = ManuallyDrop::new(unsafe { ptr::read(&slice[start]) });
+            let mut hole = Hole {
+                item,
+                slice: &mut *slice,
+                start,
+            };
+            while pos != start {
+                // skip past any duplicates that are already in place
+                while compare(&hole.slice[pos], &hole.item) == Ordering::Equal {
+                    pos += 1;
+                }
+                std::mem::swap(&mut *hole.item, &mut hole.slice[pos]);
+                writes += 1;
+                pos = position(hole.slice, start, &hole.item, &mut compare);
+            }
+            // the cycle is closed: dropping `hole` fills `slice[start]`
+            drop(hole);
+            writes += 1;
+        }
+        writes
+    }
+}
+
+impl Sorter for Cycle {
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.sort_by_counting_writes(slice, compare);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let mut items = vec![4, 2, 3, 1];
+        Cycle.sort(&mut items);
+        assert_eq!(items, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn handles_duplicates() {
+        let mut items = vec![3, 1, 3, 2, 1, 3, 0];
+        Cycle.sort(&mut items);
+        assert_eq!(items, &[0, 1, 1, 2, 3, 3, 3]);
+    }
+
+    #[test]
+    fn writes_each_misplaced_element_once() {
+        // 0 and 4 are already in place, the other three form a single cycle
+        let mut items = vec![0, 3, 1, 2, 4];
+        assert_eq!(Cycle.sort_counting_writes(&mut items), 3);
+        assert_eq!(items, &[0, 1, 2, 3, 4]);
+
+        let mut sorted: Vec<_> = (0..10).collect();
+        assert_eq!(Cycle.sort_counting_writes(&mut sorted), 0);
+    }
+
+    #[test]
+    fn owned_values_are_not_dropped_twice() {
+        let mut items: Vec<String> = ["d", "b", "a", "c", "b"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        Cycle.sort(&mut items);
+        assert_eq!(items, &["a", "b", "b", "c", "d"]);
+    }
+}
@@ -0,0 +1,218 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sorting floating point numbers. `f32` and `f64` are only `PartialOrd`,
+//! because `NaN` compares as neither less than, equal to, nor greater than
+//! anything (itself included), so none of the [`Sorter`]s accept them directly.
+//!
+//! [`TotalF64`] and [`TotalF32`] wrap a float and order it by the IEEE 754
+//! [totalOrder][1] predicate, which is a genuine total order:
+//!
+//! ```text
+//! -NaN < -∞ < ... < -0.0 < +0.0 < ... < +∞ < +NaN
+//! ```
+//!
+//! Both wrappers are `#[repr(transparent)]`, so a `&mut [f64]` can be viewed as
+//! a `&mut [TotalF64]` in place; [`sort_f64`] and [`sort_f32`] use this to sort
+//! float slices with any [`Sorter`] without allocating.
+//!
+//! [1]: https://en.wikipedia.org/wiki/IEEE_754#Total-ordering_predicate
+use super::*;
+use std::cmp::Ordering;
+
+/// Where `NaN`s end up when sorting a float slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NanPlacement {
+    /// Follow IEEE 754 totalOrder: negative `NaN`s sort before `-∞` and
+    /// positive `NaN`s sort after `+∞`
+    Total,
+    /// Every `NaN` sorts before every number, regardless of its sign bit
+    First,
+    /// Every `NaN` sorts after every number, regardless of its sign bit
+    Last,
+}
+
+macro_rules! total_float {
+    ($name:ident, $float:ty, $sort:ident, $doc:expr) => {
+        #[doc = $doc]
+        #[repr(transparent)]
+        #[derive(Debug, Default, Clone, Copy)]
+        pub struct $name(pub $float);
+
+        impl $name {
+            /// Views a float slice as a slice of totally ordered wrappers,
+            /// without copying.
+            pub fn from_mut_slice(slice: &mut [$float]) -> &mut [$name] {
+                // SAFETY: `$name` is `#[repr(transparent)]` over `$float`, so
+                // the two slices have identical layouts.
+                unsafe { &mut *(slice as *mut [$float] as *mut [$name]) }
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        /// Consistent with `Ord`, so unlike `f64`, `-0.0 != +0.0` and a `NaN`
+        /// is equal to another `NaN` with the same bit pattern.
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl From<$float> for $name {
+            fn from(f: $float) -> Self {
+                $name(f)
+            }
+        }
+
+        /// Sorts a float slice in place using `sorter`, putting `NaN`s where
+        /// `nans` says.
+        pub fn $sort<S: Sorter>(sorter: &S, slice: &mut [$float], nans: NanPlacement) {
+            let numbers = match nans {
+                NanPlacement::Total => slice,
+                NanPlacement::First => {
+                    let n = partition_nans(slice, true);
+                    &mut slice[n..]
+                }
+                NanPlacement::Last => {
+                    let n = partition_nans(slice, false);
+                    let len = slice.len();
+                    &mut slice[..len - n]
+                }
+            };
+            sorter.sort($name::from_mut_slice(numbers));
+        }
+
+        impl FloatBits for $float {
+            fn is_nan(&self) -> bool {
+                <$float>::is_nan(*self)
+            }
+        }
+    };
+}
+
+total_float!(
+    TotalF64,
+    f64,
+    sort_f64,
+    "An `f64` ordered by IEEE 754 totalOrder. See the [module docs](self)."
+);
+total_float!(
+    TotalF32,
+    f32,
+    sort_f32,
+    "An `f32` ordered by IEEE 754 totalOrder. See the [module docs](self)."
+);
+
+/// Just enough of a float to move its `NaN`s around
+trait FloatBits {
+    fn is_nan(&self) -> bool;
+}
+
+/// Moves every `NaN` to the front (or back) of the slice, returning how many
+/// there were. The relative order of everything else is not preserved, which
+/// is fine since it is about to be sorted anyway.
+fn partition_nans<F: FloatBits>(slice: &mut [F], front: bool) -> usize {
+    let len = slice.len();
+    let mut nans = 0;
+    if front {
+        for i in 0..len {
+            if slice[i].is_nan() {
+                slice.swap(i, nans);
+                nans += 1;
+            }
+        }
+    } else {
+        for i in (0..len).rev() {
+            if slice[i].is_nan() {
+                slice.swap(i, len - 1 - nans);
+                nans += 1;
+            }
+        }
+    }
+    nans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(v: &[f64]) -> Vec<u64> {
+        v.iter().map(|f| f.to_bits()).collect()
+    }
+
+    #[test]
+    fn total_order_places_signed_nans_at_both_ends() {
+        let mut v = vec![1.0, f64::NAN, -0.0, f64::NEG_INFINITY, 0.0, -f64::NAN, -1.5];
+        sort_f64(&Quick, &mut v, NanPlacement::Total);
+        assert!(v[0].is_nan() && v[0].is_sign_negative());
+        assert_eq!(
+            bits(&v[1..6]),
+            bits(&[f64::NEG_INFINITY, -1.5, -0.0, 0.0, 1.0])
+        );
+        assert!(v[6].is_nan() && v[6].is_sign_positive());
+    }
+
+    #[test]
+    fn nans_first_and_last_ignore_the_sign_bit() {
+        let input = vec![2.0, -f64::NAN, 1.0, f64::NAN, -3.0];
+
+        let mut v = input.clone();
+        sort_f64(&Insertion { smart: true }, &mut v, NanPlacement::First);
+        assert!(v[0].is_nan() && v[1].is_nan());
+        assert_eq!(&v[2..], &[-3.0, 1.0, 2.0]);
+
+        let mut v = input;
+        sort_f64(&Selection, &mut v, NanPlacement::Last);
+        assert_eq!(&v[..3], &[-3.0, 1.0, 2.0]);
+        assert!(v[3].is_nan() && v[4].is_nan());
+    }
+
+    #[test]
+    fn f32_and_every_sorter() {
+        let input = vec![0.5f32, -2.0, f32::NAN, 8.25, f32::INFINITY, -0.0];
+        let expected = [-2.0, -0.0, 0.5, 8.25, f32::INFINITY];
+        let check = |v: Vec<f32>| {
+            assert_eq!(&v[..5], &expected);
+            assert!(v[5].is_nan());
+        };
+
+        let mut v = input.clone();
+        sort_f32(&Bubble, &mut v, NanPlacement::Last);
+        check(v);
+        let mut v = input.clone();
+        sort_f32(&Quick, &mut v, NanPlacement::Last);
+        check(v);
+        let mut v = input;
+        sort_f32(&Cycle, &mut v, NanPlacement::Total);
+        check(v);
+    }
+
+    #[test]
+    fn wrapper_equality_follows_total_order() {
+        assert_ne!(TotalF64(0.0), TotalF64(-0.0));
+        assert_eq!(TotalF64(f64::NAN), TotalF64(f64::NAN));
+        assert!(TotalF32(-0.0) < TotalF32(0.0));
+    }
+}
@@ -19,19 +19,20 @@ pub struct Insertion {
     pub smart: bool,
 }
 impl Sorter for Insertion {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
         for unsorted in 1..slice.len() {
             if !self.smart {
                 let mut i = unsorted;
-                while i > 0 && slice[i - 1] > slice[i] {
+                while i > 0 && compare(&slice[i - 1], &slice[i]) == Ordering::Greater {
                     slice.swap(i, i - 1);
                     i -= 1;
                 }
             } else {
-                let i = match slice[..unsorted].binary_search(&slice[unsorted]) {
+                let (sorted, rest) = slice.split_at(unsorted);
+                let i = match sorted.binary_search_by(|x| compare(x, &rest[0])) {
                     Ok(i) => i,  // match found, returning found index
                     Err(i) => i, // no match, returning appropriate index
                 };
@@ -0,0 +1,361 @@
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utilities for combining data that is _already_ sorted: a lazy [k-way
+//! merge][1] over any number of sorted iterators, and the classic set
+//! operations over sorted slices. All of these are linear in the size of their
+//! inputs (plus a `log k` factor for the k-way merge), which is the whole point
+//! of having sorted the data in the first place.
+//!
+//! [1]: https://en.wikipedia.org/wiki/K-way_merge_algorithm
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt::{self, Debug},
+};
+
+/// The head of one of the source iterators, as it sits in the heap
+struct Head<T> {
+    item: T,
+    source: usize,
+}
+
+// `BinaryHeap` is a max-heap, so the ordering is inverted here: the smallest
+// item wins, and on ties the item from the _earlier_ source wins, which is what
+// makes the merge stable.
+impl<T: Ord> Ord for Head<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .item
+            .cmp(&self.item)
+            .then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl<T: Ord> PartialOrd for Head<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> PartialEq for Head<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Head<T> {}
+
+/// A lazy merge of `k` sorted iterators, created by [`kmerge`]. Each call to
+/// `next` costs `O(log k)`, and only one item per source is held at a time.
+pub struct KMerge<I>
+where
+    I: Iterator,
+{
+    sources: Vec<I>,
+    heap: BinaryHeap<Head<I::Item>>,
+}
+
+impl<I> Debug for KMerge<I>
+where
+    I: Iterator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KMerge")
+            .field("sources", &self.sources.len())
+            .field("pending", &self.heap.len())
+            .finish()
+    }
+}
+
+/// Merges any number of sorted iterators into a single sorted iterator.
+///
+/// Items that compare equal are yielded in the order of the sources they came
+/// from, so merging already-stable runs produces a stable result.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::merge::kmerge;
+///
+/// let a = vec![1, 4, 7];
+/// let b = vec![2, 5, 8];
+/// let c = vec![3, 6, 9];
+/// let merged: Vec<_> = kmerge(vec![a, b, c]).collect();
+/// assert_eq!(merged, (1..=9).collect::<Vec<_>>());
+/// ```
+pub fn kmerge<I>(sources: I) -> KMerge<<I::Item as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    let mut sources: Vec<_> = sources.into_iter().map(IntoIterator::into_iter).collect();
+    let mut heap = BinaryHeap::with_capacity(sources.len());
+    for (source, it) in sources.iter_mut().enumerate() {
+        if let Some(item) = it.next() {
+            heap.push(Head { item, source });
+        }
+    }
+    KMerge { sources, heap }
+}
+
+impl<I> Iterator for KMerge<I>
+where
+    I: Iterator,
+    I::Item: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Head { item, source } = self.heap.pop()?;
+        if let Some(next) = self.sources[source].next() {
+            self.heap.push(Head { item: next, source });
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.heap.len();
+        self.sources
+            .iter()
+            .fold((pending, Some(pending)), |(lo, hi), it| {
+                let (l, h) = it.size_hint();
+                (
+                    lo.saturating_add(l),
+                    hi.and_then(|hi| h.and_then(|h| hi.checked_add(h))),
+                )
+            })
+    }
+}
+
+/// The values that appear in either `a` or `b`.
+///
+/// Both inputs must be sorted. Values that appear several times are treated
+/// as a multiset: the output keeps the larger of the two multiplicities.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::merge::union;
+///
+/// assert_eq!(union(&[1, 3, 5], &[2, 3, 4]), vec![1, 2, 3, 4, 5]);
+/// ```
+pub fn union<T: Ord + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => {
+                out.push(a[i].clone());
+                i += 1;
+            }
+            Ordering::Greater => {
+                out.push(b[j].clone());
+                j += 1;
+            }
+            Ordering::Equal => {
+                out.push(a[i].clone());
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+/// The values that appear in both `a` and `b`. Both inputs must be sorted.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::merge::intersection;
+///
+/// assert_eq!(intersection(&[1, 2, 3, 4], &[2, 4, 6]), vec![2, 4]);
+/// ```
+pub fn intersection<T: Ord + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                out.push(a[i].clone());
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The values of `a` that do not appear in `b`. Both inputs must be sorted.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::merge::difference;
+///
+/// assert_eq!(difference(&[1, 2, 3, 4], &[2, 4, 6]), vec![1, 3]);
+/// ```
+pub fn difference<T: Ord + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => {
+                out.push(a[i].clone());
+                i += 1;
+            }
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out
+}
+
+/// The values that appear in exactly one of `a` and `b`. Both inputs must be
+/// sorted.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::merge::symmetric_difference;
+///
+/// assert_eq!(symmetric_difference(&[1, 2, 3, 4], &[2, 4, 6]), vec![1, 3, 6]);
+/// ```
+pub fn symmetric_difference<T: Ord + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => {
+                out.push(a[i].clone());
+                i += 1;
+            }
+            Ordering::Greater => {
+                out.push(b[j].clone());
+                j += 1;
+            }
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+/// Removes consecutive duplicates from a sorted `Vec`, keeping the first of
+/// each run. Unlike [`Vec::dedup`] this is written out as a single
+/// read/write-cursor pass so the moves are easy to follow.
+///
+/// ## Example:
+/// ```
+/// use tinystd::sort::merge::dedup_sorted;
+///
+/// let mut v = vec![1, 1, 2, 3, 3, 3, 4];
+/// dedup_sorted(&mut v);
+/// assert_eq!(v, vec![1, 2, 3, 4]);
+/// ```
+pub fn dedup_sorted<T: PartialEq>(v: &mut Vec<T>) {
+    if v.len() < 2 {
+        return;
+    }
+    // `write` is the index of the last kept element
+    let mut write = 0;
+    for read in 1..v.len() {
+        if v[read] != v[write] {
+            write += 1;
+            v.swap(read, write);
+        }
+    }
+    v.truncate(write + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmerge_handles_empty_and_uneven_sources() {
+        let sources: Vec<Vec<i32>> = vec![vec![], vec![5], vec![1, 2, 3, 10], vec![]];
+        let merged: Vec<_> = kmerge(sources).collect();
+        assert_eq!(merged, vec![1, 2, 3, 5, 10]);
+
+        let none: Vec<Vec<i32>> = vec![];
+        assert_eq!(kmerge(none).next(), None);
+    }
+
+    #[test]
+    fn kmerge_is_stable_by_source_index() {
+        // compare only on the key, so the tag records which source won the tie
+        #[derive(Debug, PartialEq, Eq)]
+        struct Tagged(u8, &'static str);
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Tagged {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let a = vec![Tagged(1, "a"), Tagged(2, "a")];
+        let b = vec![Tagged(1, "b"), Tagged(2, "b")];
+        let c = vec![Tagged(1, "c")];
+        let tags: Vec<_> = kmerge(vec![a, b, c]).map(|t| t.1).collect();
+        assert_eq!(tags, vec!["a", "b", "c", "a", "b"]);
+    }
+
+    #[test]
+    fn kmerge_size_hint_is_exact_for_vecs() {
+        let merged = kmerge(vec![vec![1, 2], vec![3], vec![4, 5, 6]]);
+        assert_eq!(merged.size_hint(), (6, Some(6)));
+    }
+
+    #[test]
+    fn set_operations_with_duplicates() {
+        let a = [1, 1, 2, 4];
+        let b = [1, 2, 2, 3];
+        assert_eq!(union(&a, &b), vec![1, 1, 2, 2, 3, 4]);
+        assert_eq!(intersection(&a, &b), vec![1, 2]);
+        assert_eq!(difference(&a, &b), vec![1, 4]);
+        assert_eq!(symmetric_difference(&a, &b), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn set_operations_with_empty_inputs() {
+        let empty: [i32; 0] = [];
+        assert_eq!(union(&empty, &[1, 2]), vec![1, 2]);
+        assert_eq!(intersection(&empty, &[1, 2]), Vec::<i32>::new());
+        assert_eq!(difference(&[1, 2], &empty), vec![1, 2]);
+        assert_eq!(symmetric_difference(&empty, &[1]), vec![1]);
+    }
+
+    #[test]
+    fn dedup_sorted_keeps_first_of_each_run() {
+        let mut v = vec!["a", "a", "b", "c", "c"];
+        dedup_sorted(&mut v);
+        assert_eq!(v, vec!["a", "b", "c"]);
+
+        let mut single = vec![7];
+        dedup_sorted(&mut single);
+        assert_eq!(single, vec![7]);
+    }
+}
@@ -17,14 +17,15 @@ use super::*;
 pub struct Quick;
 
 /// The well-known recursive sorting algorithm
-pub fn quicksort<T: Ord>(slice: &mut [T]) {
+pub fn quicksort<T, F>(slice: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     match slice.len() {
         0 | 1 => return,
-        2 => {
-            if slice[0] > slice[1] {
-                slice.swap(0, 1);
-                return;
-            }
+        2 if compare(&slice[0], &slice[1]) == Ordering::Greater => {
+            slice.swap(0, 1);
+            return;
         }
         _ => (),
     }
@@ -34,9 +35,9 @@ pub fn quicksort<T: Ord>(slice: &mut [T]) {
     let mut right = rest.len() - 1;
 
     while left <= right {
-        if &rest[left] <= pivot {
+        if compare(&rest[left], pivot) != Ordering::Greater {
             left += 1;
-        } else if &rest[right] > pivot {
+        } else if compare(&rest[right], pivot) == Ordering::Greater {
             if right == 0 {
                 break;
             }
@@ -57,16 +58,16 @@ pub fn quicksort<T: Ord>(slice: &mut [T]) {
 
     // recurse
     let (left, right) = slice.split_at_mut(left - 1);
-    quicksort(left);
-    quicksort(&mut right[1..]);
+    quicksort(left, compare);
+    quicksort(&mut right[1..], compare);
 }
 
 impl Sorter for Quick {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
-        quicksort(slice);
+        quicksort(slice, &mut compare);
     }
 }
 
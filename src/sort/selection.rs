@@ -11,26 +11,40 @@
 // limitations under the License.
 
 //! [Selection sort][1]. Not very good, worse than [`crate::sort::insertion`] but at least has `O(1)` space complexity.
+//! It also does at most `n - 1` swaps, so it writes to the slice far less often
+//! than the other quadratic sorts; only [`crate::sort::Cycle`] does better.
 //! [1]: https://en.wikipedia.org/wiki/Selection_sort
 use super::*;
 
 pub struct Selection;
-impl Sorter for Selection {
-    fn sort<T>(&self, slice: &mut [T])
+impl CountWrites for Selection {
+    fn sort_by_counting_writes<T, F>(&self, slice: &mut [T], mut compare: F) -> usize
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
+        let mut writes = 0;
         for unsorted in 0..slice.len() {
             let min_rest = slice[unsorted..]
                 .iter()
                 .enumerate()
-                .min_by_key(|&(_, v)| v)
+                .min_by(|&(_, a), &(_, b)| compare(a, b))
                 .map(|(i, _)| unsorted + i)
                 .expect("slice must be non-empty");
             if unsorted != min_rest {
                 slice.swap(unsorted, min_rest);
+                writes += 2;
             }
         }
+        writes
+    }
+}
+
+impl Sorter for Selection {
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.sort_by_counting_writes(slice, compare);
     }
 }
 
@@ -44,4 +58,11 @@ mod tests {
         Selection.sort(&mut items);
         assert_eq!(items, &[1, 2, 3, 4]);
     }
+
+    #[test]
+    fn counts_two_writes_per_swap() {
+        let mut items = vec![4, 2, 3, 1];
+        assert_eq!(Selection.sort_counting_writes(&mut items), 2);
+        assert_eq!(items, &[1, 2, 3, 4]);
+    }
 }